@@ -0,0 +1,23 @@
+-- Drops everything 0001_initial.up.sql created, data included. Dropping the
+-- tables takes their indexes, triggers and policies with them. The uuid-ossp
+-- extension stays, since other schemas in the database may rely on it.
+
+-- This policy on chats reads chat_participants, so it has to go before that
+-- table can be dropped
+DROP POLICY "Users can view chats they participate in" ON chats;
+
+DROP TABLE user_sessions;
+DROP TABLE files;
+DROP TABLE notifications;
+DROP TABLE messages;
+DROP TABLE chat_participants;
+DROP TABLE chats;
+DROP TABLE post_comments;
+DROP TABLE post_likes;
+DROP TABLE posts;
+DROP TABLE users;
+
+DROP FUNCTION update_updated_at_column();
+
+DROP TYPE message_type;
+DROP TYPE chat_type;
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_notifications_user_id_created_at;
+DROP INDEX IF EXISTS idx_messages_chat_id_created_at;
+DROP INDEX IF EXISTS idx_post_comments_post_id_created_at;
+DROP INDEX IF EXISTS idx_posts_created_at_id;
+CREATE INDEX idx_posts_created_at ON posts(created_at DESC);
\ No newline at end of file
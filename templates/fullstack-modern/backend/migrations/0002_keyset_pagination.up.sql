@@ -0,0 +1,6 @@
+-- Indexes for keyset pagination on (created_at, id), see models::Cursor
+DROP INDEX IF EXISTS idx_posts_created_at;
+CREATE INDEX idx_posts_created_at_id ON posts(created_at DESC, id DESC);
+CREATE INDEX idx_post_comments_post_id_created_at ON post_comments(post_id, created_at DESC, id DESC);
+CREATE INDEX idx_messages_chat_id_created_at ON messages(chat_id, created_at DESC, id DESC);
+CREATE INDEX idx_notifications_user_id_created_at ON notifications(user_id, created_at DESC, id DESC);
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Soft delete for posts: hidden from reads until restored or purged
+ALTER TABLE posts ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+-- The purge job only scans deleted posts
+CREATE INDEX idx_posts_deleted_at ON posts(deleted_at) WHERE deleted_at IS NOT NULL;
\ No newline at end of file
@@ -0,0 +1,14 @@
+-- One row per edit, holding the title and content the edit replaced
+CREATE TABLE post_revisions (
+    post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    editor_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(200) NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (post_id, revision)
+);
+
+ALTER TABLE post_revisions ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Post revisions are viewable by everyone" ON post_revisions FOR SELECT USING (true);
@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS idx_post_likes_post_id_created_at;
+
+DROP TRIGGER update_posts_updated_at ON posts;
+CREATE TRIGGER update_posts_updated_at BEFORE UPDATE ON posts
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+DROP TRIGGER IF EXISTS post_comments_count ON post_comments;
+DROP TRIGGER IF EXISTS post_likes_count ON post_likes;
+DROP FUNCTION IF EXISTS update_post_comments_count();
+DROP FUNCTION IF EXISTS update_post_likes_count();
+
+ALTER TABLE posts DROP COLUMN comments_count;
+ALTER TABLE posts DROP COLUMN likes_count;
@@ -0,0 +1,48 @@
+-- Denormalized like and comment counts, kept in step by triggers so post
+-- reads no longer aggregate post_likes and post_comments
+ALTER TABLE posts ADD COLUMN likes_count BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE posts ADD COLUMN comments_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE posts p SET
+    likes_count = (SELECT COUNT(*) FROM post_likes WHERE post_id = p.id),
+    comments_count = (SELECT COUNT(*) FROM post_comments WHERE post_id = p.id);
+
+CREATE OR REPLACE FUNCTION update_post_likes_count()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE posts SET likes_count = likes_count + 1 WHERE id = NEW.post_id;
+    ELSE
+        UPDATE posts SET likes_count = likes_count - 1 WHERE id = OLD.post_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION update_post_comments_count()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE posts SET comments_count = comments_count + 1 WHERE id = NEW.post_id;
+    ELSE
+        UPDATE posts SET comments_count = comments_count - 1 WHERE id = OLD.post_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER post_likes_count AFTER INSERT OR DELETE ON post_likes
+    FOR EACH ROW EXECUTE FUNCTION update_post_likes_count();
+
+CREATE TRIGGER post_comments_count AFTER INSERT OR DELETE ON post_comments
+    FOR EACH ROW EXECUTE FUNCTION update_post_comments_count();
+
+-- A like or comment doesn't count as an edit of the post
+DROP TRIGGER update_posts_updated_at ON posts;
+CREATE TRIGGER update_posts_updated_at BEFORE UPDATE ON posts
+    FOR EACH ROW
+    WHEN (OLD.likes_count = NEW.likes_count AND OLD.comments_count = NEW.comments_count)
+    EXECUTE FUNCTION update_updated_at_column();
+
+-- Likers of a post, most recent first
+CREATE INDEX idx_post_likes_post_id_created_at ON post_likes(post_id, created_at DESC, user_id DESC);
@@ -0,0 +1,25 @@
+-- Directed edges between users: 'follow' puts the target's posts in the
+-- user's home feed, 'mute' keeps a followed user out of it, 'block' forbids
+-- following in either direction
+CREATE TYPE follow_kind AS ENUM ('follow', 'mute', 'block');
+
+CREATE TABLE follows (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    target_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind follow_kind NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, target_id, kind),
+    CHECK (user_id <> target_id)
+);
+
+-- Followers of a user, most recent first
+CREATE INDEX idx_follows_target_id ON follows(target_id, kind, created_at DESC, user_id DESC);
+-- Following lists and the home feed
+CREATE INDEX idx_follows_user_id ON follows(user_id, kind, created_at DESC, target_id DESC);
+-- A user's posts in a home feed, newest first
+CREATE INDEX idx_posts_author_id_created_at ON posts(author_id, created_at DESC, id DESC);
+
+ALTER TABLE follows ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Follows are viewable by everyone, mutes and blocks by their owner" ON follows FOR SELECT USING (kind = 'follow' OR auth.uid() = user_id);
+CREATE POLICY "Users can manage their own follows" ON follows FOR ALL USING (auth.uid() = user_id);
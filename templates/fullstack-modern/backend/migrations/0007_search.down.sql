@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS idx_messages_search_vector;
+DROP INDEX IF EXISTS idx_users_search_vector;
+DROP INDEX IF EXISTS idx_posts_search_vector;
+
+ALTER TABLE messages DROP COLUMN search_vector;
+ALTER TABLE users DROP COLUMN search_vector;
+ALTER TABLE posts DROP COLUMN search_vector;
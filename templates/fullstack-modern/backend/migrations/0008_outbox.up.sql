@@ -0,0 +1,23 @@
+-- Domain events, written in the same transaction as the change they describe
+-- and relayed to consumers afterwards. dedup_key names the change (e.g.
+-- 'post_created:<id>'), so recording it twice is a no-op and consumers can
+-- drop redeliveries.
+CREATE TABLE outbox (
+    id BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    dedup_key TEXT NOT NULL UNIQUE,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- Claimed by a relay until then; handed out again if it never reports back
+    locked_until TIMESTAMP WITH TIME ZONE,
+    published_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Undelivered events, oldest first
+CREATE INDEX idx_outbox_pending ON outbox(id) WHERE published_at IS NULL;
+-- Purging delivered events
+CREATE INDEX idx_outbox_published_at ON outbox(published_at) WHERE published_at IS NOT NULL;
+
+-- Only the server touches the outbox
+ALTER TABLE outbox ENABLE ROW LEVEL SECURITY;
@@ -0,0 +1,42 @@
+-- Append-only record of security-relevant and administrative actions. Each
+-- entry's hash covers its contents and the previous entry's hash, so editing
+-- or removing entries breaks the chain. actor_id and target_id deliberately
+-- have no foreign keys: entries outlive the users and posts they mention.
+CREATE TABLE audit_events (
+    id UUID PRIMARY KEY,
+    seq BIGINT NOT NULL UNIQUE,
+    occurred_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    action TEXT NOT NULL,
+    -- NULL for the admin CLI
+    actor_id UUID,
+    target_type TEXT,
+    target_id UUID,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    before JSONB,
+    after JSONB,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+
+CREATE INDEX idx_audit_events_occurred_at ON audit_events(occurred_at DESC, id DESC);
+CREATE INDEX idx_audit_events_actor_id ON audit_events(actor_id, occurred_at DESC, id DESC);
+CREATE INDEX idx_audit_events_target_id ON audit_events(target_id, occurred_at DESC, id DESC);
+CREATE INDEX idx_audit_events_action ON audit_events(action, occurred_at DESC, id DESC);
+
+CREATE OR REPLACE FUNCTION forbid_audit_event_changes()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION forbid_audit_event_changes();
+
+CREATE TRIGGER audit_events_no_truncate BEFORE TRUNCATE ON audit_events
+    FOR EACH STATEMENT EXECUTE FUNCTION forbid_audit_event_changes();
+
+-- Only the server touches the audit log
+ALTER TABLE audit_events ENABLE ROW LEVEL SECURITY;
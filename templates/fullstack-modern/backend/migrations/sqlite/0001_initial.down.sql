@@ -0,0 +1,12 @@
+-- Drops everything 0001_initial.up.sql created, data included. Dropping the
+-- tables takes their indexes and triggers with them.
+DROP TABLE user_sessions;
+DROP TABLE files;
+DROP TABLE notifications;
+DROP TABLE messages;
+DROP TABLE chat_participants;
+DROP TABLE chats;
+DROP TABLE post_comments;
+DROP TABLE post_likes;
+DROP TABLE posts;
+DROP TABLE users;
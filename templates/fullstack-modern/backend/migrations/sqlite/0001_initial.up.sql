@@ -0,0 +1,163 @@
+-- SQLite equivalent of ../0001_initial.sql (used with the `sqlite` cargo feature).
+-- UUIDs are stored as 16-byte BLOBs, enums as CHECK-constrained TEXT and
+-- timestamps as RFC 3339 TEXT in UTC. There is no RLS: access checks live in
+-- the handlers, as they do for the service-role Postgres connection. Foreign
+-- keys are enforced per connection (see `SqliteDatabase::new`).
+
+-- Users table (synced with Supabase auth.users)
+CREATE TABLE users (
+    id BLOB PRIMARY KEY,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    username VARCHAR(50) UNIQUE,
+    full_name VARCHAR(100),
+    avatar_url TEXT,
+    bio TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Posts table
+CREATE TABLE posts (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    title VARCHAR(200) NOT NULL,
+    content TEXT NOT NULL,
+    author_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Post likes table
+CREATE TABLE post_likes (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    post_id BLOB NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE(user_id, post_id)
+);
+
+-- Post comments table
+CREATE TABLE post_comments (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    content TEXT NOT NULL,
+    author_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    post_id BLOB NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    parent_id BLOB REFERENCES post_comments(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Chats table
+CREATE TABLE chats (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    name VARCHAR(100),
+    chat_type TEXT NOT NULL DEFAULT 'direct' CHECK (chat_type IN ('direct', 'group')),
+    created_by BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Chat participants table
+CREATE TABLE chat_participants (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    chat_id BLOB NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    left_at TEXT,
+    UNIQUE(chat_id, user_id)
+);
+
+-- Messages table
+CREATE TABLE messages (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    chat_id BLOB NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    sender_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    message_type TEXT NOT NULL DEFAULT 'text' CHECK (message_type IN ('text', 'image', 'file')),
+    metadata TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Notifications table
+CREATE TABLE notifications (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    notification_type VARCHAR(50) NOT NULL,
+    title VARCHAR(100) NOT NULL,
+    message TEXT NOT NULL,
+    read BOOLEAN NOT NULL DEFAULT FALSE,
+    metadata TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Files table (for file uploads)
+CREATE TABLE files (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    filename VARCHAR(255) NOT NULL,
+    original_name VARCHAR(255) NOT NULL,
+    mime_type VARCHAR(100) NOT NULL,
+    file_size BIGINT NOT NULL,
+    file_path TEXT NOT NULL,
+    uploaded_by BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- User sessions table (for tracking user activity)
+CREATE TABLE user_sessions (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    session_token TEXT NOT NULL UNIQUE,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    last_accessed TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    ip_address TEXT,
+    user_agent TEXT
+);
+
+-- Create indexes for better performance
+CREATE INDEX idx_posts_author_id ON posts(author_id);
+CREATE INDEX idx_posts_created_at ON posts(created_at DESC);
+CREATE INDEX idx_post_likes_post_id ON post_likes(post_id);
+CREATE INDEX idx_post_likes_user_id ON post_likes(user_id);
+CREATE INDEX idx_post_comments_post_id ON post_comments(post_id);
+CREATE INDEX idx_post_comments_author_id ON post_comments(author_id);
+CREATE INDEX idx_post_comments_parent_id ON post_comments(parent_id);
+
+CREATE INDEX idx_chats_created_by ON chats(created_by);
+CREATE INDEX idx_chat_participants_chat_id ON chat_participants(chat_id);
+CREATE INDEX idx_chat_participants_user_id ON chat_participants(user_id);
+CREATE INDEX idx_messages_chat_id ON messages(chat_id);
+CREATE INDEX idx_messages_sender_id ON messages(sender_id);
+CREATE INDEX idx_messages_created_at ON messages(created_at DESC);
+
+CREATE INDEX idx_notifications_user_id ON notifications(user_id);
+CREATE INDEX idx_notifications_read ON notifications(read);
+CREATE INDEX idx_notifications_created_at ON notifications(created_at DESC);
+
+CREATE INDEX idx_files_uploaded_by ON files(uploaded_by);
+CREATE INDEX idx_files_created_at ON files(created_at DESC);
+
+CREATE INDEX idx_user_sessions_user_id ON user_sessions(user_id);
+CREATE INDEX idx_user_sessions_expires_at ON user_sessions(expires_at);
+
+-- Add updated_at triggers (recursive_triggers is off, so the inner UPDATE
+-- does not fire them again)
+CREATE TRIGGER update_users_updated_at AFTER UPDATE ON users
+    FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+    BEGIN UPDATE users SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id; END;
+
+CREATE TRIGGER update_posts_updated_at AFTER UPDATE ON posts
+    FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+    BEGIN UPDATE posts SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id; END;
+
+CREATE TRIGGER update_post_comments_updated_at AFTER UPDATE ON post_comments
+    FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+    BEGIN UPDATE post_comments SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id; END;
+
+CREATE TRIGGER update_chats_updated_at AFTER UPDATE ON chats
+    FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+    BEGIN UPDATE chats SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id; END;
+
+CREATE TRIGGER update_messages_updated_at AFTER UPDATE ON messages
+    FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+    BEGIN UPDATE messages SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id; END;
@@ -0,0 +1,10 @@
+-- One row per edit, holding the title and content the edit replaced
+CREATE TABLE post_revisions (
+    post_id BLOB NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    editor_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(200) NOT NULL,
+    content TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (post_id, revision)
+);
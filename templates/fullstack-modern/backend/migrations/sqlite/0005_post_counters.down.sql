@@ -0,0 +1,14 @@
+DROP INDEX IF EXISTS idx_post_likes_post_id_created_at;
+
+DROP TRIGGER update_posts_updated_at;
+CREATE TRIGGER update_posts_updated_at AFTER UPDATE ON posts
+    FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+    BEGIN UPDATE posts SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id; END;
+
+DROP TRIGGER IF EXISTS post_comments_count_delete;
+DROP TRIGGER IF EXISTS post_comments_count_insert;
+DROP TRIGGER IF EXISTS post_likes_count_delete;
+DROP TRIGGER IF EXISTS post_likes_count_insert;
+
+ALTER TABLE posts DROP COLUMN comments_count;
+ALTER TABLE posts DROP COLUMN likes_count;
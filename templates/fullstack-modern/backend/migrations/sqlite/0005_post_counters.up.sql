@@ -0,0 +1,30 @@
+-- Denormalized like and comment counts, kept in step by triggers so post
+-- reads no longer aggregate post_likes and post_comments
+ALTER TABLE posts ADD COLUMN likes_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE posts ADD COLUMN comments_count INTEGER NOT NULL DEFAULT 0;
+
+UPDATE posts SET
+    likes_count = (SELECT COUNT(*) FROM post_likes WHERE post_id = posts.id),
+    comments_count = (SELECT COUNT(*) FROM post_comments WHERE post_id = posts.id);
+
+CREATE TRIGGER post_likes_count_insert AFTER INSERT ON post_likes
+    BEGIN UPDATE posts SET likes_count = likes_count + 1 WHERE id = NEW.post_id; END;
+
+CREATE TRIGGER post_likes_count_delete AFTER DELETE ON post_likes
+    BEGIN UPDATE posts SET likes_count = likes_count - 1 WHERE id = OLD.post_id; END;
+
+CREATE TRIGGER post_comments_count_insert AFTER INSERT ON post_comments
+    BEGIN UPDATE posts SET comments_count = comments_count + 1 WHERE id = NEW.post_id; END;
+
+CREATE TRIGGER post_comments_count_delete AFTER DELETE ON post_comments
+    BEGIN UPDATE posts SET comments_count = comments_count - 1 WHERE id = OLD.post_id; END;
+
+-- A like or comment doesn't count as an edit of the post
+DROP TRIGGER update_posts_updated_at;
+CREATE TRIGGER update_posts_updated_at AFTER UPDATE ON posts
+    FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+        AND NEW.likes_count = OLD.likes_count AND NEW.comments_count = OLD.comments_count
+    BEGIN UPDATE posts SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id; END;
+
+-- Likers of a post, most recent first
+CREATE INDEX idx_post_likes_post_id_created_at ON post_likes(post_id, created_at DESC, user_id DESC);
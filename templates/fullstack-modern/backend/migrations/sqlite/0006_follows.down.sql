@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_posts_author_id_created_at;
+DROP TABLE follows;
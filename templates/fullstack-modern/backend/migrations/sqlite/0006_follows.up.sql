@@ -0,0 +1,18 @@
+-- Directed edges between users: 'follow' puts the target's posts in the
+-- user's home feed, 'mute' keeps a followed user out of it, 'block' forbids
+-- following in either direction
+CREATE TABLE follows (
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    target_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('follow', 'mute', 'block')),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (user_id, target_id, kind),
+    CHECK (user_id <> target_id)
+);
+
+-- Followers of a user, most recent first
+CREATE INDEX idx_follows_target_id ON follows(target_id, kind, created_at DESC, user_id DESC);
+-- Following lists and the home feed
+CREATE INDEX idx_follows_user_id ON follows(user_id, kind, created_at DESC, target_id DESC);
+-- A user's posts in a home feed, newest first
+CREATE INDEX idx_posts_author_id_created_at ON posts(author_id, created_at DESC, id DESC);
@@ -0,0 +1,13 @@
+DROP TRIGGER messages_fts_delete;
+DROP TRIGGER messages_fts_update;
+DROP TRIGGER messages_fts_insert;
+DROP TRIGGER users_fts_delete;
+DROP TRIGGER users_fts_update;
+DROP TRIGGER users_fts_insert;
+DROP TRIGGER posts_fts_delete;
+DROP TRIGGER posts_fts_update;
+DROP TRIGGER posts_fts_insert;
+
+DROP TABLE messages_fts;
+DROP TABLE users_fts;
+DROP TABLE posts_fts;
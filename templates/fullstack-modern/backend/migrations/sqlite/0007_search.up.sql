@@ -0,0 +1,52 @@
+-- Full-text indexes, kept in step with their tables by triggers. Each stores
+-- the source row's id unindexed, since FTS5 tables are keyed by their own
+-- rowid. Post and message text is stemmed; user fields match as typed.
+CREATE VIRTUAL TABLE posts_fts USING fts5(
+    id UNINDEXED, title, content, tokenize = 'porter unicode61'
+);
+CREATE VIRTUAL TABLE users_fts USING fts5(
+    id UNINDEXED, username, full_name, bio, tokenize = 'unicode61'
+);
+CREATE VIRTUAL TABLE messages_fts USING fts5(
+    id UNINDEXED, content, tokenize = 'porter unicode61'
+);
+
+INSERT INTO posts_fts (id, title, content) SELECT id, title, content FROM posts;
+INSERT INTO users_fts (id, username, full_name, bio)
+    SELECT id, coalesce(username, ''), coalesce(full_name, ''), coalesce(bio, '') FROM users;
+INSERT INTO messages_fts (id, content) SELECT id, content FROM messages;
+
+CREATE TRIGGER posts_fts_insert AFTER INSERT ON posts
+    BEGIN INSERT INTO posts_fts (id, title, content) VALUES (NEW.id, NEW.title, NEW.content); END;
+
+CREATE TRIGGER posts_fts_update AFTER UPDATE OF title, content ON posts
+    BEGIN UPDATE posts_fts SET title = NEW.title, content = NEW.content WHERE id = OLD.id; END;
+
+CREATE TRIGGER posts_fts_delete AFTER DELETE ON posts
+    BEGIN DELETE FROM posts_fts WHERE id = OLD.id; END;
+
+CREATE TRIGGER users_fts_insert AFTER INSERT ON users
+    BEGIN
+        INSERT INTO users_fts (id, username, full_name, bio)
+        VALUES (NEW.id, coalesce(NEW.username, ''), coalesce(NEW.full_name, ''), coalesce(NEW.bio, ''));
+    END;
+
+CREATE TRIGGER users_fts_update AFTER UPDATE OF username, full_name, bio ON users
+    BEGIN
+        UPDATE users_fts
+        SET username = coalesce(NEW.username, ''), full_name = coalesce(NEW.full_name, ''),
+            bio = coalesce(NEW.bio, '')
+        WHERE id = OLD.id;
+    END;
+
+CREATE TRIGGER users_fts_delete AFTER DELETE ON users
+    BEGIN DELETE FROM users_fts WHERE id = OLD.id; END;
+
+CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages
+    BEGIN INSERT INTO messages_fts (id, content) VALUES (NEW.id, NEW.content); END;
+
+CREATE TRIGGER messages_fts_update AFTER UPDATE OF content ON messages
+    BEGIN UPDATE messages_fts SET content = NEW.content WHERE id = OLD.id; END;
+
+CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages
+    BEGIN DELETE FROM messages_fts WHERE id = OLD.id; END;
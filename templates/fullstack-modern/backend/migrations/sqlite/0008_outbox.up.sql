@@ -0,0 +1,20 @@
+-- Domain events, written in the same transaction as the change they describe
+-- and relayed to consumers afterwards. dedup_key names the change (e.g.
+-- 'post_created:<id>'), so recording it twice is a no-op and consumers can
+-- drop redeliveries.
+CREATE TABLE outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_type TEXT NOT NULL,
+    dedup_key TEXT NOT NULL UNIQUE,
+    payload TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- Claimed by a relay until then; handed out again if it never reports back
+    locked_until TEXT,
+    published_at TEXT
+);
+
+-- Undelivered events, oldest first
+CREATE INDEX idx_outbox_pending ON outbox(id) WHERE published_at IS NULL;
+-- Purging delivered events
+CREATE INDEX idx_outbox_published_at ON outbox(published_at) WHERE published_at IS NOT NULL;
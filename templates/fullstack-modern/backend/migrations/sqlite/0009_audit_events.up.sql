@@ -0,0 +1,36 @@
+-- Append-only record of security-relevant and administrative actions. Each
+-- entry's hash covers its contents and the previous entry's hash, so editing
+-- or removing entries breaks the chain. actor_id and target_id deliberately
+-- have no foreign keys: entries outlive the users and posts they mention.
+CREATE TABLE audit_events (
+    id BLOB PRIMARY KEY,
+    seq INTEGER NOT NULL UNIQUE,
+    occurred_at TEXT NOT NULL,
+    action TEXT NOT NULL,
+    -- NULL for the admin CLI
+    actor_id BLOB,
+    target_type TEXT,
+    target_id BLOB,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    before TEXT,
+    after TEXT,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+
+CREATE INDEX idx_audit_events_occurred_at ON audit_events(occurred_at DESC, id DESC);
+CREATE INDEX idx_audit_events_actor_id ON audit_events(actor_id, occurred_at DESC, id DESC);
+CREATE INDEX idx_audit_events_target_id ON audit_events(target_id, occurred_at DESC, id DESC);
+CREATE INDEX idx_audit_events_action ON audit_events(action, occurred_at DESC, id DESC);
+
+CREATE TRIGGER audit_events_no_update BEFORE UPDATE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
+
+CREATE TRIGGER audit_events_no_delete BEFORE DELETE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
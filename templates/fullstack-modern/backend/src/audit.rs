@@ -0,0 +1,486 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+    routing::get,
+    Json,
+};
+use chrono::{SubsecRound, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    auth::{require_admin, AuthUserWithRole},
+    error::AppResult,
+    models::{
+        ApiResponse, AuditAction, AuditChainReport, AuditEvent, AuditFilter, AuditParams,
+        CursorPage, NewAuditEvent, PageRequest, AUDIT_GENESIS_HASH,
+    },
+    outbox::Dedup,
+    repository::Repository,
+    request_id,
+    routing::Route,
+    services::Services,
+};
+
+// Audit log of security-relevant and administrative actions. Every entry is
+// appended to `audit_events` with a hash over its contents and the previous
+// entry's hash, so editing, removing or reordering entries shows up as a
+// break in the chain at `/api/admin/audit/verify`. Recording never fails the
+// action being recorded: a failed append is logged and the request goes on.
+
+// Supabase tokens this instance remembers, so each one counts as one login
+// rather than one per request
+const LOGIN_DEDUP_WINDOW: usize = 10_000;
+
+// Entries fetched per query while verifying the chain
+const VERIFY_BATCH_SIZE: i64 = 1_000;
+
+// Longer user agents are cut to this many characters
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Where the request being handled came from.
+#[derive(Debug, Clone, Default)]
+struct ClientInfo {
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+tokio::task_local! {
+    static CLIENT: ClientInfo;
+}
+
+fn current_client() -> ClientInfo {
+    CLIENT.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Captures the client address and user agent for entries recorded while
+/// handling the request. The address is the peer's, or behind
+/// `trusted_proxy_hops` proxies the `X-Forwarded-For` hop the outermost one
+/// added. Needs the server to be started with connect info.
+pub async fn client_info(
+    State(services): State<Services>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    let forwarded = forwarded_for(headers, services.config.trusted_proxy_hops);
+    let ip = forwarded.or_else(|| {
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+    });
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(MAX_USER_AGENT_LEN).collect());
+
+    CLIENT
+        .scope(ClientInfo { ip, user_agent }, next.run(request))
+        .await
+}
+
+// Each proxy appends the address it received the request from, so the hops
+// left of the ones our own proxies added were written by the client and can't
+// be trusted. With fewer hops than proxies every hop is a proxy's.
+fn forwarded_for(headers: &HeaderMap, trusted_hops: usize) -> Option<String> {
+    if trusted_hops == 0 {
+        return None;
+    }
+
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|hop| !hop.is_empty())
+        .collect();
+
+    hops.get(hops.len().saturating_sub(trusted_hops))
+        .map(|hop| hop.to_string())
+}
+
+/// One action to record. The client address, user agent and request id are
+/// filled in from the request being handled, if any.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    action: AuditAction,
+    actor_id: Option<Uuid>,
+    target: Option<(&'static str, Uuid)>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+}
+
+impl AuditEntry {
+    /// An entry without an actor, as recorded by the admin CLI.
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            actor_id: None,
+            target: None,
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn target(mut self, target_type: &'static str, target_id: Uuid) -> Self {
+        self.target = Some((target_type, target_id));
+        self
+    }
+
+    /// State of the target before the action.
+    pub fn before<T: Serialize>(mut self, value: &T) -> Self {
+        self.before = serde_json::to_value(value).ok();
+        self
+    }
+
+    /// State of the target after the action.
+    pub fn after<T: Serialize>(mut self, value: &T) -> Self {
+        self.after = serde_json::to_value(value).ok();
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    repository: Arc<dyn Repository>,
+    logins: Arc<Mutex<Dedup>>,
+}
+
+impl AuditLog {
+    pub fn new(repository: Arc<dyn Repository>) -> Self {
+        Self {
+            repository,
+            logins: Arc::new(Mutex::new(Dedup::new(LOGIN_DEDUP_WINDOW))),
+        }
+    }
+
+    pub async fn record(&self, entry: AuditEntry) {
+        let client = current_client();
+        let event = NewAuditEvent {
+            // Hashed at millisecond precision, so stored that way too
+            occurred_at: Utc::now().trunc_subsecs(3),
+            action: entry.action,
+            actor_id: entry.actor_id,
+            target_type: entry.target.map(|(target_type, _)| target_type.to_string()),
+            target_id: entry.target.map(|(_, target_id)| target_id),
+            ip: client.ip,
+            user_agent: client.user_agent,
+            request_id: request_id::current(),
+            before: entry.before,
+            after: entry.after,
+        };
+
+        if let Err(e) = self.repository.append_audit_event(&event).await {
+            tracing::error!(
+                "Failed to record audit event {}: {}",
+                event.action.as_str(),
+                e
+            );
+        }
+    }
+
+    /// Records a login the first time this instance sees `token`.
+    pub async fn login(&self, user_id: Uuid, token: &str) {
+        let key = format!("{:x}", Sha256::digest(token.as_bytes()));
+        let first_time = self
+            .logins
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .first_time(&key);
+
+        if first_time {
+            let entry = AuditEntry::new(AuditAction::Login)
+                .actor(user_id)
+                .target("user", user_id);
+            self.record(entry).await;
+        }
+    }
+
+    /// Records who `admin_emails` makes an admin whenever that differs from
+    /// the list last recorded. The list only changes on restart, so this runs
+    /// once at startup.
+    pub async fn admin_roles(&self, admin_emails: &[String]) {
+        let mut admins: Vec<String> = admin_emails
+            .iter()
+            .map(|email| email.to_lowercase())
+            .collect();
+        admins.sort();
+        admins.dedup();
+
+        let filter = AuditFilter {
+            action: Some(AuditAction::AdminRolesChanged.as_str().to_string()),
+            ..Default::default()
+        };
+        let previous: Vec<String> = match self
+            .repository
+            .get_audit_events_page(&filter, &PageRequest::first(1))
+            .await
+        {
+            Ok(page) => page
+                .data
+                .into_iter()
+                .next()
+                .and_then(|event| event.after)
+                .and_then(|after| serde_json::from_value(after).ok())
+                .unwrap_or_default(),
+            Err(e) => {
+                tracing::error!("Failed to read the recorded admin roles: {}", e);
+                return;
+            }
+        };
+
+        if previous != admins {
+            let entry = AuditEntry::new(AuditAction::AdminRolesChanged)
+                .before(&previous)
+                .after(&admins);
+            self.record(entry).await;
+        }
+    }
+
+    /// Walks the chain from the first entry, stopping at the first one that
+    /// doesn't match its hash or doesn't link to the entry before it.
+    pub async fn verify(&self) -> anyhow::Result<AuditChainReport> {
+        let mut checked = 0;
+        let mut previous_seq = 0;
+        let mut previous_hash = AUDIT_GENESIS_HASH.to_string();
+
+        loop {
+            let batch = self
+                .repository
+                .get_audit_chain(previous_seq, VERIFY_BATCH_SIZE)
+                .await?;
+            if batch.is_empty() {
+                break;
+            }
+
+            for event in batch {
+                if event.seq != previous_seq + 1
+                    || event.prev_hash != previous_hash
+                    || event.compute_hash() != event.hash
+                {
+                    return Ok(AuditChainReport {
+                        valid: false,
+                        checked,
+                        first_broken_seq: Some(event.seq),
+                    });
+                }
+
+                checked += 1;
+                previous_seq = event.seq;
+                previous_hash = event.hash;
+            }
+        }
+
+        Ok(AuditChainReport {
+            valid: true,
+            checked,
+            first_broken_seq: None,
+        })
+    }
+}
+
+// Admin-only access to the audit log
+pub fn routes() -> Vec<Route> {
+    vec![
+        ("GET", "/", get(list_events)),
+        ("GET", "/verify", get(verify_chain)),
+    ]
+}
+
+/// Newest first. `?actor=`, `?target=` (user, post or comment id),
+/// `?action=` (e.g. `post.deleted`) and `?since=`/`?until=` (RFC 3339)
+/// narrow the entries; follow `next_cursor` for more.
+async fn list_events(
+    State(services): State<Services>,
+    user: AuthUserWithRole,
+    Query(params): Query<AuditParams>,
+) -> AppResult<Json<ApiResponse<CursorPage<AuditEvent>>>> {
+    require_admin(&user)?;
+
+    let (filter, page) = params.audit_query()?;
+    let events = services
+        .repository
+        .get_audit_events_page(&filter, &page)
+        .await?;
+
+    Ok(Json(ApiResponse::success(events)))
+}
+
+async fn verify_chain(
+    State(services): State<Services>,
+    user: AuthUserWithRole,
+) -> AppResult<Json<ApiResponse<AuditChainReport>>> {
+    require_admin(&user)?;
+
+    let report = services.audit.verify().await?;
+    if !report.valid {
+        tracing::error!(
+            "Audit log hash chain is broken at seq {:?}",
+            report.first_broken_seq
+        );
+    }
+
+    Ok(Json(ApiResponse::success(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{Method, StatusCode},
+        Router,
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        models::User,
+        repository::{memory::InMemoryRepository, AuditRepository},
+        testing::{self, create_user},
+    };
+
+    async fn send(router: &Router, uri: &str, user: &User) -> (StatusCode, Value) {
+        testing::send(router, Method::GET, uri, Some(user), None).await
+    }
+
+    #[tokio::test]
+    async fn recorded_entries_form_a_valid_chain() {
+        let repository = InMemoryRepository::new();
+        let audit = AuditLog::new(Arc::new(repository.clone()));
+        let user = create_user(&repository, "member").await;
+
+        audit
+            .record(AuditEntry::new(AuditAction::UserCreated).target("user", user.id))
+            .await;
+        audit
+            .record(
+                AuditEntry::new(AuditAction::ProfileUpdated)
+                    .actor(user.id)
+                    .target("user", user.id)
+                    .before(&user)
+                    .after(&user),
+            )
+            .await;
+
+        let report = audit.verify().await.unwrap();
+        assert!(report.valid);
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.first_broken_seq, None);
+    }
+
+    #[tokio::test]
+    async fn each_token_is_one_login() {
+        let repository = InMemoryRepository::new();
+        let audit = AuditLog::new(Arc::new(repository.clone()));
+        let user = create_user(&repository, "member").await;
+
+        audit.login(user.id, "first-token").await;
+        audit.login(user.id, "first-token").await;
+        audit.login(user.id, "second-token").await;
+
+        assert_eq!(audit.verify().await.unwrap().checked, 2);
+    }
+
+    #[tokio::test]
+    async fn only_admins_read_the_audit_log() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let audit = services.audit.clone();
+        let router = testing::router(services, "/api/admin/audit", routes());
+        let admin = create_user(&repository, "admin").await;
+        let member = create_user(&repository, "member").await;
+        audit.login(member.id, "token").await;
+        // Entries are timestamped to the millisecond; keep these two apart
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        audit
+            .record(AuditEntry::new(AuditAction::CountersRepaired))
+            .await;
+
+        let (status, _) = send(&router, "/api/admin/audit", &member).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&router, "/api/admin/audit/verify", &member).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = send(&router, "/api/admin/audit", &admin).await;
+        assert_eq!(status, StatusCode::OK);
+        let actions: Vec<&str> = body["data"]["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, ["admin.counters_repaired", "auth.login"]);
+
+        let uri = format!("/api/admin/audit?actor={}&action=auth.login", member.id);
+        let (status, body) = send(&router, &uri, &admin).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["data"].as_array().unwrap().len(), 1);
+
+        let (status, _) = send(&router, "/api/admin/audit?action=nope", &admin).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = send(&router, "/api/admin/audit/verify", &admin).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["valid"], true);
+        assert_eq!(body["data"]["checked"], 2);
+    }
+
+    #[tokio::test]
+    async fn admin_role_changes_are_recorded_once() {
+        let repository = InMemoryRepository::new();
+        let audit = AuditLog::new(Arc::new(repository.clone()));
+
+        audit.admin_roles(&[]).await;
+        assert_eq!(audit.verify().await.unwrap().checked, 0);
+
+        let admins = vec!["Admin@example.com".to_string()];
+        audit.admin_roles(&admins).await;
+        audit.admin_roles(&admins).await;
+        assert_eq!(audit.verify().await.unwrap().checked, 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        audit.admin_roles(&[]).await;
+        let filter = AuditFilter::default();
+        let events = repository
+            .get_audit_events_page(&filter, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .data;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].action, "admin.roles_changed");
+        assert_eq!(
+            events[0].before,
+            Some(serde_json::json!(["admin@example.com"]))
+        );
+        assert_eq!(events[0].after, Some(serde_json::json!([])));
+    }
+
+    #[test]
+    fn the_hop_our_outermost_proxy_added_is_the_client() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(forwarded_for(&headers, 1), None);
+
+        headers.insert(
+            "x-forwarded-for",
+            "198.51.100.1, 203.0.113.7, 10.0.0.1".parse().unwrap(),
+        );
+        assert_eq!(forwarded_for(&headers, 0), None);
+        assert_eq!(forwarded_for(&headers, 1).as_deref(), Some("10.0.0.1"));
+        assert_eq!(forwarded_for(&headers, 2).as_deref(), Some("203.0.113.7"));
+        assert_eq!(forwarded_for(&headers, 5).as_deref(), Some("198.51.100.1"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::env;
+use std::sync::OnceLock;
 
 use anyhow::Result;
 use axum::{
@@ -16,7 +16,14 @@ use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation}
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{error::AppError, services::Services};
+use crate::{
+    audit::AuditEntry,
+    config::Config,
+    database,
+    error::AppError,
+    models::AuditAction,
+    services::Services,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -43,17 +50,31 @@ impl Claims {
 }
 
 // JWT token management
+static JWT_SECRET: OnceLock<String> = OnceLock::new();
+
+// Installs the validated secret from `Config`, called once at startup
+pub fn set_jwt_secret(secret: &str) {
+    if JWT_SECRET.set(secret.to_string()).is_err() {
+        tracing::warn!("JWT secret already initialized, ignoring new value");
+    }
+}
+
+fn jwt_secret() -> Result<&'static str> {
+    JWT_SECRET
+        .get()
+        .map(String::as_str)
+        .ok_or_else(|| anyhow::anyhow!("JWT secret has not been configured"))
+}
+
 pub fn create_token(claims: &Claims) -> Result<String> {
-    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
-    let encoding_key = EncodingKey::from_secret(secret.as_ref());
+    let encoding_key = EncodingKey::from_secret(jwt_secret()?.as_ref());
     
     encode(&Header::default(), claims, &encoding_key)
         .map_err(|e| anyhow::anyhow!("Failed to create token: {}", e))
 }
 
 pub fn verify_token(token: &str) -> Result<Claims> {
-    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
-    let decoding_key = DecodingKey::from_secret(secret.as_ref());
+    let decoding_key = DecodingKey::from_secret(jwt_secret()?.as_ref());
     
     decode::<Claims>(token, &decoding_key, &Validation::default())
         .map(|data| data.claims)
@@ -69,15 +90,12 @@ pub struct SupabaseUser {
     pub app_metadata: serde_json::Value,
 }
 
-pub async fn verify_supabase_token(token: &str) -> Result<SupabaseUser> {
-    let supabase_url = env::var("SUPABASE_URL")?;
-    let supabase_service_key = env::var("SUPABASE_SERVICE_ROLE_KEY")?;
-    
+pub async fn verify_supabase_token(token: &str, config: &Config) -> Result<SupabaseUser> {
     let client = reqwest::Client::new();
     let response = client
-        .get(&format!("{}/auth/v1/user", supabase_url))
+        .get(&format!("{}/auth/v1/user", config.supabase_url))
         .header("Authorization", format!("Bearer {}", token))
-        .header("apikey", &supabase_service_key)
+        .header("apikey", config.supabase_service_role_key.expose())
         .send()
         .await?;
 
@@ -136,11 +154,12 @@ where
         if let Ok(claims) = verify_token(token) {
             // Verify user still exists in our database
             let user = services
-                .database
+                .repository
                 .get_user_by_id(&claims.sub)
                 .await
                 .map_err(|_| AppError::InternalServer("Database error".to_string()))?
                 .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+            database::authenticated(claims.sub);
 
             return Ok(AuthUser {
                 user_id: claims.sub,
@@ -150,10 +169,10 @@ where
         }
 
         // If JWT verification fails, try Supabase token
-        match verify_supabase_token(token).await {
+        match verify_supabase_token(token, &services.config).await {
             Ok(supabase_user) => {
                 // Check if user exists in our database, create if not
-                let user = match services.database.get_user_by_id(&supabase_user.id).await {
+                let user = match services.repository.get_user_by_id(&supabase_user.id).await {
                     Ok(Some(user)) => user,
                     Ok(None) => {
                         // Create user from Supabase data
@@ -175,17 +194,26 @@ where
                             bio: None,
                         };
 
-                        services
-                            .database
+                        let user = services
+                            .repository
                             .create_user(&create_user)
                             .await
-                            .map_err(|_| AppError::InternalServer("Failed to create user".to_string()))?
+                            .map_err(|_| AppError::InternalServer("Failed to create user".to_string()))?;
+
+                        let entry = AuditEntry::new(AuditAction::UserCreated)
+                            .actor(user.id)
+                            .target("user", user.id)
+                            .after(&user);
+                        services.audit.record(entry).await;
+                        user
                     }
                     Err(_) => {
                         return Err(AppError::InternalServer("Database error".to_string()));
                     }
                 };
 
+                services.audit.login(user.id, token).await;
+                database::authenticated(user.id);
                 let claims = Claims::new(user.id, user.email.clone());
 
                 Ok(AuthUser {
@@ -252,10 +280,14 @@ where
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let auth_user = AuthUser::from_request_parts(parts, state).await?;
-        
-        // Get user role from database or JWT claims
-        // For now, default to User role
-        let role = Role::User;
+
+        // Admins are configured by email (`admin_emails`); everyone else is a User
+        let services = Services::from_ref(state);
+        let role = if services.config.is_admin_email(&auth_user.email) {
+            Role::Admin
+        } else {
+            Role::User
+        };
 
         Ok(AuthUserWithRole {
             user: auth_user,
@@ -273,4 +305,13 @@ pub fn require_role(required_role: Role) -> impl Fn(AuthUserWithRole) -> Result<
             Err(AppError::Forbidden("Insufficient permissions".to_string()))
         }
     }
+}
+
+/// Rejects anyone but an admin, for admin-only endpoints.
+pub fn require_admin(user: &AuthUserWithRole) -> Result<(), AppError> {
+    if user.role == Role::Admin {
+        Ok(())
+    } else {
+        Err(AppError::forbidden("Only admins can do this"))
+    }
 }
\ No newline at end of file
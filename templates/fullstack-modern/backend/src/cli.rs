@@ -0,0 +1,317 @@
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    auth::{self, Claims},
+    config::{Config, ConfigError, Profile},
+    database::Backend,
+    error::AppError,
+    i18n,
+    models::{AuditAction, CreateUser, User},
+    seed::{self, SeedSize},
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "{{projectName}} backend server and admin commands")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the HTTP server (default)
+    Serve,
+    /// Apply, revert or inspect database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Insert a deterministic demo dataset: users, posts, comments and chats
+    Seed(SeedArgs),
+    /// Create a user record
+    CreateUser(CreateUserArgs),
+    /// Print a signed JWT for a user, given their id or email
+    IssueToken { user: String },
+    /// Recompute every post's like and comment counters
+    RepairCounters,
+    /// Load and validate configuration, printing it with secrets masked
+    CheckConfig,
+    /// Print the router table
+    Routes,
+    /// Print every API error code as JSON (also served at /api/meta/errors)
+    ErrorCatalog {
+        /// Language of the default messages
+        #[arg(long, default_value = i18n::FALLBACK_LOCALE)]
+        locale: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateAction {
+    /// Apply all pending migrations
+    Up {
+        /// List pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Revert reversible migrations (the latest one by default)
+    Down {
+        /// Revert every migration newer than this version
+        #[arg(long)]
+        target: Option<i64>,
+    },
+    /// List migrations and whether they are applied
+    Status,
+}
+
+#[derive(Debug, Args)]
+pub struct SeedArgs {
+    /// Number of demo users (demo1@example.com, ...)
+    #[arg(long, default_value_t = 3)]
+    pub users: usize,
+    #[arg(long, default_value_t = 1)]
+    pub posts_per_user: usize,
+    #[arg(long, default_value_t = 2)]
+    pub comments_per_post: usize,
+    /// Chats between random demo users (needs at least two users)
+    #[arg(long, default_value_t = 1)]
+    pub chats: usize,
+    #[arg(long, default_value_t = 5)]
+    pub messages_per_chat: usize,
+    /// The same seed always produces the same dataset
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct CreateUserArgs {
+    #[arg(long)]
+    pub email: String,
+    #[arg(long)]
+    pub username: Option<String>,
+    #[arg(long)]
+    pub full_name: Option<String>,
+    #[arg(long)]
+    pub bio: Option<String>,
+}
+
+// Shared setup for commands that talk to the database
+async fn connect() -> anyhow::Result<(Config, Backend)> {
+    let config = Config::load()?;
+    let backend = Backend::connect(&config).await?;
+    Ok((config, backend))
+}
+
+pub async fn migrate(action: MigrateAction) -> anyhow::Result<()> {
+    let (_, backend) = connect().await?;
+
+    match action {
+        MigrateAction::Up { dry_run: true } => {
+            let pending = backend.pending_migrations().await?;
+            if pending.is_empty() {
+                println!("No pending migrations");
+            }
+            for migration in pending {
+                println!("{:>16}  {}", migration.version, migration.description);
+            }
+        }
+        MigrateAction::Up { dry_run: false } => {
+            backend.migrate().await?;
+            println!("Migrations applied");
+        }
+        MigrateAction::Down { target } => {
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    // Step back to the migration before the latest applied one
+                    let mut applied: Vec<i64> = backend
+                        .migration_status()
+                        .await?
+                        .into_iter()
+                        .filter(|m| m.applied)
+                        .map(|m| m.version)
+                        .collect();
+                    applied.pop();
+                    applied.last().copied().unwrap_or(0)
+                }
+            };
+            backend.migrate_down(target).await?;
+            println!("Reverted migrations newer than {}", target);
+        }
+        MigrateAction::Status => {
+            for migration in backend.migration_status().await? {
+                let state = if migration.applied { "applied" } else { "pending" };
+                println!("{:>16}  {:<8} {}", migration.version, state, migration.description);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn seed(args: SeedArgs) -> anyhow::Result<()> {
+    let (_, backend) = connect().await?;
+    let repository = backend.repository();
+
+    let size = SeedSize {
+        users: args.users,
+        posts_per_user: args.posts_per_user,
+        comments_per_post: args.comments_per_post,
+        chats: args.chats,
+        messages_per_chat: args.messages_per_chat,
+    };
+    let report = seed::seed(repository.as_ref(), &size, args.seed).await?;
+
+    println!(
+        "Seeded {} users, {} posts, {} comments, {} chats and {} messages (seed {})",
+        report.users, report.posts, report.comments, report.chats, report.messages, args.seed
+    );
+    Ok(())
+}
+
+pub async fn create_user(args: CreateUserArgs) -> anyhow::Result<()> {
+    let (_, backend) = connect().await?;
+    let repository = backend.repository();
+
+    let new_user = CreateUser {
+        id: Uuid::new_v4(),
+        email: args.email,
+        username: args.username,
+        full_name: args.full_name,
+        avatar_url: None,
+        bio: args.bio,
+    };
+    new_user.validate()?;
+
+    let user = repository.create_user(&new_user).await?;
+    let entry = AuditEntry::new(AuditAction::UserCreated)
+        .target("user", user.id)
+        .after(&user);
+    AuditLog::new(repository).record(entry).await;
+
+    println!("Created user {} <{}>", user.id, user.email);
+    Ok(())
+}
+
+pub async fn issue_token(identifier: &str) -> anyhow::Result<()> {
+    let (config, backend) = connect().await?;
+    let repository = backend.repository();
+    auth::set_jwt_secret(config.jwt_secret.expose());
+
+    let user: User = match Uuid::parse_str(identifier) {
+        Ok(user_id) => repository.get_user_by_id(&user_id).await?,
+        Err(_) => repository.get_user_by_email(identifier).await?,
+    }
+    .with_context(|| format!("User '{}' not found", identifier))?;
+
+    let token = auth::create_token(&Claims::new(user.id, user.email))?;
+    let entry = AuditEntry::new(AuditAction::TokenIssued).target("user", user.id);
+    AuditLog::new(repository).record(entry).await;
+
+    println!("{}", token);
+    Ok(())
+}
+
+pub async fn repair_counters() -> anyhow::Result<()> {
+    let (_, backend) = connect().await?;
+    let repository = backend.repository();
+
+    let repaired = repository.recount_post_counters().await?;
+    let entry = AuditEntry::new(AuditAction::CountersRepaired)
+        .after(&serde_json::json!({ "posts_repaired": repaired }));
+    AuditLog::new(repository).record(entry).await;
+
+    println!("Repaired counters on {} posts", repaired);
+    Ok(())
+}
+
+pub fn check_config() -> anyhow::Result<()> {
+    let config = Config::load()?;
+    println!("{}", config.describe());
+
+    let violations = config.violations();
+    if violations.is_empty() {
+        println!("\nConfiguration OK");
+        return Ok(());
+    }
+
+    println!("\n{} violation(s) for {} profile:", violations.len(), config.profile);
+    for violation in &violations {
+        println!("  - {}", violation);
+    }
+
+    if config.profile == Profile::Production {
+        return Err(ConfigError::Unsafe {
+            profile: config.profile,
+            violations,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+pub fn error_catalog(locale: &str) -> anyhow::Result<()> {
+    let locale = i18n::supported_locale(locale)
+        .with_context(|| format!("No message catalog for '{}'", locale))?;
+
+    println!("{}", serde_json::to_string_pretty(&AppError::catalog(locale))?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Option<Command> {
+        let args = std::iter::once("backend").chain(args.iter().copied());
+        Cli::try_parse_from(args).expect("arguments parse").command
+    }
+
+    #[test]
+    fn the_cli_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn no_command_serves() {
+        assert!(parse(&[]).is_none());
+        assert!(matches!(parse(&["serve"]), Some(Command::Serve)));
+    }
+
+    #[test]
+    fn seed_sizes_have_defaults() {
+        let Some(Command::Seed(args)) = parse(&["seed", "--users", "10"]) else {
+            panic!("expected the seed command");
+        };
+
+        assert_eq!(args.users, 10);
+        assert_eq!(args.posts_per_user, 1);
+        assert_eq!(args.seed, 42);
+    }
+
+    #[test]
+    fn migrations_revert_to_a_target() {
+        assert!(matches!(
+            parse(&["migrate", "down", "--target", "20240101000000"]),
+            Some(Command::Migrate {
+                action: MigrateAction::Down {
+                    target: Some(20240101000000)
+                }
+            })
+        ));
+        assert!(Cli::try_parse_from(["backend", "migrate", "sideways"]).is_err());
+    }
+
+    #[test]
+    fn error_catalogs_need_a_known_locale() {
+        assert!(error_catalog("en").is_ok());
+        assert!(error_catalog("xx").is_err());
+    }
+}
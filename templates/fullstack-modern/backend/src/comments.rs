@@ -0,0 +1,362 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{delete, get, patch, post},
+    Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    auth::{AuthUser, AuthUserWithRole, Role},
+    error::{AppError, AppResult},
+    models::{
+        ApiResponse, AuditAction, Comment, CommentTree, CommentTreeParams, CreateComment,
+        NewComment, UpdateComment,
+    },
+    repository::Repository,
+    routing::Route,
+    services::Services,
+};
+
+// Rules for threaded comments. Comments hang off a live post, replies off a
+// comment on the same post; only the author edits a comment, and the author
+// or an admin deletes it along with every reply below it. Trees are read in
+// slices so deep or busy threads come back with "N more replies" counts
+// instead of every row.
+
+fn not_found() -> AppError {
+    AppError::not_found("comment.not_found")
+}
+
+async fn ensure_live_post(repository: &dyn Repository, post_id: &Uuid) -> AppResult<()> {
+    repository
+        .get_post_status(post_id)
+        .await?
+        .filter(|status| status.deleted_at.is_none())
+        .map(|_| ())
+        .ok_or_else(|| AppError::not_found("post.not_found"))
+}
+
+// A comment on `post_id`; one on another post reads as missing
+async fn post_comment(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+    comment_id: &Uuid,
+) -> AppResult<Comment> {
+    repository
+        .get_comment(comment_id)
+        .await?
+        .filter(|comment| comment.post_id == *post_id)
+        .ok_or_else(not_found)
+}
+
+/// A slice of a post's comment tree, as described by `params`.
+pub async fn comment_tree(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+    params: &CommentTreeParams,
+) -> AppResult<CommentTree> {
+    let request = params.tree_request()?;
+
+    ensure_live_post(repository, post_id).await?;
+    if let Some(parent_id) = &request.parent_id {
+        post_comment(repository, post_id, parent_id).await?;
+    }
+
+    Ok(repository.get_comment_tree(post_id, &request).await?)
+}
+
+/// Adds a comment, or a reply when `parent_id` is set.
+pub async fn create_comment(
+    repository: &dyn Repository,
+    comment: &CreateComment,
+) -> AppResult<Comment> {
+    comment.validate()?;
+
+    ensure_live_post(repository, &comment.post_id).await?;
+    if let Some(parent_id) = &comment.parent_id {
+        post_comment(repository, &comment.post_id, parent_id).await?;
+    }
+
+    Ok(repository.create_comment(comment).await?)
+}
+
+/// Replaces a comment's content; only its author may edit it.
+pub async fn update_comment(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    post_id: &Uuid,
+    comment_id: &Uuid,
+    updates: &UpdateComment,
+) -> AppResult<Comment> {
+    updates.validate()?;
+
+    ensure_live_post(repository, post_id).await?;
+    let comment = post_comment(repository, post_id, comment_id).await?;
+    if comment.author_id != *user_id {
+        return Err(AppError::forbidden("Only the author can edit this comment"));
+    }
+
+    // False when the comment was deleted since the check above
+    if !repository.update_comment(comment_id, &updates.content).await? {
+        return Err(not_found());
+    }
+
+    repository
+        .get_comment(comment_id)
+        .await?
+        .ok_or_else(not_found)
+}
+
+/// Deletes a comment and its replies on behalf of its author or an admin.
+pub async fn delete_comment(
+    repository: &dyn Repository,
+    audit: &AuditLog,
+    user_id: &Uuid,
+    role: &Role,
+    post_id: &Uuid,
+    comment_id: &Uuid,
+) -> AppResult<()> {
+    ensure_live_post(repository, post_id).await?;
+    let comment = post_comment(repository, post_id, comment_id).await?;
+    if comment.author_id != *user_id && *role != Role::Admin {
+        return Err(AppError::forbidden("Only the author or an admin can delete this comment"));
+    }
+
+    if !repository.delete_comment(comment_id).await? {
+        return Err(not_found());
+    }
+
+    let entry = AuditEntry::new(AuditAction::CommentDeleted)
+        .actor(*user_id)
+        .target("comment", *comment_id)
+        .before(&comment);
+    audit.record(entry).await;
+
+    Ok(())
+}
+
+// Comment threads, mounted under `/api/posts`
+pub fn routes() -> Vec<Route> {
+    vec![
+        ("GET", "/:id/comments", get(tree)),
+        ("POST", "/:id/comments", post(create)),
+        ("PATCH", "/:id/comments/:comment_id", patch(edit)),
+        ("DELETE", "/:id/comments/:comment_id", delete(remove)),
+    ]
+}
+
+/// `?parent_id=` starts below a comment; `?sort=`, `?depth=`, `?limit=`,
+/// `?offset=` and `?replies=` shape the slice that comes back.
+async fn tree(
+    State(services): State<Services>,
+    _user: AuthUser,
+    Path(post_id): Path<Uuid>,
+    Query(params): Query<CommentTreeParams>,
+) -> AppResult<Json<ApiResponse<CommentTree>>> {
+    let tree = comment_tree(services.repository.as_ref(), &post_id, &params).await?;
+
+    Ok(Json(ApiResponse::success(tree)))
+}
+
+async fn create(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(post_id): Path<Uuid>,
+    Json(body): Json<NewComment>,
+) -> AppResult<Json<ApiResponse<Comment>>> {
+    let comment = CreateComment {
+        id: Uuid::new_v4(),
+        post_id,
+        parent_id: body.parent_id,
+        author_id: user.user_id,
+        content: body.content,
+    };
+    let comment = create_comment(services.repository.as_ref(), &comment).await?;
+
+    Ok(Json(ApiResponse::success(comment)))
+}
+
+async fn edit(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path((post_id, comment_id)): Path<(Uuid, Uuid)>,
+    Json(updates): Json<UpdateComment>,
+) -> AppResult<Json<ApiResponse<Comment>>> {
+    let comment = update_comment(
+        services.repository.as_ref(),
+        &user.user_id,
+        &post_id,
+        &comment_id,
+        &updates,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(comment)))
+}
+
+async fn remove(
+    State(services): State<Services>,
+    user: AuthUserWithRole,
+    Path((post_id, comment_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<ApiResponse<()>>> {
+    delete_comment(
+        services.repository.as_ref(),
+        &services.audit,
+        &user.user.user_id,
+        &user.role,
+        &post_id,
+        &comment_id,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{Method, StatusCode},
+        Router,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        models::User,
+        repository::{memory::InMemoryRepository, CommentRepository},
+        testing::{self, create_post, create_user},
+    };
+
+    async fn comment(
+        router: &Router,
+        user: &User,
+        post_id: &Uuid,
+        parent_id: Option<&Value>,
+        content: &str,
+    ) -> (StatusCode, Value) {
+        let body = json!({ "parent_id": parent_id, "content": content });
+        let uri = format!("/api/posts/{}/comments", post_id);
+        testing::send(router, Method::POST, &uri, Some(user), Some(body)).await
+    }
+
+    #[tokio::test]
+    async fn replies_nest_under_their_parent() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/posts", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "Thread").await;
+        let other_post = create_post(&repository, &alice, "Elsewhere").await;
+
+        let (status, top) = comment(&router, &alice, &post_id, None, "Top").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(top["data"]["author"].get("email").is_none());
+        let top_id = &top["data"]["id"];
+        let (_, reply) = comment(&router, &alice, &post_id, Some(top_id), "Reply").await;
+        comment(
+            &router,
+            &alice,
+            &post_id,
+            Some(&reply["data"]["id"]),
+            "Nested",
+        )
+        .await;
+
+        // Replies stay on their parent's post
+        let (status, _) = comment(&router, &alice, &other_post, Some(top_id), "Stray").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let uri = format!("/api/posts/{}/comments", post_id);
+        let (status, tree) = testing::send(&router, Method::GET, &uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let top = &tree["data"]["comments"][0];
+        assert_eq!(top["content"], "Top");
+        assert_eq!(top["replies"][0]["content"], "Reply");
+        assert_eq!(top["replies"][0]["replies"][0]["content"], "Nested");
+        assert_eq!(top["replies"][0]["replies"][0]["depth"], 3);
+
+        // Authors are shown by their public profile only
+        assert_eq!(top["author"]["username"], "alice");
+        assert!(top["author"].get("email").is_none());
+
+        // Cut off below the first level, the reply is only counted
+        let uri = format!("{}?depth=1", uri);
+        let (_, tree) = testing::send(&router, Method::GET, &uri, Some(&alice), None).await;
+        let top = &tree["data"]["comments"][0];
+        assert_eq!(top["replies"], json!([]));
+        assert_eq!(top["more_replies"], 1);
+    }
+
+    #[tokio::test]
+    async fn trees_are_read_in_slices() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/posts", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "Busy").await;
+        for n in 0..5 {
+            comment(&router, &alice, &post_id, None, &format!("Comment {}", n)).await;
+        }
+
+        let uri = format!(
+            "/api/posts/{}/comments?sort=oldest&limit=2&offset=2",
+            post_id
+        );
+        let (status, tree) = testing::send(&router, Method::GET, &uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let contents: Vec<&Value> = tree["data"]["comments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| &node["content"])
+            .collect();
+        assert_eq!(contents, [&json!("Comment 2"), &json!("Comment 3")]);
+        assert_eq!(tree["data"]["more_comments"], 1);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_comment_takes_its_replies() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/posts", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        let post_id = create_post(&repository, &alice, "Thread").await;
+        let (_, top) = comment(&router, &alice, &post_id, None, "Top").await;
+        let (_, reply) = comment(&router, &bob, &post_id, Some(&top["data"]["id"]), "Reply").await;
+
+        let top_uri = format!(
+            "/api/posts/{}/comments/{}",
+            post_id,
+            top["data"]["id"].as_str().unwrap()
+        );
+        let edit = json!({ "content": "Edited" });
+        let (status, _) = testing::send(
+            &router,
+            Method::PATCH,
+            &top_uri,
+            Some(&bob),
+            Some(edit.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) =
+            testing::send(&router, Method::PATCH, &top_uri, Some(&alice), Some(edit)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["content"], "Edited");
+
+        let (status, _) = testing::send(&router, Method::DELETE, &top_uri, Some(&bob), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) =
+            testing::send(&router, Method::DELETE, &top_uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let reply_id: Uuid = reply["data"]["id"].as_str().unwrap().parse().unwrap();
+        assert!(repository.get_comment(&reply_id).await.unwrap().is_none());
+    }
+}
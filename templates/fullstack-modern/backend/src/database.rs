@@ -1,26 +1,213 @@
-use sqlx::{postgres::PgPoolOptions, PgPool, Row};
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    migrate::{AppliedMigration, Migrate, Migrator},
+    postgres::PgPoolOptions,
+    Connection, PgConnection, PgPool, Row,
+};
 use uuid::Uuid;
 
+use crate::{
+    config::Config,
+    models::{
+        AuditEvent, AuditFilter, Chat, ChatType, Comment, CommentTree, CommentTreeRequest,
+        CommentTreeRow, CommentWithAuthor, CreateChat, CreateComment, CreateMessage,
+        CreateNotification, CreatePost, CreateUser, CursorDirection, CursorPage, DomainEvent,
+        FeedEntry, FollowEdge, FollowKind, FollowUser, FollowUserRow, Message, MessageType,
+        MessageWithSender, NewAuditEvent, Notification, OutboxRow, PageRequest, Post, PostLiker,
+        PostLikerRow, PostRevision, PostStatus, PostWithAuthor, SearchHit, SearchHitRow,
+        SearchKind, SearchRequest, UpdatePost, UpdateUser, User,
+    },
+    repository::{
+        AuditRepository, ChatRepository, CommentRepository, FollowRepository,
+        NotificationRepository, OutboxRepository, PostRepository, Repository, SearchRepository,
+        TransactionRepository, UnitOfWork, UserRepository,
+    },
+};
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod replicas;
+mod transaction;
+
+pub use replicas::{authenticated, read_your_writes};
+pub use transaction::Isolation;
+
+use replicas::Replicas;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+// `ts_headline` options for search snippets; matches are bracketed with
+// `MATCH_START`/`MATCH_END` and only turned into tags once escaped
+const HEADLINE_OPTIONS: &str = concat!(
+    "StartSel=\u{E000}, StopSel=\u{E001}, MinWords=15, MaxWords=35, ",
+    "MaxFragments=2, FragmentDelimiter=\" … \""
+);
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+// Lists `migrator`'s up migrations, marking those recorded in `applied`
+fn migration_list(migrator: &Migrator, applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+    migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.iter().any(|a| a.version == migration.version),
+        })
+        .collect()
+}
+
+// Records `event` in the outbox on `conn`, which should be the transaction
+// making the change it describes
+async fn record_event(conn: &mut PgConnection, event: &DomainEvent) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO outbox (event_type, dedup_key, payload)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (dedup_key) DO NOTHING
+        "#,
+        event.event_type(),
+        event.dedup_key(),
+        serde_json::to_value(event)?
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// The storage backend selected by `DATABASE_URL`: Postgres, or SQLite for
+/// `sqlite:` URLs when built with the `sqlite` feature.
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Postgres(Database),
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlite::SqliteDatabase),
+}
+
+impl Backend {
+    pub async fn connect(config: &Config) -> anyhow::Result<Self> {
+        let database_url = config.database_url.expose();
+        if database_url.starts_with("sqlite:") {
+            if !config.database_replica_urls.is_empty() {
+                tracing::warn!("Ignoring database_replica_urls, which SQLite doesn't support");
+            }
+
+            #[cfg(feature = "sqlite")]
+            return Ok(Self::Sqlite(sqlite::SqliteDatabase::new(database_url).await?));
+
+            #[cfg(not(feature = "sqlite"))]
+            anyhow::bail!(
+                "DATABASE_URL points at SQLite but this binary was built without the `sqlite` feature"
+            );
+        }
+
+        Ok(Self::Postgres(Database::new(config).await?))
+    }
+
+    pub fn repository(&self) -> Arc<dyn Repository> {
+        match self {
+            Self::Postgres(database) => Arc::new(database.clone()),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(database) => Arc::new(database.clone()),
+        }
+    }
+
+    /// Applies pending migrations. On Postgres this holds an advisory lock,
+    /// so instances starting together apply them once.
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Postgres(database) => database.migrate().await,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(database) => database.migrate().await,
+        }
+    }
+
+    pub async fn migrate_down(&self, target: i64) -> anyhow::Result<()> {
+        match self {
+            Self::Postgres(database) => database.migrate_down(target).await,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(database) => database.migrate_down(target).await,
+        }
+    }
+
+    pub async fn migration_status(&self) -> anyhow::Result<Vec<MigrationStatus>> {
+        match self {
+            Self::Postgres(database) => database.migration_status().await,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(database) => database.migration_status().await,
+        }
+    }
+
+    /// Up migrations not applied yet, oldest first.
+    pub async fn pending_migrations(&self) -> anyhow::Result<Vec<MigrationStatus>> {
+        let mut migrations = self.migration_status().await?;
+        migrations.retain(|migration| !migration.applied);
+        Ok(migrations)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Database {
+    // The primary
     pool: PgPool,
+    replicas: Option<Arc<Replicas>>,
+    // Set on the handles `transaction_with` hands out; their queries all run in it
+    scope: Option<transaction::Scope>,
 }
 
 impl Database {
-    pub async fn new(database_url: &str) -> anyhow::Result<Self> {
+    pub async fn new(config: &Config) -> anyhow::Result<Self> {
         let pool = PgPoolOptions::new()
-            .max_connections(10)
-            .connect(database_url)
+            .max_connections(config.database_max_connections)
+            .connect(config.database_url.expose())
             .await?;
 
-        Ok(Self { pool })
+        let replicas = if config.database_replica_urls.is_empty() {
+            None
+        } else {
+            Some(Arc::new(Replicas::connect(
+                &config.database_replica_urls,
+                config.database_max_connections,
+                Duration::from_secs(config.read_your_writes_secs),
+            )?))
+        };
+
+        Ok(Self {
+            pool,
+            replicas,
+            scope: None,
+        })
     }
 
     pub async fn migrate(&self) -> anyhow::Result<()> {
-        sqlx::migrate!("./migrations").run(&self.pool).await?;
+        MIGRATOR.run(&self.pool).await?;
         Ok(())
     }
 
+    // Reverts applied reversible migrations newer than `target`
+    pub async fn migrate_down(&self, target: i64) -> anyhow::Result<()> {
+        MIGRATOR.undo(&self.pool, target).await?;
+        Ok(())
+    }
+
+    pub async fn migration_status(&self) -> anyhow::Result<Vec<MigrationStatus>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        let applied = conn.list_applied_migrations().await?;
+
+        Ok(migration_list(&MIGRATOR, &applied))
+    }
+
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
@@ -38,11 +225,13 @@ impl Database {
             Err(anyhow::anyhow!("Database health check failed"))
         }
     }
+}
 
-    // User operations
-    pub async fn get_user_by_id(&self, user_id: &Uuid) -> anyhow::Result<Option<crate::models::User>> {
+#[async_trait]
+impl UserRepository for Database {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> anyhow::Result<Option<User>> {
         let user = sqlx::query_as!(
-            crate::models::User,
+            User,
             r#"
             SELECT id, email, username, full_name, avatar_url, bio, created_at, updated_at
             FROM users 
@@ -50,15 +239,15 @@ impl Database {
             "#,
             user_id
         )
-        .fetch_optional(&self.pool)
+        .fetch_optional(&mut *self.read_conn().await?)
         .await?;
 
         Ok(user)
     }
 
-    pub async fn get_user_by_email(&self, email: &str) -> anyhow::Result<Option<crate::models::User>> {
+    async fn get_user_by_email(&self, email: &str) -> anyhow::Result<Option<User>> {
         let user = sqlx::query_as!(
-            crate::models::User,
+            User,
             r#"
             SELECT id, email, username, full_name, avatar_url, bio, created_at, updated_at
             FROM users 
@@ -66,15 +255,15 @@ impl Database {
             "#,
             email
         )
-        .fetch_optional(&self.pool)
+        .fetch_optional(&mut *self.read_conn().await?)
         .await?;
 
         Ok(user)
     }
 
-    pub async fn create_user(&self, user: &crate::models::CreateUser) -> anyhow::Result<crate::models::User> {
+    async fn create_user(&self, user: &CreateUser) -> anyhow::Result<User> {
         let user = sqlx::query_as!(
-            crate::models::User,
+            User,
             r#"
             INSERT INTO users (id, email, username, full_name, avatar_url, bio)
             VALUES ($1, $2, $3, $4, $5, $6)
@@ -87,15 +276,18 @@ impl Database {
             user.avatar_url,
             user.bio
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *self.conn().await?)
         .await?;
 
         Ok(user)
     }
 
-    pub async fn update_user(&self, user_id: &Uuid, updates: &crate::models::UpdateUser) -> anyhow::Result<crate::models::User> {
+    async fn update_user(&self, user_id: &Uuid, updates: &UpdateUser) -> anyhow::Result<User> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
         let user = sqlx::query_as!(
-            crate::models::User,
+            User,
             r#"
             UPDATE users 
             SET 
@@ -113,39 +305,47 @@ impl Database {
             updates.avatar_url,
             updates.bio
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        let event = DomainEvent::UserUpdated {
+            user_id: user.id,
+            updated_at: user.updated_at,
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+
         Ok(user)
     }
 
-    // Post operations
-    pub async fn get_posts(&self, limit: i64, offset: i64, user_id: Option<&Uuid>) -> anyhow::Result<Vec<crate::models::Post>> {
+    // Posts, likes, comments, chats and messages go with the user via ON DELETE CASCADE
+    async fn delete_user(&self, user_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl PostRepository for Database {
+    async fn get_posts(&self, limit: i64, offset: i64, user_id: Option<&Uuid>) -> anyhow::Result<Vec<Post>> {
         let posts = if let Some(user_id) = user_id {
             sqlx::query_as!(
-                crate::models::PostWithAuthor,
+                PostWithAuthor,
                 r#"
                 SELECT 
                     p.id, p.title, p.content, p.author_id, p.created_at, p.updated_at,
-                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username!", 
-                    u.full_name as "author_full_name!", u.avatar_url as "author_avatar_url",
+                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username", 
+                    u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
                     u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
-                    COALESCE(l.likes_count, 0) as "likes_count!",
-                    COALESCE(c.comments_count, 0) as "comments_count!",
+                    p.likes_count, p.comments_count,
                     CASE WHEN ul.user_id IS NOT NULL THEN true ELSE false END as "is_liked!"
                 FROM posts p
                 JOIN users u ON p.author_id = u.id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as likes_count 
-                    FROM post_likes 
-                    GROUP BY post_id
-                ) l ON p.id = l.post_id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as comments_count 
-                    FROM post_comments 
-                    GROUP BY post_id
-                ) c ON p.id = c.post_id
                 LEFT JOIN post_likes ul ON p.id = ul.post_id AND ul.user_id = $3
+                WHERE p.deleted_at IS NULL
                 ORDER BY p.created_at DESC
                 LIMIT $1 OFFSET $2
                 "#,
@@ -153,114 +353,182 @@ impl Database {
                 offset,
                 user_id
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *self.read_conn().await?)
             .await?
         } else {
             sqlx::query_as!(
-                crate::models::PostWithAuthor,
+                PostWithAuthor,
                 r#"
                 SELECT 
                     p.id, p.title, p.content, p.author_id, p.created_at, p.updated_at,
-                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username!", 
-                    u.full_name as "author_full_name!", u.avatar_url as "author_avatar_url",
+                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username", 
+                    u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
                     u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
-                    COALESCE(l.likes_count, 0) as "likes_count!",
-                    COALESCE(c.comments_count, 0) as "comments_count!",
+                    p.likes_count, p.comments_count,
                     false as "is_liked!"
                 FROM posts p
                 JOIN users u ON p.author_id = u.id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as likes_count 
-                    FROM post_likes 
-                    GROUP BY post_id
-                ) l ON p.id = l.post_id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as comments_count 
-                    FROM post_comments 
-                    GROUP BY post_id
-                ) c ON p.id = c.post_id
+                WHERE p.deleted_at IS NULL
                 ORDER BY p.created_at DESC
                 LIMIT $1 OFFSET $2
                 "#,
                 limit,
                 offset
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *self.read_conn().await?)
             .await?
         };
 
         Ok(posts.into_iter().map(Into::into).collect())
     }
 
-    pub async fn get_post_by_id(&self, post_id: &Uuid, user_id: Option<&Uuid>) -> anyhow::Result<Option<crate::models::Post>> {
+    async fn get_post_by_id(&self, post_id: &Uuid, user_id: Option<&Uuid>) -> anyhow::Result<Option<Post>> {
         let post = if let Some(user_id) = user_id {
             sqlx::query_as!(
-                crate::models::PostWithAuthor,
+                PostWithAuthor,
                 r#"
                 SELECT 
                     p.id, p.title, p.content, p.author_id, p.created_at, p.updated_at,
-                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username!", 
-                    u.full_name as "author_full_name!", u.avatar_url as "author_avatar_url",
+                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username", 
+                    u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
                     u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
-                    COALESCE(l.likes_count, 0) as "likes_count!",
-                    COALESCE(c.comments_count, 0) as "comments_count!",
+                    p.likes_count, p.comments_count,
                     CASE WHEN ul.user_id IS NOT NULL THEN true ELSE false END as "is_liked!"
                 FROM posts p
                 JOIN users u ON p.author_id = u.id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as likes_count 
-                    FROM post_likes 
-                    GROUP BY post_id
-                ) l ON p.id = l.post_id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as comments_count 
-                    FROM post_comments 
-                    GROUP BY post_id
-                ) c ON p.id = c.post_id
                 LEFT JOIN post_likes ul ON p.id = ul.post_id AND ul.user_id = $2
-                WHERE p.id = $1
+                WHERE p.id = $1 AND p.deleted_at IS NULL
                 "#,
                 post_id,
                 user_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *self.read_conn().await?)
             .await?
         } else {
             sqlx::query_as!(
-                crate::models::PostWithAuthor,
+                PostWithAuthor,
                 r#"
                 SELECT 
                     p.id, p.title, p.content, p.author_id, p.created_at, p.updated_at,
-                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username!", 
-                    u.full_name as "author_full_name!", u.avatar_url as "author_avatar_url",
+                    u.id as "author_id!", u.email as "author_email!", u.username as "author_username", 
+                    u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
                     u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
-                    COALESCE(l.likes_count, 0) as "likes_count!",
-                    COALESCE(c.comments_count, 0) as "comments_count!",
+                    p.likes_count, p.comments_count,
                     false as "is_liked!"
                 FROM posts p
                 JOIN users u ON p.author_id = u.id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as likes_count 
-                    FROM post_likes 
-                    GROUP BY post_id
-                ) l ON p.id = l.post_id
-                LEFT JOIN (
-                    SELECT post_id, COUNT(*) as comments_count 
-                    FROM post_comments 
-                    GROUP BY post_id
-                ) c ON p.id = c.post_id
-                WHERE p.id = $1
+                WHERE p.id = $1 AND p.deleted_at IS NULL
                 "#,
                 post_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *self.read_conn().await?)
             .await?
         };
 
         Ok(post.map(Into::into))
     }
 
-    pub async fn create_post(&self, post: &crate::models::CreatePost) -> anyhow::Result<Uuid> {
+    async fn get_posts_by_ids(
+        &self,
+        post_ids: &[Uuid],
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>> {
+        let posts = sqlx::query_as!(
+            PostWithAuthor,
+            r#"
+            SELECT
+                p.id, p.title, p.content, p.author_id,
+                p.created_at as "created_at!", p.updated_at as "updated_at!",
+                u.email as "author_email!", u.username as "author_username",
+                u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
+                u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
+                p.likes_count, p.comments_count,
+                (ul.user_id IS NOT NULL) as "is_liked!"
+            FROM posts p
+            JOIN users u ON p.author_id = u.id
+            LEFT JOIN post_likes ul ON p.id = ul.post_id AND ul.user_id = $2
+            WHERE p.id = ANY($1) AND p.deleted_at IS NULL
+            "#,
+            post_ids,
+            user_id
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(posts.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_posts_page(
+        &self,
+        page: &PageRequest,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        let posts = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    PostWithAuthor,
+                    r#"
+                    SELECT
+                        p.id, p.title, p.content, p.author_id,
+                        p.created_at as "created_at!", p.updated_at as "updated_at!",
+                        u.email as "author_email!", u.username as "author_username",
+                        u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
+                        u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
+                        p.likes_count, p.comments_count,
+                        (ul.user_id IS NOT NULL) as "is_liked!"
+                    FROM posts p
+                    JOIN users u ON p.author_id = u.id
+                    LEFT JOIN post_likes ul ON p.id = ul.post_id AND ul.user_id = $4
+                    WHERE p.deleted_at IS NULL AND (p.created_at, p.id) > ($1, $2)
+                    ORDER BY p.created_at ASC, p.id ASC
+                    LIMIT $3
+                    "#,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit(),
+                    user_id
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    PostWithAuthor,
+                    r#"
+                    SELECT
+                        p.id, p.title, p.content, p.author_id,
+                        p.created_at as "created_at!", p.updated_at as "updated_at!",
+                        u.email as "author_email!", u.username as "author_username",
+                        u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
+                        u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
+                        p.likes_count, p.comments_count,
+                        (ul.user_id IS NOT NULL) as "is_liked!"
+                    FROM posts p
+                    JOIN users u ON p.author_id = u.id
+                    LEFT JOIN post_likes ul ON p.id = ul.post_id AND ul.user_id = $4
+                    WHERE p.deleted_at IS NULL
+                        AND ($1::timestamptz IS NULL OR (p.created_at, p.id) < ($1, $2))
+                    ORDER BY p.created_at DESC, p.id DESC
+                    LIMIT $3
+                    "#,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit(),
+                    user_id
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        let posts: Vec<Post> = posts.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(posts, page, |post| (post.created_at, post.id)))
+    }
+
+    async fn create_post(&self, post: &CreatePost) -> anyhow::Result<Uuid> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
         let row = sqlx::query!(
             r#"
             INSERT INTO posts (id, title, content, author_id)
@@ -272,9 +540,1579 @@ impl Database {
             post.content,
             post.author_id
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        let event = DomainEvent::PostCreated {
+            post_id: row.id,
+            author_id: post.author_id,
+            title: post.title.clone(),
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+
         Ok(row.id)
     }
-}
\ No newline at end of file
+
+    async fn get_post_status(&self, post_id: &Uuid) -> anyhow::Result<Option<PostStatus>> {
+        let status = sqlx::query_as!(
+            PostStatus,
+            "SELECT id, author_id, deleted_at FROM posts WHERE id = $1",
+            post_id
+        )
+        .fetch_optional(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(status)
+    }
+
+    async fn update_post(
+        &self,
+        post_id: &Uuid,
+        editor_id: &Uuid,
+        updates: &UpdatePost,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        // Lock the post first so concurrent edits number their revisions in turn
+        let locked = sqlx::query!(
+            "SELECT title, content FROM posts WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+            post_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(current) = locked else {
+            return Ok(false);
+        };
+
+        // Nothing changes: no revision, and the trigger must not bump updated_at
+        let title = updates.title.as_ref().unwrap_or(&current.title);
+        let content = updates.content.as_ref().unwrap_or(&current.content);
+        if *title == current.title && *content == current.content {
+            return Ok(true);
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO post_revisions (post_id, revision, editor_id, title, content)
+            SELECT
+                p.id,
+                COALESCE((SELECT MAX(revision) FROM post_revisions WHERE post_id = p.id), 0) + 1,
+                $2,
+                p.title,
+                p.content
+            FROM posts p
+            WHERE p.id = $1
+            "#,
+            post_id,
+            editor_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE posts
+            SET
+                title = COALESCE($2, title),
+                content = COALESCE($3, content)
+            WHERE id = $1
+            "#,
+            post_id,
+            updates.title,
+            updates.content
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn get_post_revisions(&self, post_id: &Uuid) -> anyhow::Result<Vec<PostRevision>> {
+        let revisions = sqlx::query_as!(
+            PostRevision,
+            r#"
+            SELECT post_id, revision, editor_id, title, content, created_at
+            FROM post_revisions
+            WHERE post_id = $1
+            ORDER BY revision DESC
+            "#,
+            post_id
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(revisions)
+    }
+
+    async fn get_post_revision(
+        &self,
+        post_id: &Uuid,
+        revision: i32,
+    ) -> anyhow::Result<Option<PostRevision>> {
+        let revision = sqlx::query_as!(
+            PostRevision,
+            r#"
+            SELECT post_id, revision, editor_id, title, content, created_at
+            FROM post_revisions
+            WHERE post_id = $1 AND revision = $2
+            "#,
+            post_id,
+            revision
+        )
+        .fetch_optional(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(revision)
+    }
+
+    async fn delete_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE posts SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+            post_id
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn restore_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE posts SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL",
+            post_id
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Likes and comments go with the post via ON DELETE CASCADE
+    async fn purge_deleted_posts(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let result = sqlx::query!("DELETE FROM posts WHERE deleted_at < $1", cutoff)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // `likes_count` is kept in step by the post_likes_count trigger
+    async fn like_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO post_likes (post_id, user_id)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, post_id) DO NOTHING
+            "#,
+            post_id,
+            user_id
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unlike_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM post_likes WHERE post_id = $1 AND user_id = $2",
+            post_id,
+            user_id
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_post_likers(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<PostLiker>> {
+        let likers = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    PostLikerRow,
+                    r#"
+                    SELECT
+                        u.id, u.username, u.full_name, u.avatar_url, u.bio,
+                        pl.created_at as "liked_at!"
+                    FROM post_likes pl
+                    JOIN users u ON pl.user_id = u.id
+                    WHERE pl.post_id = $1 AND (pl.created_at, pl.user_id) > ($2, $3)
+                    ORDER BY pl.created_at ASC, pl.user_id ASC
+                    LIMIT $4
+                    "#,
+                    post_id,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    PostLikerRow,
+                    r#"
+                    SELECT
+                        u.id, u.username, u.full_name, u.avatar_url, u.bio,
+                        pl.created_at as "liked_at!"
+                    FROM post_likes pl
+                    JOIN users u ON pl.user_id = u.id
+                    WHERE pl.post_id = $1
+                        AND ($2::timestamptz IS NULL OR (pl.created_at, pl.user_id) < ($2, $3))
+                    ORDER BY pl.created_at DESC, pl.user_id DESC
+                    LIMIT $4
+                    "#,
+                    post_id,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        let likers: Vec<PostLiker> = likers.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(likers, page, |liker| {
+            (liker.liked_at, liker.user.id)
+        }))
+    }
+
+    async fn recount_post_counters(&self) -> anyhow::Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE posts p
+            SET likes_count = counts.likes_count, comments_count = counts.comments_count
+            FROM (
+                SELECT
+                    id,
+                    (SELECT COUNT(*) FROM post_likes WHERE post_id = posts.id) AS likes_count,
+                    (SELECT COUNT(*) FROM post_comments WHERE post_id = posts.id) AS comments_count
+                FROM posts
+            ) counts
+            WHERE p.id = counts.id
+                AND (p.likes_count <> counts.likes_count OR p.comments_count <> counts.comments_count)
+            "#
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl CommentRepository for Database {
+    async fn get_comments_page(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Comment>> {
+        let comments = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    CommentWithAuthor,
+                    r#"
+                    SELECT
+                        pc.id, pc.post_id, pc.parent_id, pc.author_id, pc.content,
+                        pc.created_at as "created_at!", pc.updated_at as "updated_at!",
+                        u.username as "author_username", u.full_name as "author_full_name",
+                        u.avatar_url as "author_avatar_url", u.bio as "author_bio"
+                    FROM post_comments pc
+                    JOIN users u ON pc.author_id = u.id
+                    WHERE pc.post_id = $1 AND (pc.created_at, pc.id) > ($2, $3)
+                    ORDER BY pc.created_at ASC, pc.id ASC
+                    LIMIT $4
+                    "#,
+                    post_id,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    CommentWithAuthor,
+                    r#"
+                    SELECT
+                        pc.id, pc.post_id, pc.parent_id, pc.author_id, pc.content,
+                        pc.created_at as "created_at!", pc.updated_at as "updated_at!",
+                        u.username as "author_username", u.full_name as "author_full_name",
+                        u.avatar_url as "author_avatar_url", u.bio as "author_bio"
+                    FROM post_comments pc
+                    JOIN users u ON pc.author_id = u.id
+                    WHERE pc.post_id = $1
+                        AND ($2::timestamptz IS NULL OR (pc.created_at, pc.id) < ($2, $3))
+                    ORDER BY pc.created_at DESC, pc.id DESC
+                    LIMIT $4
+                    "#,
+                    post_id,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        let comments: Vec<Comment> = comments.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(comments, page, |comment| {
+            (comment.created_at, comment.id)
+        }))
+    }
+
+    async fn get_comment_tree(
+        &self,
+        post_id: &Uuid,
+        request: &CommentTreeRequest,
+    ) -> anyhow::Result<CommentTree> {
+        // Rank every comment of the post among its siblings, then walk down
+        // from the requested parent keeping the first `replies_limit` replies
+        // of each node
+        let rows = sqlx::query_as!(
+            CommentTreeRow,
+            r#"
+            WITH RECURSIVE counted AS (
+                SELECT
+                    pc.id, pc.post_id, pc.parent_id, pc.author_id, pc.content,
+                    pc.created_at, pc.updated_at,
+                    (SELECT COUNT(*) FROM post_comments r WHERE r.parent_id = pc.id) AS reply_count
+                FROM post_comments pc
+                WHERE pc.post_id = $1
+            ),
+            ranked AS (
+                SELECT
+                    c.*,
+                    COUNT(*) OVER (PARTITION BY c.parent_id) AS sibling_count,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY c.parent_id
+                        ORDER BY
+                            CASE WHEN $3 = 'top' THEN c.reply_count END DESC,
+                            CASE WHEN $3 = 'oldest' THEN c.created_at END ASC,
+                            CASE WHEN $3 = 'oldest' THEN c.id END ASC,
+                            c.created_at DESC,
+                            c.id DESC
+                    ) AS position
+                FROM counted c
+            ),
+            tree AS (
+                SELECT ranked.*, 1 AS depth
+                FROM ranked
+                WHERE ranked.parent_id IS NOT DISTINCT FROM $2
+                    AND ranked.position > $4 AND ranked.position <= $4 + $5
+                UNION ALL
+                SELECT ranked.*, tree.depth + 1
+                FROM ranked
+                JOIN tree ON ranked.parent_id = tree.id
+                WHERE tree.depth < $6 AND ranked.position <= $7
+            )
+            SELECT
+                t.id as "id!", t.post_id as "post_id!", t.parent_id, t.author_id as "author_id!",
+                t.content as "content!", t.created_at as "created_at!",
+                t.updated_at as "updated_at!",
+                u.username as "author_username", u.full_name as "author_full_name",
+                u.avatar_url as "author_avatar_url", u.bio as "author_bio",
+                t.depth as "depth!", t.position as "position!",
+                t.sibling_count as "sibling_count!", t.reply_count as "reply_count!"
+            FROM tree t
+            JOIN users u ON t.author_id = u.id
+            "#,
+            post_id,
+            request.parent_id,
+            request.sort.as_str(),
+            request.offset,
+            request.limit,
+            request.max_depth,
+            request.replies_limit
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(CommentTree::from_rows(rows, request))
+    }
+
+    async fn get_comment(&self, comment_id: &Uuid) -> anyhow::Result<Option<Comment>> {
+        let comment = sqlx::query_as!(
+            CommentWithAuthor,
+            r#"
+            SELECT
+                pc.id, pc.post_id, pc.parent_id, pc.author_id, pc.content,
+                pc.created_at as "created_at!", pc.updated_at as "updated_at!",
+                u.username as "author_username", u.full_name as "author_full_name",
+                u.avatar_url as "author_avatar_url", u.bio as "author_bio"
+            FROM post_comments pc
+            JOIN users u ON pc.author_id = u.id
+            WHERE pc.id = $1
+            "#,
+            comment_id
+        )
+        .fetch_optional(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(comment.map(Into::into))
+    }
+
+    async fn create_comment(&self, comment: &CreateComment) -> anyhow::Result<Comment> {
+        sqlx::query!(
+            r#"
+            INSERT INTO post_comments (id, post_id, parent_id, author_id, content)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            comment.id,
+            comment.post_id,
+            comment.parent_id,
+            comment.author_id,
+            comment.content
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        self.on_primary()
+            .get_comment(&comment.id)
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
+    }
+
+    async fn update_comment(&self, comment_id: &Uuid, content: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE post_comments SET content = $2 WHERE id = $1",
+            comment_id,
+            content
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_comment(&self, comment_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query!("DELETE FROM post_comments WHERE id = $1", comment_id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl FollowRepository for Database {
+    async fn add_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        if kind == FollowKind::Block {
+            sqlx::query!(
+                r#"
+                DELETE FROM follows
+                WHERE kind = 'follow'
+                    AND ((user_id = $1 AND target_id = $2) OR (user_id = $2 AND target_id = $1))
+                "#,
+                user_id,
+                target_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO follows (user_id, target_id, kind)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, target_id, kind) DO NOTHING
+            "#,
+            user_id,
+            target_id,
+            kind as FollowKind
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM follows WHERE user_id = $1 AND target_id = $2 AND kind = $3",
+            user_id,
+            target_id,
+            kind as FollowKind
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_follow_edges(
+        &self,
+        user_id: &Uuid,
+        other_id: &Uuid,
+    ) -> anyhow::Result<Vec<FollowEdge>> {
+        let edges = sqlx::query_as!(
+            FollowEdge,
+            r#"
+            SELECT user_id, target_id, kind as "kind: FollowKind"
+            FROM follows
+            WHERE (user_id = $1 AND target_id = $2) OR (user_id = $2 AND target_id = $1)
+            "#,
+            user_id,
+            other_id
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(edges)
+    }
+
+    async fn get_followers_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        let followers = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    FollowUserRow,
+                    r#"
+                    SELECT
+                        u.id, u.username, u.full_name, u.avatar_url, u.bio,
+                        f.created_at as "followed_at"
+                    FROM follows f
+                    JOIN users u ON f.user_id = u.id
+                    WHERE f.target_id = $1 AND f.kind = 'follow'
+                        AND (f.created_at, f.user_id) > ($2, $3)
+                    ORDER BY f.created_at ASC, f.user_id ASC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    FollowUserRow,
+                    r#"
+                    SELECT
+                        u.id, u.username, u.full_name, u.avatar_url, u.bio,
+                        f.created_at as "followed_at"
+                    FROM follows f
+                    JOIN users u ON f.user_id = u.id
+                    WHERE f.target_id = $1 AND f.kind = 'follow'
+                        AND ($2::timestamptz IS NULL OR (f.created_at, f.user_id) < ($2, $3))
+                    ORDER BY f.created_at DESC, f.user_id DESC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        let followers: Vec<FollowUser> = followers.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(followers, page, |follower| {
+            (follower.followed_at, follower.user.id)
+        }))
+    }
+
+    async fn get_following_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        let following = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    FollowUserRow,
+                    r#"
+                    SELECT
+                        u.id, u.username, u.full_name, u.avatar_url, u.bio,
+                        f.created_at as "followed_at"
+                    FROM follows f
+                    JOIN users u ON f.target_id = u.id
+                    WHERE f.user_id = $1 AND f.kind = 'follow'
+                        AND (f.created_at, f.target_id) > ($2, $3)
+                    ORDER BY f.created_at ASC, f.target_id ASC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    FollowUserRow,
+                    r#"
+                    SELECT
+                        u.id, u.username, u.full_name, u.avatar_url, u.bio,
+                        f.created_at as "followed_at"
+                    FROM follows f
+                    JOIN users u ON f.target_id = u.id
+                    WHERE f.user_id = $1 AND f.kind = 'follow'
+                        AND ($2::timestamptz IS NULL OR (f.created_at, f.target_id) < ($2, $3))
+                    ORDER BY f.created_at DESC, f.target_id DESC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        let following: Vec<FollowUser> = following.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(following, page, |followed| {
+            (followed.followed_at, followed.user.id)
+        }))
+    }
+
+    async fn get_feed_subscribers(&self, author_id: &Uuid) -> anyhow::Result<Vec<Uuid>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT f.user_id
+            FROM follows f
+            WHERE f.target_id = $1 AND f.kind = 'follow'
+                AND NOT EXISTS (
+                    SELECT 1 FROM follows m
+                    WHERE m.user_id = f.user_id AND m.target_id = $1 AND m.kind = 'mute'
+                )
+            "#,
+            author_id
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.user_id).collect())
+    }
+
+    async fn get_home_feed_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        let posts = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    PostWithAuthor,
+                    r#"
+                    SELECT
+                        p.id, p.title, p.content, p.author_id,
+                        p.created_at as "created_at!", p.updated_at as "updated_at!",
+                        u.email as "author_email!", u.username as "author_username",
+                        u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
+                        u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
+                        p.likes_count, p.comments_count,
+                        (ul.user_id IS NOT NULL) as "is_liked!"
+                    FROM posts p
+                    JOIN users u ON p.author_id = u.id
+                    LEFT JOIN post_likes ul ON p.id = ul.post_id AND ul.user_id = $1
+                    WHERE p.deleted_at IS NULL
+                        AND (p.author_id = $1 OR p.author_id IN (
+                            SELECT f.target_id FROM follows f
+                            WHERE f.user_id = $1 AND f.kind = 'follow'
+                                AND NOT EXISTS (
+                                    SELECT 1 FROM follows m
+                                    WHERE m.user_id = $1 AND m.target_id = f.target_id
+                                        AND m.kind = 'mute'
+                                )
+                        ))
+                        AND (p.created_at, p.id) > ($2, $3)
+                    ORDER BY p.created_at ASC, p.id ASC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    PostWithAuthor,
+                    r#"
+                    SELECT
+                        p.id, p.title, p.content, p.author_id,
+                        p.created_at as "created_at!", p.updated_at as "updated_at!",
+                        u.email as "author_email!", u.username as "author_username",
+                        u.full_name as "author_full_name", u.avatar_url as "author_avatar_url",
+                        u.bio as "author_bio", u.created_at as "author_created_at!", u.updated_at as "author_updated_at!",
+                        p.likes_count, p.comments_count,
+                        (ul.user_id IS NOT NULL) as "is_liked!"
+                    FROM posts p
+                    JOIN users u ON p.author_id = u.id
+                    LEFT JOIN post_likes ul ON p.id = ul.post_id AND ul.user_id = $1
+                    WHERE p.deleted_at IS NULL
+                        AND (p.author_id = $1 OR p.author_id IN (
+                            SELECT f.target_id FROM follows f
+                            WHERE f.user_id = $1 AND f.kind = 'follow'
+                                AND NOT EXISTS (
+                                    SELECT 1 FROM follows m
+                                    WHERE m.user_id = $1 AND m.target_id = f.target_id
+                                        AND m.kind = 'mute'
+                                )
+                        ))
+                        AND ($2::timestamptz IS NULL OR (p.created_at, p.id) < ($2, $3))
+                    ORDER BY p.created_at DESC, p.id DESC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        let posts: Vec<Post> = posts.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(posts, page, |post| (post.created_at, post.id)))
+    }
+
+    async fn get_home_feed_entries(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+    ) -> anyhow::Result<Vec<FeedEntry>> {
+        let entries = sqlx::query_as!(
+            FeedEntry,
+            r#"
+            SELECT p.id as "post_id", p.created_at as "created_at!"
+            FROM posts p
+            WHERE p.deleted_at IS NULL
+                AND (p.author_id = $1 OR p.author_id IN (
+                    SELECT f.target_id FROM follows f
+                    WHERE f.user_id = $1 AND f.kind = 'follow'
+                        AND NOT EXISTS (
+                            SELECT 1 FROM follows m
+                            WHERE m.user_id = $1 AND m.target_id = f.target_id AND m.kind = 'mute'
+                        )
+                ))
+            ORDER BY p.created_at DESC, p.id DESC
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(entries)
+    }
+}
+
+impl Database {
+    async fn get_message(&self, message_id: &Uuid) -> anyhow::Result<Option<Message>> {
+        let message = sqlx::query_as!(
+            MessageWithSender,
+            r#"
+            SELECT
+                m.id, m.chat_id, m.sender_id, m.content,
+                m.message_type as "message_type: MessageType", m.metadata,
+                m.created_at as "created_at!",
+                u.email as "sender_email", u.username as "sender_username",
+                u.full_name as "sender_full_name", u.avatar_url as "sender_avatar_url",
+                u.bio as "sender_bio", u.created_at as "sender_created_at!",
+                u.updated_at as "sender_updated_at!"
+            FROM messages m
+            JOIN users u ON m.sender_id = u.id
+            WHERE m.id = $1
+            "#,
+            message_id
+        )
+        .fetch_optional(&mut *self.conn().await?)
+        .await?;
+
+        Ok(message.map(Into::into))
+    }
+}
+
+#[async_trait]
+impl ChatRepository for Database {
+    async fn create_chat(&self, chat: &CreateChat) -> anyhow::Result<Chat> {
+        let mut participant_ids = vec![chat.created_by];
+        for user_id in &chat.participant_ids {
+            if !participant_ids.contains(user_id) {
+                participant_ids.push(*user_id);
+            }
+        }
+
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let created = sqlx::query_as!(
+            Chat,
+            r#"
+            INSERT INTO chats (id, name, chat_type, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, chat_type as "chat_type: ChatType",
+                created_at as "created_at!", updated_at as "updated_at!"
+            "#,
+            chat.id,
+            chat.name,
+            chat.chat_type.clone() as ChatType,
+            chat.created_by
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO chat_participants (chat_id, user_id)
+            SELECT $1, UNNEST($2::uuid[])
+            "#,
+            chat.id,
+            &participant_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(created)
+    }
+
+    async fn get_chat(&self, chat_id: &Uuid) -> anyhow::Result<Option<Chat>> {
+        let chat = sqlx::query_as!(
+            Chat,
+            r#"
+            SELECT id, name, chat_type as "chat_type: ChatType",
+                created_at as "created_at!", updated_at as "updated_at!"
+            FROM chats
+            WHERE id = $1
+            "#,
+            chat_id
+        )
+        .fetch_optional(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(chat)
+    }
+
+    async fn get_user_chats(&self, user_id: &Uuid) -> anyhow::Result<Vec<Chat>> {
+        let chats = sqlx::query_as!(
+            Chat,
+            r#"
+            SELECT c.id, c.name, c.chat_type as "chat_type: ChatType",
+                c.created_at as "created_at!", c.updated_at as "updated_at!"
+            FROM chats c
+            JOIN chat_participants cp ON cp.chat_id = c.id
+            WHERE cp.user_id = $1 AND cp.left_at IS NULL
+            ORDER BY c.updated_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(chats)
+    }
+
+    async fn is_chat_participant(&self, chat_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let row = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM chat_participants
+                WHERE chat_id = $1 AND user_id = $2 AND left_at IS NULL
+            ) as "is_participant!"
+            "#,
+            chat_id,
+            user_id
+        )
+        .fetch_one(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(row.is_participant)
+    }
+
+    async fn create_message(&self, message: &CreateMessage) -> anyhow::Result<Message> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO messages (id, chat_id, sender_id, content, message_type, metadata)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING created_at as "created_at!"
+            "#,
+            message.id,
+            message.chat_id,
+            message.sender_id,
+            message.content,
+            message.message_type.clone() as MessageType,
+            message.metadata
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE chats SET updated_at = NOW() WHERE id = $1",
+            message.chat_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let event = DomainEvent::MessageSent {
+            message_id: message.id,
+            chat_id: message.chat_id,
+            sender_id: message.sender_id,
+            content: message.content.clone(),
+            created_at: row.created_at,
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+        // `get_message` needs the connection, which inside a transaction is this one
+        drop(conn);
+
+        self.get_message(&message.id)
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
+    }
+
+    async fn get_messages(
+        &self,
+        chat_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Message>> {
+        let messages = sqlx::query_as!(
+            MessageWithSender,
+            r#"
+            SELECT
+                m.id, m.chat_id, m.sender_id, m.content,
+                m.message_type as "message_type: MessageType", m.metadata,
+                m.created_at as "created_at!",
+                u.email as "sender_email", u.username as "sender_username",
+                u.full_name as "sender_full_name", u.avatar_url as "sender_avatar_url",
+                u.bio as "sender_bio", u.created_at as "sender_created_at!",
+                u.updated_at as "sender_updated_at!"
+            FROM messages m
+            JOIN users u ON m.sender_id = u.id
+            WHERE m.chat_id = $1
+            ORDER BY m.created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            chat_id,
+            limit,
+            offset
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_messages_page(
+        &self,
+        chat_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Message>> {
+        let messages = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    MessageWithSender,
+                    r#"
+                    SELECT
+                        m.id, m.chat_id, m.sender_id, m.content,
+                        m.message_type as "message_type: MessageType", m.metadata,
+                        m.created_at as "created_at!",
+                        u.email as "sender_email", u.username as "sender_username",
+                        u.full_name as "sender_full_name", u.avatar_url as "sender_avatar_url",
+                        u.bio as "sender_bio", u.created_at as "sender_created_at!",
+                        u.updated_at as "sender_updated_at!"
+                    FROM messages m
+                    JOIN users u ON m.sender_id = u.id
+                    WHERE m.chat_id = $1 AND (m.created_at, m.id) > ($2, $3)
+                    ORDER BY m.created_at ASC, m.id ASC
+                    LIMIT $4
+                    "#,
+                    chat_id,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    MessageWithSender,
+                    r#"
+                    SELECT
+                        m.id, m.chat_id, m.sender_id, m.content,
+                        m.message_type as "message_type: MessageType", m.metadata,
+                        m.created_at as "created_at!",
+                        u.email as "sender_email", u.username as "sender_username",
+                        u.full_name as "sender_full_name", u.avatar_url as "sender_avatar_url",
+                        u.bio as "sender_bio", u.created_at as "sender_created_at!",
+                        u.updated_at as "sender_updated_at!"
+                    FROM messages m
+                    JOIN users u ON m.sender_id = u.id
+                    WHERE m.chat_id = $1
+                        AND ($2::timestamptz IS NULL OR (m.created_at, m.id) < ($2, $3))
+                    ORDER BY m.created_at DESC, m.id DESC
+                    LIMIT $4
+                    "#,
+                    chat_id,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        let messages: Vec<Message> = messages.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(messages, page, |message| {
+            (message.created_at, message.id)
+        }))
+    }
+}
+
+#[async_trait]
+impl NotificationRepository for Database {
+    async fn create_notification(
+        &self,
+        notification: &CreateNotification,
+    ) -> anyhow::Result<Notification> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let notification = sqlx::query_as!(
+            Notification,
+            r#"
+            INSERT INTO notifications (id, user_id, notification_type, title, message, metadata)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, notification_type, title, message, read, metadata,
+                created_at as "created_at!"
+            "#,
+            notification.id,
+            notification.user_id,
+            notification.notification_type,
+            notification.title,
+            notification.message,
+            notification.metadata
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let event = DomainEvent::NotificationCreated {
+            notification_id: notification.id,
+            user_id: notification.user_id,
+            notification_type: notification.notification_type.clone(),
+            title: notification.title.clone(),
+            message: notification.message.clone(),
+            created_at: notification.created_at,
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+
+        Ok(notification)
+    }
+
+    async fn get_notifications(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Notification>> {
+        let notifications = sqlx::query_as!(
+            Notification,
+            r#"
+            SELECT id, user_id, notification_type, title, message, read, metadata,
+                created_at as "created_at!"
+            FROM notifications
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(notifications)
+    }
+
+    async fn get_notifications_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Notification>> {
+        let notifications = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    Notification,
+                    r#"
+                    SELECT id, user_id, notification_type, title, message, read, metadata,
+                        created_at as "created_at!"
+                    FROM notifications
+                    WHERE user_id = $1 AND (created_at, id) > ($2, $3)
+                    ORDER BY created_at ASC, id ASC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    Notification,
+                    r#"
+                    SELECT id, user_id, notification_type, title, message, read, metadata,
+                        created_at as "created_at!"
+                    FROM notifications
+                    WHERE user_id = $1
+                        AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3))
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT $4
+                    "#,
+                    user_id,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        Ok(CursorPage::from_rows(notifications, page, |notification| {
+            (notification.created_at, notification.id)
+        }))
+    }
+
+    async fn mark_notification_read(
+        &self,
+        notification_id: &Uuid,
+        user_id: &Uuid,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE notifications SET read = TRUE WHERE id = $1 AND user_id = $2",
+            notification_id,
+            user_id
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+// Each kind is ranked and paged on its own with `ts_rank` normalized to
+// 0..1 (flag 32), then merged; snippets are only built for the page.
+#[async_trait]
+impl SearchRepository for Database {
+    async fn search(
+        &self,
+        user_id: &Uuid,
+        request: &SearchRequest,
+    ) -> anyhow::Result<CursorPage<SearchHit>> {
+        let (rank, id) = request.cursor.map(|cursor| (cursor.rank, cursor.id)).unzip();
+        let mut hits = Vec::new();
+
+        for kind in &request.kinds {
+            let rows = match kind {
+                SearchKind::Post => {
+                    sqlx::query_as!(
+                        SearchHitRow,
+                        r#"
+                        WITH query AS (SELECT websearch_to_tsquery('english', $1) AS q),
+                        page AS (
+                            SELECT p.id, ts_rank(p.search_vector, query.q, 32)::float8 AS rank
+                            FROM posts p, query
+                            WHERE p.search_vector @@ query.q AND p.deleted_at IS NULL
+                                AND ($2::float8 IS NULL
+                                    OR (ts_rank(p.search_vector, query.q, 32)::float8, p.id)
+                                        < ($2, $3::uuid))
+                            ORDER BY rank DESC, p.id DESC
+                            LIMIT $4
+                        )
+                        SELECT
+                            p.id AS "id!", p.title AS "title?",
+                            ts_headline('english', p.content, query.q, $5) AS "snippet!",
+                            NULL::uuid AS "chat_id?", page.rank AS "rank!",
+                            p.created_at AS "created_at!"
+                        FROM page
+                        JOIN posts p ON p.id = page.id, query
+                        "#,
+                        &request.query,
+                        rank,
+                        id,
+                        request.fetch_limit(),
+                        HEADLINE_OPTIONS
+                    )
+                    .fetch_all(&mut *self.read_conn().await?)
+                    .await?
+                }
+                SearchKind::User => {
+                    sqlx::query_as!(
+                        SearchHitRow,
+                        r#"
+                        WITH query AS (SELECT websearch_to_tsquery('simple', $1) AS q),
+                        page AS (
+                            SELECT u.id, ts_rank(u.search_vector, query.q, 32)::float8 AS rank
+                            FROM users u, query
+                            WHERE u.search_vector @@ query.q
+                                AND ($2::float8 IS NULL
+                                    OR (ts_rank(u.search_vector, query.q, 32)::float8, u.id)
+                                        < ($2, $3::uuid))
+                            ORDER BY rank DESC, u.id DESC
+                            LIMIT $4
+                        )
+                        SELECT
+                            u.id AS "id!", coalesce(u.full_name, u.username) AS "title?",
+                            ts_headline('simple', concat_ws(' ', u.username, u.full_name, u.bio),
+                                query.q, $5) AS "snippet!",
+                            NULL::uuid AS "chat_id?", page.rank AS "rank!",
+                            u.created_at AS "created_at!"
+                        FROM page
+                        JOIN users u ON u.id = page.id, query
+                        "#,
+                        &request.query,
+                        rank,
+                        id,
+                        request.fetch_limit(),
+                        HEADLINE_OPTIONS
+                    )
+                    .fetch_all(&mut *self.read_conn().await?)
+                    .await?
+                }
+                SearchKind::Message => {
+                    sqlx::query_as!(
+                        SearchHitRow,
+                        r#"
+                        WITH query AS (SELECT websearch_to_tsquery('english', $1) AS q),
+                        page AS (
+                            SELECT m.id, ts_rank(m.search_vector, query.q, 32)::float8 AS rank
+                            FROM messages m
+                            JOIN chat_participants cp ON cp.chat_id = m.chat_id
+                                AND cp.user_id = $6 AND cp.left_at IS NULL,
+                                query
+                            WHERE m.search_vector @@ query.q
+                                AND ($2::float8 IS NULL
+                                    OR (ts_rank(m.search_vector, query.q, 32)::float8, m.id)
+                                        < ($2, $3::uuid))
+                            ORDER BY rank DESC, m.id DESC
+                            LIMIT $4
+                        )
+                        SELECT
+                            m.id AS "id!", u.username AS "title?",
+                            ts_headline('english', m.content, query.q, $5) AS "snippet!",
+                            m.chat_id AS "chat_id?", page.rank AS "rank!",
+                            m.created_at AS "created_at!"
+                        FROM page
+                        JOIN messages m ON m.id = page.id
+                        JOIN users u ON u.id = m.sender_id, query
+                        "#,
+                        &request.query,
+                        rank,
+                        id,
+                        request.fetch_limit(),
+                        HEADLINE_OPTIONS,
+                        user_id
+                    )
+                    .fetch_all(&mut *self.read_conn().await?)
+                    .await?
+                }
+            };
+
+            hits.extend(rows.into_iter().map(|row| row.into_hit(*kind)));
+        }
+
+        Ok(CursorPage::from_hits(hits, request))
+    }
+}
+
+// Several relays can run at once: each claims a batch with SKIP LOCKED and
+// holds it through `locked_until` rather than an open transaction.
+#[async_trait]
+impl OutboxRepository for Database {
+    async fn claim_outbox_events(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> anyhow::Result<Vec<OutboxRow>> {
+        let mut rows = sqlx::query_as!(
+            OutboxRow,
+            r#"
+            UPDATE outbox
+            SET locked_until = NOW() + make_interval(secs => $2), attempts = attempts + 1
+            WHERE id IN (
+                SELECT id
+                FROM outbox
+                WHERE published_at IS NULL AND (locked_until IS NULL OR locked_until < NOW())
+                ORDER BY id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, dedup_key, payload, created_at
+            "#,
+            limit,
+            lease.as_secs_f64()
+        )
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        // RETURNING doesn't keep the subquery's order
+        rows.sort_by_key(|row| row.id);
+        Ok(rows)
+    }
+
+    async fn mark_outbox_published(&self, event_ids: &[i64]) -> anyhow::Result<()> {
+        sqlx::query!(
+            "UPDATE outbox SET published_at = NOW(), locked_until = NULL WHERE id = ANY($1)",
+            event_ids
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn purge_outbox(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let result = sqlx::query!("DELETE FROM outbox WHERE published_at < $1", cutoff)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl AuditRepository for Database {
+    async fn append_audit_event(&self, event: &NewAuditEvent) -> anyhow::Result<AuditEvent> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        // Blocks other appends (but not reads) until this one commits, so
+        // two entries never link to the same predecessor
+        sqlx::query!("LOCK TABLE audit_events IN SHARE ROW EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await?;
+
+        let previous = sqlx::query!("SELECT seq, hash FROM audit_events ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|row| (row.seq, row.hash));
+
+        let event = event.link(previous);
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_events (
+                id, seq, occurred_at, action, actor_id, target_type, target_id, ip,
+                user_agent, request_id, before, after, prev_hash, hash
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            "#,
+            event.id,
+            event.seq,
+            event.occurred_at,
+            event.action,
+            event.actor_id,
+            event.target_type,
+            event.target_id,
+            event.ip,
+            event.user_agent,
+            event.request_id,
+            event.before,
+            event.after,
+            event.prev_hash,
+            event.hash
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(event)
+    }
+
+    async fn get_audit_events_page(
+        &self,
+        filter: &AuditFilter,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<AuditEvent>> {
+        let events = match page.cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                sqlx::query_as!(
+                    AuditEvent,
+                    r#"
+                    SELECT id, seq, occurred_at, action, actor_id, target_type, target_id, ip,
+                           user_agent, request_id, before, after, prev_hash, hash
+                    FROM audit_events
+                    WHERE ($1::uuid IS NULL OR actor_id = $1)
+                        AND ($2::uuid IS NULL OR target_id = $2)
+                        AND ($3::text IS NULL OR action = $3)
+                        AND ($4::timestamptz IS NULL OR occurred_at >= $4)
+                        AND ($5::timestamptz IS NULL OR occurred_at < $5)
+                        AND (occurred_at, id) > ($6, $7)
+                    ORDER BY occurred_at ASC, id ASC
+                    LIMIT $8
+                    "#,
+                    filter.actor_id,
+                    filter.target_id,
+                    filter.action,
+                    filter.since,
+                    filter.until,
+                    cursor.created_at,
+                    cursor.id,
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+            cursor => {
+                sqlx::query_as!(
+                    AuditEvent,
+                    r#"
+                    SELECT id, seq, occurred_at, action, actor_id, target_type, target_id, ip,
+                           user_agent, request_id, before, after, prev_hash, hash
+                    FROM audit_events
+                    WHERE ($1::uuid IS NULL OR actor_id = $1)
+                        AND ($2::uuid IS NULL OR target_id = $2)
+                        AND ($3::text IS NULL OR action = $3)
+                        AND ($4::timestamptz IS NULL OR occurred_at >= $4)
+                        AND ($5::timestamptz IS NULL OR occurred_at < $5)
+                        AND ($6::timestamptz IS NULL OR (occurred_at, id) < ($6, $7))
+                    ORDER BY occurred_at DESC, id DESC
+                    LIMIT $8
+                    "#,
+                    filter.actor_id,
+                    filter.target_id,
+                    filter.action,
+                    filter.since,
+                    filter.until,
+                    cursor.map(|cursor| cursor.created_at),
+                    cursor.map(|cursor| cursor.id),
+                    page.fetch_limit()
+                )
+                .fetch_all(&mut *self.read_conn().await?)
+                .await?
+            }
+        };
+
+        Ok(CursorPage::from_rows(events, page, |event| (event.occurred_at, event.id)))
+    }
+
+    async fn get_audit_chain(&self, after_seq: i64, limit: i64) -> anyhow::Result<Vec<AuditEvent>> {
+        let events = sqlx::query_as!(
+            AuditEvent,
+            r#"
+            SELECT id, seq, occurred_at, action, actor_id, target_type, target_id, ip,
+                   user_agent, request_id, before, after, prev_hash, hash
+            FROM audit_events
+            WHERE seq > $1
+            ORDER BY seq
+            LIMIT $2
+            "#,
+            after_seq,
+            limit
+        )
+        .fetch_all(&mut *self.read_conn().await?)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+#[async_trait]
+impl TransactionRepository for Database {
+    async fn transaction_with(
+        &self,
+        isolation: Isolation,
+        work: UnitOfWork<'_>,
+    ) -> anyhow::Result<()> {
+        Database::transaction_with(self, isolation, |scoped| work(Arc::new(scoped))).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    // Each test gets a database of its own, migrated by `MIGRATOR`, on the
+    // server at `DATABASE_URL`
+    fn database(pool: PgPool) -> Database {
+        Database {
+            pool,
+            replicas: None,
+            scope: None,
+        }
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn transactions_commit_or_roll_back(pool: PgPool) {
+        let database = database(pool);
+
+        testing::committed_work_is_kept(&database).await;
+        testing::failed_work_is_rolled_back(&database).await;
+        testing::nested_work_rolls_back_on_its_own(&database).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn keyset_pages_walk_both_ways(pool: PgPool) {
+        testing::posts_page_by_keyset(&database(pool)).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn comment_trees_nest_and_slice(pool: PgPool) {
+        testing::comment_trees_nest_and_slice(&database(pool)).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn counter_triggers_follow_likes_and_comments(pool: PgPool) {
+        testing::post_counters_follow_likes_and_comments(&database(pool)).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn audit_appends_extend_the_chain(pool: PgPool) {
+        testing::audit_appends_extend_the_chain(&database(pool)).await;
+    }
+}
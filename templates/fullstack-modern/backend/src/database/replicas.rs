@@ -0,0 +1,381 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use sqlx::{pool::PoolConnection, postgres::PgPoolOptions, PgPool, Postgres};
+use uuid::Uuid;
+
+use super::{transaction::Conn, Database};
+use crate::config::Secret;
+
+// Read-replica routing. Read-only repository methods run on a replica,
+// round-robin; everything else, and every query inside a transaction, runs on
+// the primary. After a write, reads go to the primary for
+// `read_your_writes_secs` so the writer sees its own changes despite
+// replication lag. This instance remembers which users wrote, and the
+// response carries the write time in a cookie so the client's next requests
+// stick to the primary on any instance, signed in or not. Replicas that
+// can't hand out a connection are skipped for a while and reads fall back
+// to the primary.
+
+// Replicas get this long to hand out a connection before a read falls back
+const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How long a replica that failed is left out of the rotation
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+// Tracked users beyond which expired ones are swept on the next write
+const SWEEP_THRESHOLD: usize = 10_000;
+
+// Milliseconds since the epoch of the client's last write on the primary
+const LAST_WRITE_COOKIE: &str = "last_write";
+
+// One request's view of read-your-writes
+#[derive(Debug, Default)]
+struct Session {
+    // Set once the request authenticates
+    user_id: OnceLock<Uuid>,
+    // From the cookie the client sent back
+    last_write: Option<DateTime<Utc>>,
+    wrote: AtomicBool,
+}
+
+tokio::task_local! {
+    static SESSION: Arc<Session>;
+}
+
+/// Middleware giving each request a session for read-your-writes
+/// stickiness, and telling the client when a request wrote.
+pub async fn read_your_writes(request: Request, next: Next) -> Response {
+    let session = Arc::new(Session {
+        last_write: last_write(request.headers()),
+        ..Session::default()
+    });
+
+    let mut response = SESSION.scope(session.clone(), next.run(request)).await;
+    if session.wrote.load(Ordering::Relaxed) {
+        let cookie = format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax",
+            LAST_WRITE_COOKIE,
+            Utc::now().timestamp_millis()
+        );
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+
+    response
+}
+
+/// Ties the current request's session to the user it authenticated as.
+pub fn authenticated(user_id: Uuid) {
+    let _ = SESSION.try_with(|session| session.user_id.set(user_id));
+}
+
+fn last_write(headers: &HeaderMap) -> Option<DateTime<Utc>> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            cookie
+                .trim()
+                .strip_prefix(LAST_WRITE_COOKIE)?
+                .strip_prefix('=')
+        })
+        .and_then(|millis| millis.parse().ok())
+        .and_then(DateTime::from_timestamp_millis)
+}
+
+#[derive(Debug)]
+struct Replica {
+    pool: PgPool,
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl Replica {
+    fn is_down(&self) -> bool {
+        let down_until = self.down_until.lock().unwrap_or_else(PoisonError::into_inner);
+        down_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    fn mark_down(&self) {
+        *self.down_until.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some(Instant::now() + RETRY_AFTER);
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct Replicas {
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+    sticky_for: Duration,
+    // When each user last wrote through this instance
+    writes: Mutex<HashMap<Uuid, Instant>>,
+}
+
+impl Replicas {
+    /// Pools connect lazily, so a replica that is down at startup only
+    /// costs a fallback to the primary.
+    pub(super) fn connect(
+        urls: &[Secret],
+        max_connections: u32,
+        sticky_for: Duration,
+    ) -> anyhow::Result<Self> {
+        let replicas = urls
+            .iter()
+            .map(|url| {
+                let pool = PgPoolOptions::new()
+                    .max_connections(max_connections)
+                    .acquire_timeout(ACQUIRE_TIMEOUT)
+                    .connect_lazy(url.expose())?;
+                Ok(Replica {
+                    pool,
+                    down_until: Mutex::new(None),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            replicas,
+            next: AtomicUsize::new(0),
+            sticky_for,
+            writes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub(super) fn record_write(&self) {
+        let Ok(user_id) = SESSION.try_with(|session| {
+            session.wrote.store(true, Ordering::Relaxed);
+            session.user_id.get().copied()
+        }) else {
+            return;
+        };
+        let Some(user_id) = user_id else {
+            return;
+        };
+
+        let now = Instant::now();
+        let mut writes = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+        if writes.len() >= SWEEP_THRESHOLD {
+            writes.retain(|_, at| now.duration_since(*at) < self.sticky_for);
+        }
+        writes.insert(user_id, now);
+    }
+
+    fn wrote_recently(&self) -> bool {
+        SESSION
+            .try_with(|session| {
+                session.wrote.load(Ordering::Relaxed)
+                    || session.last_write.is_some_and(|at| self.is_recent(at))
+                    || session.user_id.get().is_some_and(|user_id| {
+                        let writes = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+                        writes
+                            .get(user_id)
+                            .is_some_and(|at| at.elapsed() < self.sticky_for)
+                    })
+            })
+            .unwrap_or(false)
+    }
+
+    // Whether a write the client reports is inside the window. Times in the
+    // future are ignored so a forged cookie can't pin a client to the primary.
+    fn is_recent(&self, at: DateTime<Utc>) -> bool {
+        (Utc::now() - at)
+            .to_std()
+            .is_ok_and(|elapsed| elapsed < self.sticky_for)
+    }
+
+    // A replica connection, unless the session must read its own writes or
+    // no replica is reachable
+    async fn acquire(&self) -> Option<PoolConnection<Postgres>> {
+        if self.wrote_recently() {
+            return None;
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for offset in 0..self.replicas.len() {
+            let index = (start + offset) % self.replicas.len();
+            let replica = &self.replicas[index];
+            if replica.is_down() {
+                continue;
+            }
+
+            match replica.pool.acquire().await {
+                Ok(conn) => return Some(conn),
+                Err(e) => {
+                    tracing::warn!(replica = index, "Replica unavailable, skipping it: {}", e);
+                    replica.mark_down();
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Database {
+    // Connection for a read-only query: a replica when one is usable,
+    // otherwise the primary (or the transaction this handle is scoped to)
+    pub(super) async fn read_conn(&self) -> sqlx::Result<Conn<'_>> {
+        if let (None, Some(replicas)) = (&self.scope, &self.replicas) {
+            if let Some(conn) = replicas.acquire().await {
+                return Ok(Conn::Pooled(Box::new(conn)));
+            }
+        }
+
+        self.primary_conn().await
+    }
+
+    // This handle with every read on the primary, for reading back a write
+    // outside any request session (which would otherwise make it sticky)
+    pub(super) fn on_primary(&self) -> Database {
+        Database {
+            replicas: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware::from_fn, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    // Nothing listens on port 1; acquiring a connection gives up after
+    // ACQUIRE_TIMEOUT
+    fn unreachable(sticky_for: Duration) -> Replicas {
+        let urls = [Secret::new("postgres://replica@127.0.0.1:1/app")];
+        Replicas::connect(&urls, 1, sticky_for).unwrap()
+    }
+
+    fn signed_in(user_id: Uuid) -> Arc<Session> {
+        let session = Session::default();
+        session.user_id.set(user_id).unwrap();
+        Arc::new(session)
+    }
+
+    #[tokio::test]
+    async fn writes_stick_to_their_user() {
+        let replicas = unreachable(Duration::from_millis(50));
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+
+        SESSION
+            .scope(signed_in(alice), async { replicas.record_write() })
+            .await;
+        assert!(
+            SESSION
+                .scope(signed_in(alice), async { replicas.wrote_recently() })
+                .await
+        );
+        assert!(
+            !SESSION
+                .scope(signed_in(bob), async { replicas.wrote_recently() })
+                .await
+        );
+        assert!(!replicas.wrote_recently());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(
+            !SESSION
+                .scope(signed_in(alice), async { replicas.wrote_recently() })
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_outside_a_session_are_not_tracked() {
+        let replicas = unreachable(Duration::from_secs(60));
+
+        replicas.record_write();
+        assert!(replicas.writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_recent_cookie_writes_stick() {
+        let replicas = unreachable(Duration::from_secs(5));
+        let wrote_at = |at: DateTime<Utc>| {
+            let session = Arc::new(Session {
+                last_write: Some(at),
+                ..Session::default()
+            });
+            SESSION.scope(session, async { replicas.wrote_recently() })
+        };
+
+        assert!(wrote_at(Utc::now() - chrono::Duration::seconds(1)).await);
+        assert!(!wrote_at(Utc::now() - chrono::Duration::seconds(10)).await);
+        assert!(!wrote_at(Utc::now() + chrono::Duration::hours(1)).await);
+    }
+
+    #[tokio::test]
+    async fn unreachable_replicas_fall_back_and_sit_out() {
+        let replicas = unreachable(Duration::from_secs(60));
+
+        assert!(replicas.acquire().await.is_none());
+        assert!(replicas.replicas[0].is_down());
+        // Skipped without another wait while it sits out
+        let start = Instant::now();
+        assert!(replicas.acquire().await.is_none());
+        assert!(start.elapsed() < ACQUIRE_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn writes_hand_the_client_a_cookie_that_sticks() {
+        let replicas = Arc::new(unreachable(Duration::from_secs(60)));
+        let (writer, reader) = (replicas.clone(), replicas.clone());
+        let router = Router::new()
+            .route("/write", get(move || async move { writer.record_write() }))
+            .route(
+                "/read",
+                get(move || async move { reader.wrote_recently().to_string() }),
+            )
+            .layer(from_fn(read_your_writes));
+        let send = |uri: &'static str, cookie: Option<String>| {
+            let router = router.clone();
+            async move {
+                let mut request = Request::builder().uri(uri);
+                if let Some(cookie) = cookie {
+                    request = request.header(header::COOKIE, cookie);
+                }
+                router
+                    .oneshot(request.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let response = send("/read", None).await;
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+
+        let response = send("/write", None).await;
+        let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+        assert!(cookie.starts_with("last_write="));
+
+        for (cookie, expected) in [
+            (None, "false"),
+            (Some(format!("theme=dark; {}", cookie)), "true"),
+        ] {
+            let response = send("/read", cookie).await;
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, expected);
+        }
+    }
+}
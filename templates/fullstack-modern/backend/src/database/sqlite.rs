@@ -0,0 +1,1893 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    pool::PoolConnection,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    Connection, Row, Sqlite, SqliteConnection, SqlitePool, Transaction,
+};
+use tokio::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+use super::{migration_list, Isolation, MigrationStatus};
+use crate::{
+    models::{
+        AuditEvent, AuditFilter, Chat, Comment, CommentTree, CommentTreeRequest, CommentTreeRow,
+        CommentWithAuthor, CreateChat, CreateComment, CreateMessage, CreateNotification,
+        CreatePost, CreateUser, Cursor, CursorDirection, CursorPage, DomainEvent, FeedEntry,
+        FollowEdge, FollowKind, FollowUser, FollowUserRow, Message, MessageWithSender,
+        NewAuditEvent, Notification, OutboxRow, PageRequest, Post, PostLiker, PostLikerRow,
+        PostRevision, PostStatus, SearchHit, SearchHitRow, SearchKind, SearchRequest, UpdatePost,
+        UpdateUser, User, MATCH_END, MATCH_START,
+    },
+    repository::{
+        AuditRepository, ChatRepository, CommentRepository, FollowRepository,
+        NotificationRepository, OutboxRepository, PostRepository, SearchRepository,
+        TransactionRepository, UnitOfWork, UserRepository,
+    },
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+// Same format as the column defaults in migrations/sqlite, so TEXT timestamps sort correctly
+const NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+// Every post query selects these; ?1 is the viewer for `is_liked` (NULL when anonymous)
+const POST_SELECT: &str = r#"
+    SELECT
+        p.id, p.title, p.content, p.author_id, p.created_at, p.updated_at,
+        u.email AS author_email, u.username AS author_username,
+        u.full_name AS author_full_name, u.avatar_url AS author_avatar_url,
+        u.bio AS author_bio, u.created_at AS author_created_at,
+        u.updated_at AS author_updated_at,
+        p.likes_count, p.comments_count,
+        EXISTS (SELECT 1 FROM post_likes WHERE post_id = p.id AND user_id = ?1) AS is_liked
+    FROM posts p
+    JOIN users u ON p.author_id = u.id
+"#;
+
+const MESSAGE_SELECT: &str = r#"
+    SELECT
+        m.id, m.chat_id, m.sender_id, m.content, m.message_type, m.metadata, m.created_at,
+        u.email AS sender_email, u.username AS sender_username,
+        u.full_name AS sender_full_name, u.avatar_url AS sender_avatar_url,
+        u.bio AS sender_bio, u.created_at AS sender_created_at,
+        u.updated_at AS sender_updated_at
+    FROM messages m
+    JOIN users u ON m.sender_id = u.id
+"#;
+
+const COMMENT_SELECT: &str = r#"
+    SELECT
+        pc.id, pc.post_id, pc.parent_id, pc.author_id, pc.content, pc.created_at, pc.updated_at,
+        u.username AS author_username, u.full_name AS author_full_name,
+        u.avatar_url AS author_avatar_url, u.bio AS author_bio
+    FROM post_comments pc
+    JOIN users u ON pc.author_id = u.id
+"#;
+
+// Posts shown in ?1's home feed: their own, and those of users they follow
+// and haven't muted
+const HOME_FEED_FILTER: &str = r#"
+    p.deleted_at IS NULL
+    AND (p.author_id = ?1 OR p.author_id IN (
+        SELECT f.target_id FROM follows f
+        WHERE f.user_id = ?1 AND f.kind = 'follow'
+            AND NOT EXISTS (
+                SELECT 1 FROM follows m
+                WHERE m.user_id = ?1 AND m.target_id = f.target_id AND m.kind = 'mute'
+            )
+    ))
+"#;
+
+const FOLLOW_USER_COLUMNS: &str = r#"
+    u.id, u.username, u.full_name, u.avatar_url, u.bio, f.created_at AS followed_at
+"#;
+
+const USER_COLUMNS: &str = "id, email, username, full_name, avatar_url, bio, created_at, updated_at";
+const CHAT_COLUMNS: &str = "id, name, chat_type, created_at, updated_at";
+const NOTIFICATION_COLUMNS: &str =
+    "id, user_id, notification_type, title, message, read, metadata, created_at";
+
+const AUDIT_EVENT_COLUMNS: &str = "id, seq, occurred_at, action, actor_id, target_type, \
+    target_id, ip, user_agent, request_id, before, after, prev_hash, hash";
+
+// Keyset condition and ordering on `{alias}.created_at, {alias}.id`; the cursor
+// is bound as `?{n}`/`?{n+1}` and may be NULL for the first page
+fn keyset(alias: &str, n: usize, page: &PageRequest) -> String {
+    keyset_on(&format!("{}.created_at", alias), &format!("{}.id", alias), n, page)
+}
+
+// `keyset` for tables whose key columns are named differently
+fn keyset_on(created_at: &str, id: &str, n: usize, page: &PageRequest) -> String {
+    let (op, order) = match page.cursor.map(|cursor| cursor.direction) {
+        Some(CursorDirection::Before) => (">", "ASC"),
+        _ => ("<", "DESC"),
+    };
+    format!(
+        "(?{n} IS NULL OR ({c}, {i}) {op} (?{n}, ?{m})) ORDER BY {c} {order}, {i} {order}",
+        c = created_at,
+        i = id,
+        n = n,
+        m = n + 1,
+        op = op,
+        order = order,
+    )
+}
+
+// Timestamps compared in SQL are bound in the stored TEXT format (see `NOW`)
+// so they compare correctly as strings
+fn timestamp(value: &DateTime<Utc>) -> String {
+    value.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+fn cursor_bounds(page: &PageRequest) -> (Option<String>, Option<Uuid>) {
+    match page.cursor {
+        Some(Cursor { created_at, id, .. }) => (Some(timestamp(&created_at)), Some(id)),
+        None => (None, None),
+    }
+}
+
+// Ranked FTS5 matches as `id, rank, snippet`, for `search_sql`. bm25 is
+// negative, better matches lower; `-bm25 / (1 - bm25)` maps it onto 0..1
+// like Postgres' normalized `ts_rank`. Snippets bracket matches with
+// `MATCH_START`/`MATCH_END`, escaped into HTML by `SearchHitRow::into_hit`.
+fn fts_matches(table: &str, weights: &str, snippet_column: i32) -> String {
+    format!(
+        r#"
+        SELECT {t}.id,
+            -bm25({t}, {w}) / (1 - bm25({t}, {w})) AS rank,
+            snippet({t}, {c}, '{open}', '{close}', '…', 24) AS snippet
+        FROM {t}
+        WHERE {t} MATCH ?1
+        "#,
+        t = table,
+        w = weights,
+        c = snippet_column,
+        open = MATCH_START,
+        close = MATCH_END,
+    )
+}
+
+// One page of a kind's hits: ?1 is the FTS5 query, ?2/?3 the cursor's rank
+// and id (NULL for the first page), ?4 the limit and, for messages, ?5 the
+// user whose chats are searched
+fn search_sql(kind: SearchKind) -> String {
+    let (matches, select) = match kind {
+        SearchKind::Post => (
+            fts_matches("posts_fts", "0.0, 2.0, 1.0", 2),
+            r#"
+            SELECT s.id, p.title, s.snippet, NULL AS chat_id, s.rank, p.created_at
+            FROM matches s
+            JOIN posts p ON p.id = s.id
+            WHERE p.deleted_at IS NULL
+            "#,
+        ),
+        SearchKind::User => (
+            fts_matches("users_fts", "0.0, 2.0, 2.0, 1.0", -1),
+            r#"
+            SELECT s.id, coalesce(u.full_name, u.username) AS title, s.snippet,
+                NULL AS chat_id, s.rank, u.created_at
+            FROM matches s
+            JOIN users u ON u.id = s.id
+            WHERE 1
+            "#,
+        ),
+        SearchKind::Message => (
+            fts_matches("messages_fts", "0.0, 1.0", 1),
+            r#"
+            SELECT s.id, u.username AS title, s.snippet, m.chat_id, s.rank, m.created_at
+            FROM matches s
+            JOIN messages m ON m.id = s.id
+            JOIN users u ON u.id = m.sender_id
+            JOIN chat_participants cp ON cp.chat_id = m.chat_id
+                AND cp.user_id = ?5 AND cp.left_at IS NULL
+            WHERE 1
+            "#,
+        ),
+    };
+
+    format!(
+        r#"
+        WITH matches AS ({})
+        {} AND (?2 IS NULL OR (s.rank, s.id) < (?2, ?3))
+        ORDER BY s.rank DESC, s.id DESC
+        LIMIT ?4
+        "#,
+        matches, select
+    )
+}
+
+// Free text as an FTS5 query: every word must match, taken literally, so
+// quotes and operators in user input never reach the FTS5 parser
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Records `event` in the outbox on `conn`, which should be the transaction
+// making the change it describes
+async fn record_event(conn: &mut SqliteConnection, event: &DomainEvent) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO outbox (event_type, dedup_key, payload)
+        VALUES (?1, ?2, ?3)
+        ON CONFLICT (dedup_key) DO NOTHING
+        "#,
+    )
+    .bind(event.event_type())
+    .bind(event.dedup_key())
+    .bind(serde_json::to_value(event)?)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// `PostWithAuthor` for SQLite, which needs no `!` nullability overrides
+#[derive(sqlx::FromRow)]
+struct PostRow {
+    id: Uuid,
+    title: String,
+    content: String,
+    author_id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    author_email: String,
+    author_username: Option<String>,
+    author_full_name: Option<String>,
+    author_avatar_url: Option<String>,
+    author_bio: Option<String>,
+    author_created_at: DateTime<Utc>,
+    author_updated_at: DateTime<Utc>,
+    likes_count: i64,
+    comments_count: i64,
+    is_liked: bool,
+}
+
+impl From<PostRow> for Post {
+    fn from(row: PostRow) -> Self {
+        Self {
+            id: row.id,
+            title: row.title,
+            content: row.content,
+            author_id: row.author_id,
+            author: User {
+                id: row.author_id,
+                email: row.author_email,
+                username: row.author_username,
+                full_name: row.author_full_name,
+                avatar_url: row.author_avatar_url,
+                bio: row.author_bio,
+                created_at: row.author_created_at,
+                updated_at: row.author_updated_at,
+            },
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            likes_count: row.likes_count,
+            comments_count: row.comments_count,
+            is_liked: row.is_liked,
+        }
+    }
+}
+
+/// SQLite implementation of the repository traits, for local development,
+/// CI and single-node deployments. Selected by a `sqlite:` `DATABASE_URL`.
+#[derive(Debug, Clone)]
+pub struct SqliteDatabase {
+    pool: SqlitePool,
+    // Set on the handles `transaction_with` hands out; their queries all run in it
+    scope: Option<Scope>,
+}
+
+// The open transaction a handle is scoped to, as in `Database`; `depth`
+// counts the savepoints nested inside it
+#[derive(Clone)]
+struct Scope {
+    tx: Arc<Mutex<Transaction<'static, Sqlite>>>,
+    depth: usize,
+}
+
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope").field("depth", &self.depth).finish()
+    }
+}
+
+enum Conn<'a> {
+    // Boxed: a pooled connection is far bigger than a guard
+    Pooled(Box<PoolConnection<Sqlite>>),
+    Scoped(MutexGuard<'a, Transaction<'static, Sqlite>>),
+}
+
+impl Deref for Conn<'_> {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &SqliteConnection {
+        match self {
+            Conn::Pooled(conn) => conn,
+            Conn::Scoped(tx) => tx,
+        }
+    }
+}
+
+impl DerefMut for Conn<'_> {
+    fn deref_mut(&mut self) -> &mut SqliteConnection {
+        match self {
+            Conn::Pooled(conn) => conn,
+            Conn::Scoped(tx) => tx,
+        }
+    }
+}
+
+impl SqliteDatabase {
+    pub async fn new(database_url: &str) -> anyhow::Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(SqliteJournalMode::Wal);
+
+        // Every connection to `sqlite::memory:` is a separate database, so keep exactly one
+        let pool = if database_url.contains(":memory:") {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new().max_connections(5)
+        }
+        .connect_with(options)
+        .await?;
+
+        Ok(Self { pool, scope: None })
+    }
+
+    // A connection from the pool, or the scope's transaction once it's our turn
+    async fn conn(&self) -> sqlx::Result<Conn<'_>> {
+        match &self.scope {
+            Some(scope) => Ok(Conn::Scoped(scope.tx.lock().await)),
+            None => Ok(Conn::Pooled(Box::new(self.pool.acquire().await?))),
+        }
+    }
+
+    fn scoped(&self, scope: Scope) -> SqliteDatabase {
+        SqliteDatabase {
+            pool: self.pool.clone(),
+            scope: Some(scope),
+        }
+    }
+
+    async fn begin_work(&self, work: UnitOfWork<'_>) -> anyhow::Result<()> {
+        let tx = Arc::new(Mutex::new(self.pool.begin().await?));
+        let result = work(Arc::new(self.scoped(Scope {
+            tx: tx.clone(),
+            depth: 0,
+        })))
+        .await;
+
+        // Dropping a transaction without committing rolls it back
+        let tx = Arc::try_unwrap(tx)
+            .map_err(|_| anyhow::anyhow!("Transaction handle outlived its work"))?
+            .into_inner();
+
+        match result {
+            Ok(()) => {
+                tx.commit().await?;
+                Ok(())
+            }
+            Err(e) => {
+                if let Err(rollback) = tx.rollback().await {
+                    tracing::warn!("Transaction rollback failed: {}", rollback);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn savepoint(&self, scope: &Scope, work: UnitOfWork<'_>) -> anyhow::Result<()> {
+        let depth = scope.depth + 1;
+        let name = format!("unit_of_work_{}", depth);
+        let run = |sql: String| async move {
+            sqlx::query(&sql).execute(&mut *self.conn().await?).await
+        };
+
+        run(format!("SAVEPOINT {}", name)).await?;
+        let result = work(Arc::new(self.scoped(Scope {
+            tx: scope.tx.clone(),
+            depth,
+        })))
+        .await;
+
+        match result {
+            Ok(()) => {
+                run(format!("RELEASE SAVEPOINT {}", name)).await?;
+                Ok(())
+            }
+            Err(e) => {
+                run(format!("ROLLBACK TO SAVEPOINT {}", name)).await?;
+                run(format!("RELEASE SAVEPOINT {}", name)).await?;
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn migrate_down(&self, target: i64) -> anyhow::Result<()> {
+        MIGRATOR.undo(&self.pool, target).await?;
+        Ok(())
+    }
+
+    pub async fn migration_status(&self) -> anyhow::Result<Vec<MigrationStatus>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        let applied = conn.list_applied_migrations().await?;
+
+        Ok(migration_list(&MIGRATOR, &applied))
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    pub async fn health_check(&self) -> anyhow::Result<()> {
+        let health: i64 = sqlx::query("SELECT 1 AS health")
+            .fetch_one(&mut *self.conn().await?)
+            .await?
+            .get("health");
+
+        anyhow::ensure!(health == 1, "Database health check failed");
+        Ok(())
+    }
+
+    async fn get_message(&self, message_id: &Uuid) -> anyhow::Result<Option<Message>> {
+        let message: Option<MessageWithSender> =
+            sqlx::query_as(&format!("{} WHERE m.id = ?1", MESSAGE_SELECT))
+                .bind(message_id)
+                .fetch_optional(&mut *self.conn().await?)
+                .await?;
+
+        Ok(message.map(Into::into))
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteDatabase {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as(&format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS))
+            .bind(user_id)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as(&format!("SELECT {} FROM users WHERE email = ?1", USER_COLUMNS))
+            .bind(email)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn create_user(&self, user: &CreateUser) -> anyhow::Result<User> {
+        // fetch_all rather than fetch_one: a RETURNING statement left
+        // half-stepped keeps its write lock on the pooled connection, and the
+        // next writer fails with "database is locked"
+        let users: Vec<User> = sqlx::query_as(&format!(
+            r#"
+            INSERT INTO users (id, email, username, full_name, avatar_url, bio)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING {}
+            "#,
+            USER_COLUMNS
+        ))
+        .bind(user.id)
+        .bind(&user.email)
+        .bind(&user.username)
+        .bind(&user.full_name)
+        .bind(&user.avatar_url)
+        .bind(&user.bio)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(users.into_iter().next().ok_or(sqlx::Error::RowNotFound)?)
+    }
+
+    async fn update_user(&self, user_id: &Uuid, updates: &UpdateUser) -> anyhow::Result<User> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let user: User = sqlx::query_as(&format!(
+            r#"
+            UPDATE users
+            SET
+                username = COALESCE(?2, username),
+                full_name = COALESCE(?3, full_name),
+                avatar_url = COALESCE(?4, avatar_url),
+                bio = COALESCE(?5, bio),
+                updated_at = {}
+            WHERE id = ?1
+            RETURNING {}
+            "#,
+            NOW, USER_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(&updates.username)
+        .bind(&updates.full_name)
+        .bind(&updates.avatar_url)
+        .bind(&updates.bio)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let event = DomainEvent::UserUpdated {
+            user_id: user.id,
+            updated_at: user.updated_at,
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn delete_user(&self, user_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM users WHERE id = ?1")
+            .bind(user_id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl PostRepository for SqliteDatabase {
+    async fn get_posts(
+        &self,
+        limit: i64,
+        offset: i64,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>> {
+        let posts: Vec<PostRow> = sqlx::query_as(&format!(
+            "{} WHERE p.deleted_at IS NULL ORDER BY p.created_at DESC LIMIT ?2 OFFSET ?3",
+            POST_SELECT
+        ))
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(posts.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_post_by_id(
+        &self,
+        post_id: &Uuid,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Option<Post>> {
+        let post: Option<PostRow> = sqlx::query_as(&format!(
+            "{} WHERE p.id = ?2 AND p.deleted_at IS NULL",
+            POST_SELECT
+        ))
+            .bind(user_id)
+            .bind(post_id)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+
+        Ok(post.map(Into::into))
+    }
+
+    async fn get_posts_by_ids(
+        &self,
+        post_ids: &[Uuid],
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>> {
+        if post_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // ?1 is the viewer, the ids follow from ?2
+        let placeholders: Vec<String> =
+            (0..post_ids.len()).map(|n| format!("?{}", n + 2)).collect();
+        let query = format!(
+            "{} WHERE p.id IN ({}) AND p.deleted_at IS NULL",
+            POST_SELECT,
+            placeholders.join(", ")
+        );
+
+        let mut query = sqlx::query_as::<_, PostRow>(&query).bind(user_id);
+        for post_id in post_ids {
+            query = query.bind(post_id);
+        }
+        let posts = query.fetch_all(&mut *self.conn().await?).await?;
+
+        Ok(posts.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_posts_page(
+        &self,
+        page: &PageRequest,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        let (created_at, id) = cursor_bounds(page);
+        let posts: Vec<PostRow> = sqlx::query_as(&format!(
+            "{} WHERE p.deleted_at IS NULL AND {} LIMIT ?4",
+            POST_SELECT,
+            keyset("p", 2, page)
+        ))
+        .bind(user_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        let posts: Vec<Post> = posts.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(posts, page, |post| (post.created_at, post.id)))
+    }
+
+    async fn create_post(&self, post: &CreatePost) -> anyhow::Result<Uuid> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        sqlx::query("INSERT INTO posts (id, title, content, author_id) VALUES (?1, ?2, ?3, ?4)")
+            .bind(post.id)
+            .bind(&post.title)
+            .bind(&post.content)
+            .bind(post.author_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let event = DomainEvent::PostCreated {
+            post_id: post.id,
+            author_id: post.author_id,
+            title: post.title.clone(),
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+
+        Ok(post.id)
+    }
+
+    async fn get_post_status(&self, post_id: &Uuid) -> anyhow::Result<Option<PostStatus>> {
+        let status = sqlx::query_as("SELECT id, author_id, deleted_at FROM posts WHERE id = ?1")
+            .bind(post_id)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+
+        Ok(status)
+    }
+
+    async fn update_post(
+        &self,
+        post_id: &Uuid,
+        editor_id: &Uuid,
+        updates: &UpdatePost,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        // Writing the revision first takes the write lock before anything is read
+        let recorded = sqlx::query(
+            r#"
+            INSERT INTO post_revisions (post_id, revision, editor_id, title, content)
+            SELECT
+                p.id,
+                COALESCE((SELECT MAX(revision) FROM post_revisions WHERE post_id = p.id), 0) + 1,
+                ?2,
+                p.title,
+                p.content
+            FROM posts p
+            WHERE p.id = ?1 AND p.deleted_at IS NULL
+              AND (COALESCE(?3, p.title) IS NOT p.title OR COALESCE(?4, p.content) IS NOT p.content)
+            "#,
+        )
+        .bind(post_id)
+        .bind(editor_id)
+        .bind(&updates.title)
+        .bind(&updates.content)
+        .execute(&mut *tx)
+        .await?;
+        if recorded.rows_affected() == 0 {
+            // Either the post is gone or the update changes nothing
+            let live: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM posts WHERE id = ?1 AND deleted_at IS NULL)",
+            )
+            .bind(post_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            return Ok(live);
+        }
+
+        sqlx::query(&format!(
+            r#"
+            UPDATE posts
+            SET
+                title = COALESCE(?2, title),
+                content = COALESCE(?3, content),
+                updated_at = {}
+            WHERE id = ?1
+            "#,
+            NOW
+        ))
+        .bind(post_id)
+        .bind(&updates.title)
+        .bind(&updates.content)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn get_post_revisions(&self, post_id: &Uuid) -> anyhow::Result<Vec<PostRevision>> {
+        let revisions = sqlx::query_as(
+            r#"
+            SELECT post_id, revision, editor_id, title, content, created_at
+            FROM post_revisions
+            WHERE post_id = ?1
+            ORDER BY revision DESC
+            "#,
+        )
+        .bind(post_id)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(revisions)
+    }
+
+    async fn get_post_revision(
+        &self,
+        post_id: &Uuid,
+        revision: i32,
+    ) -> anyhow::Result<Option<PostRevision>> {
+        let revision = sqlx::query_as(
+            r#"
+            SELECT post_id, revision, editor_id, title, content, created_at
+            FROM post_revisions
+            WHERE post_id = ?1 AND revision = ?2
+            "#,
+        )
+        .bind(post_id)
+        .bind(revision)
+        .fetch_optional(&mut *self.conn().await?)
+        .await?;
+
+        Ok(revision)
+    }
+
+    async fn delete_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query(&format!(
+            "UPDATE posts SET deleted_at = {} WHERE id = ?1 AND deleted_at IS NULL",
+            NOW
+        ))
+        .bind(post_id)
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn restore_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let result =
+            sqlx::query("UPDATE posts SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL")
+                .bind(post_id)
+                .execute(&mut *self.conn().await?)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn purge_deleted_posts(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let result = sqlx::query("DELETE FROM posts WHERE deleted_at < ?1")
+            .bind(timestamp(&cutoff))
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn like_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO post_likes (id, post_id, user_id) VALUES (?1, ?2, ?3) \
+             ON CONFLICT (user_id, post_id) DO NOTHING",
+        )
+        .bind(Uuid::new_v4())
+        .bind(post_id)
+        .bind(user_id)
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unlike_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM post_likes WHERE post_id = ?1 AND user_id = ?2")
+            .bind(post_id)
+            .bind(user_id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_post_likers(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<PostLiker>> {
+        let (created_at, id) = cursor_bounds(page);
+        let likers: Vec<PostLikerRow> = sqlx::query_as(&format!(
+            r#"
+            SELECT
+                u.id, u.username, u.full_name, u.avatar_url, u.bio,
+                pl.created_at AS liked_at
+            FROM post_likes pl
+            JOIN users u ON pl.user_id = u.id
+            WHERE pl.post_id = ?1 AND {}
+            LIMIT ?4
+            "#,
+            keyset_on("pl.created_at", "pl.user_id", 2, page)
+        ))
+        .bind(post_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        let likers: Vec<PostLiker> = likers.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(likers, page, |liker| {
+            (liker.liked_at, liker.user.id)
+        }))
+    }
+
+    async fn recount_post_counters(&self) -> anyhow::Result<u64> {
+        let result = sqlx::query(
+            r#"
+            WITH counts AS (
+                SELECT
+                    id,
+                    (SELECT COUNT(*) FROM post_likes WHERE post_id = posts.id) AS likes_count,
+                    (SELECT COUNT(*) FROM post_comments WHERE post_id = posts.id) AS comments_count
+                FROM posts
+            )
+            UPDATE posts
+            SET
+                likes_count = (SELECT likes_count FROM counts WHERE counts.id = posts.id),
+                comments_count = (SELECT comments_count FROM counts WHERE counts.id = posts.id)
+            WHERE EXISTS (
+                SELECT 1 FROM counts
+                WHERE counts.id = posts.id
+                    AND (counts.likes_count <> posts.likes_count
+                        OR counts.comments_count <> posts.comments_count)
+            )
+            "#,
+        )
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl CommentRepository for SqliteDatabase {
+    async fn get_comments_page(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Comment>> {
+        let (created_at, id) = cursor_bounds(page);
+        let comments: Vec<CommentWithAuthor> = sqlx::query_as(&format!(
+            "{} WHERE pc.post_id = ?1 AND {} LIMIT ?4",
+            COMMENT_SELECT,
+            keyset("pc", 2, page)
+        ))
+        .bind(post_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        let comments: Vec<Comment> = comments.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(comments, page, |comment| {
+            (comment.created_at, comment.id)
+        }))
+    }
+
+    async fn get_comment_tree(
+        &self,
+        post_id: &Uuid,
+        request: &CommentTreeRequest,
+    ) -> anyhow::Result<CommentTree> {
+        // Same walk as the Postgres query
+        let rows: Vec<CommentTreeRow> = sqlx::query_as(
+            r#"
+            WITH RECURSIVE counted AS (
+                SELECT
+                    pc.id, pc.post_id, pc.parent_id, pc.author_id, pc.content,
+                    pc.created_at, pc.updated_at,
+                    (SELECT COUNT(*) FROM post_comments r WHERE r.parent_id = pc.id) AS reply_count
+                FROM post_comments pc
+                WHERE pc.post_id = ?1
+            ),
+            ranked AS (
+                SELECT
+                    c.*,
+                    COUNT(*) OVER (PARTITION BY c.parent_id) AS sibling_count,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY c.parent_id
+                        ORDER BY
+                            CASE WHEN ?3 = 'top' THEN c.reply_count END DESC,
+                            CASE WHEN ?3 = 'oldest' THEN c.created_at END ASC,
+                            CASE WHEN ?3 = 'oldest' THEN c.id END ASC,
+                            c.created_at DESC,
+                            c.id DESC
+                    ) AS position
+                FROM counted c
+            ),
+            tree AS (
+                SELECT ranked.*, 1 AS depth
+                FROM ranked
+                WHERE ranked.parent_id IS ?2
+                    AND ranked.position > ?4 AND ranked.position <= ?4 + ?5
+                UNION ALL
+                SELECT ranked.*, tree.depth + 1
+                FROM ranked
+                JOIN tree ON ranked.parent_id = tree.id
+                WHERE tree.depth < ?6 AND ranked.position <= ?7
+            )
+            SELECT
+                t.id, t.post_id, t.parent_id, t.author_id, t.content, t.created_at, t.updated_at,
+                u.username AS author_username, u.full_name AS author_full_name,
+                u.avatar_url AS author_avatar_url, u.bio AS author_bio,
+                t.depth, t.position, t.sibling_count, t.reply_count
+            FROM tree t
+            JOIN users u ON t.author_id = u.id
+            "#,
+        )
+        .bind(post_id)
+        .bind(request.parent_id)
+        .bind(request.sort.as_str())
+        .bind(request.offset)
+        .bind(request.limit)
+        .bind(request.max_depth)
+        .bind(request.replies_limit)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(CommentTree::from_rows(rows, request))
+    }
+
+    async fn get_comment(&self, comment_id: &Uuid) -> anyhow::Result<Option<Comment>> {
+        let comment: Option<CommentWithAuthor> =
+            sqlx::query_as(&format!("{} WHERE pc.id = ?1", COMMENT_SELECT))
+                .bind(comment_id)
+                .fetch_optional(&mut *self.conn().await?)
+                .await?;
+
+        Ok(comment.map(Into::into))
+    }
+
+    async fn create_comment(&self, comment: &CreateComment) -> anyhow::Result<Comment> {
+        sqlx::query(
+            r#"
+            INSERT INTO post_comments (id, post_id, parent_id, author_id, content)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+        )
+        .bind(comment.id)
+        .bind(comment.post_id)
+        .bind(comment.parent_id)
+        .bind(comment.author_id)
+        .bind(&comment.content)
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        self.get_comment(&comment.id)
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
+    }
+
+    async fn update_comment(&self, comment_id: &Uuid, content: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query(&format!(
+            "UPDATE post_comments SET content = ?2, updated_at = {} WHERE id = ?1",
+            NOW
+        ))
+        .bind(comment_id)
+        .bind(content)
+        .execute(&mut *self.conn().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_comment(&self, comment_id: &Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM post_comments WHERE id = ?1")
+            .bind(comment_id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl FollowRepository for SqliteDatabase {
+    async fn add_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        if kind == FollowKind::Block {
+            sqlx::query(
+                r#"
+                DELETE FROM follows
+                WHERE kind = 'follow'
+                    AND ((user_id = ?1 AND target_id = ?2) OR (user_id = ?2 AND target_id = ?1))
+                "#,
+            )
+            .bind(user_id)
+            .bind(target_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO follows (user_id, target_id, kind)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT (user_id, target_id, kind) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(target_id)
+        .bind(kind)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM follows WHERE user_id = ?1 AND target_id = ?2 AND kind = ?3")
+                .bind(user_id)
+                .bind(target_id)
+                .bind(kind)
+                .execute(&mut *self.conn().await?)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_follow_edges(
+        &self,
+        user_id: &Uuid,
+        other_id: &Uuid,
+    ) -> anyhow::Result<Vec<FollowEdge>> {
+        let edges = sqlx::query_as(
+            r#"
+            SELECT user_id, target_id, kind
+            FROM follows
+            WHERE (user_id = ?1 AND target_id = ?2) OR (user_id = ?2 AND target_id = ?1)
+            "#,
+        )
+        .bind(user_id)
+        .bind(other_id)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(edges)
+    }
+
+    async fn get_followers_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        let (created_at, id) = cursor_bounds(page);
+        let followers: Vec<FollowUserRow> = sqlx::query_as(&format!(
+            r#"
+            SELECT {}
+            FROM follows f
+            JOIN users u ON f.user_id = u.id
+            WHERE f.target_id = ?1 AND f.kind = 'follow' AND {}
+            LIMIT ?4
+            "#,
+            FOLLOW_USER_COLUMNS,
+            keyset_on("f.created_at", "f.user_id", 2, page)
+        ))
+        .bind(user_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        let followers: Vec<FollowUser> = followers.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(followers, page, |follower| {
+            (follower.followed_at, follower.user.id)
+        }))
+    }
+
+    async fn get_following_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        let (created_at, id) = cursor_bounds(page);
+        let following: Vec<FollowUserRow> = sqlx::query_as(&format!(
+            r#"
+            SELECT {}
+            FROM follows f
+            JOIN users u ON f.target_id = u.id
+            WHERE f.user_id = ?1 AND f.kind = 'follow' AND {}
+            LIMIT ?4
+            "#,
+            FOLLOW_USER_COLUMNS,
+            keyset_on("f.created_at", "f.target_id", 2, page)
+        ))
+        .bind(user_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        let following: Vec<FollowUser> = following.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(following, page, |followed| {
+            (followed.followed_at, followed.user.id)
+        }))
+    }
+
+    async fn get_feed_subscribers(&self, author_id: &Uuid) -> anyhow::Result<Vec<Uuid>> {
+        let subscribers = sqlx::query_scalar(
+            r#"
+            SELECT f.user_id
+            FROM follows f
+            WHERE f.target_id = ?1 AND f.kind = 'follow'
+                AND NOT EXISTS (
+                    SELECT 1 FROM follows m
+                    WHERE m.user_id = f.user_id AND m.target_id = ?1 AND m.kind = 'mute'
+                )
+            "#,
+        )
+        .bind(author_id)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(subscribers)
+    }
+
+    async fn get_home_feed_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        let (created_at, id) = cursor_bounds(page);
+        let posts: Vec<PostRow> = sqlx::query_as(&format!(
+            "{} WHERE {} AND {} LIMIT ?4",
+            POST_SELECT,
+            HOME_FEED_FILTER,
+            keyset("p", 2, page)
+        ))
+        .bind(user_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        let posts: Vec<Post> = posts.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(posts, page, |post| (post.created_at, post.id)))
+    }
+
+    async fn get_home_feed_entries(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+    ) -> anyhow::Result<Vec<FeedEntry>> {
+        let entries = sqlx::query_as(&format!(
+            r#"
+            SELECT p.id AS post_id, p.created_at
+            FROM posts p
+            WHERE {}
+            ORDER BY p.created_at DESC, p.id DESC
+            LIMIT ?2
+            "#,
+            HOME_FEED_FILTER
+        ))
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl ChatRepository for SqliteDatabase {
+    async fn create_chat(&self, chat: &CreateChat) -> anyhow::Result<Chat> {
+        let mut participant_ids = vec![chat.created_by];
+        for user_id in &chat.participant_ids {
+            if !participant_ids.contains(user_id) {
+                participant_ids.push(*user_id);
+            }
+        }
+
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let created: Chat = sqlx::query_as(&format!(
+            r#"
+            INSERT INTO chats (id, name, chat_type, created_by)
+            VALUES (?1, ?2, ?3, ?4)
+            RETURNING {}
+            "#,
+            CHAT_COLUMNS
+        ))
+        .bind(chat.id)
+        .bind(&chat.name)
+        .bind(chat.chat_type.clone())
+        .bind(chat.created_by)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for user_id in participant_ids {
+            sqlx::query("INSERT INTO chat_participants (chat_id, user_id) VALUES (?1, ?2)")
+                .bind(chat.id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(created)
+    }
+
+    async fn get_chat(&self, chat_id: &Uuid) -> anyhow::Result<Option<Chat>> {
+        let chat = sqlx::query_as(&format!("SELECT {} FROM chats WHERE id = ?1", CHAT_COLUMNS))
+            .bind(chat_id)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+
+        Ok(chat)
+    }
+
+    async fn get_user_chats(&self, user_id: &Uuid) -> anyhow::Result<Vec<Chat>> {
+        let chats = sqlx::query_as(
+            r#"
+            SELECT c.id, c.name, c.chat_type, c.created_at, c.updated_at
+            FROM chats c
+            JOIN chat_participants cp ON cp.chat_id = c.id
+            WHERE cp.user_id = ?1 AND cp.left_at IS NULL
+            ORDER BY c.updated_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(chats)
+    }
+
+    async fn is_chat_participant(&self, chat_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let is_participant: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM chat_participants
+                WHERE chat_id = ?1 AND user_id = ?2 AND left_at IS NULL
+            )
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_one(&mut *self.conn().await?)
+        .await?;
+
+        Ok(is_participant)
+    }
+
+    async fn create_message(&self, message: &CreateMessage) -> anyhow::Result<Message> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let created_at: DateTime<Utc> = sqlx::query_scalar(
+            r#"
+            INSERT INTO messages (id, chat_id, sender_id, content, message_type, metadata)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING created_at
+            "#,
+        )
+        .bind(message.id)
+        .bind(message.chat_id)
+        .bind(message.sender_id)
+        .bind(&message.content)
+        .bind(message.message_type.clone())
+        .bind(&message.metadata)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(&format!("UPDATE chats SET updated_at = {} WHERE id = ?1", NOW))
+            .bind(message.chat_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let event = DomainEvent::MessageSent {
+            message_id: message.id,
+            chat_id: message.chat_id,
+            sender_id: message.sender_id,
+            content: message.content.clone(),
+            created_at,
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+        // `get_message` needs the connection, which inside a transaction is this one
+        drop(conn);
+
+        self.get_message(&message.id)
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
+    }
+
+    async fn get_messages(
+        &self,
+        chat_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Message>> {
+        let messages: Vec<MessageWithSender> = sqlx::query_as(&format!(
+            "{} WHERE m.chat_id = ?1 ORDER BY m.created_at DESC LIMIT ?2 OFFSET ?3",
+            MESSAGE_SELECT
+        ))
+        .bind(chat_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_messages_page(
+        &self,
+        chat_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Message>> {
+        let (created_at, id) = cursor_bounds(page);
+        let messages: Vec<MessageWithSender> = sqlx::query_as(&format!(
+            "{} WHERE m.chat_id = ?1 AND {} LIMIT ?4",
+            MESSAGE_SELECT,
+            keyset("m", 2, page)
+        ))
+        .bind(chat_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        let messages: Vec<Message> = messages.into_iter().map(Into::into).collect();
+        Ok(CursorPage::from_rows(messages, page, |message| {
+            (message.created_at, message.id)
+        }))
+    }
+}
+
+#[async_trait]
+impl NotificationRepository for SqliteDatabase {
+    async fn create_notification(
+        &self,
+        notification: &CreateNotification,
+    ) -> anyhow::Result<Notification> {
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let notification: Notification = sqlx::query_as(&format!(
+            r#"
+            INSERT INTO notifications (id, user_id, notification_type, title, message, metadata)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING {}
+            "#,
+            NOTIFICATION_COLUMNS
+        ))
+        .bind(notification.id)
+        .bind(notification.user_id)
+        .bind(&notification.notification_type)
+        .bind(&notification.title)
+        .bind(&notification.message)
+        .bind(&notification.metadata)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let event = DomainEvent::NotificationCreated {
+            notification_id: notification.id,
+            user_id: notification.user_id,
+            notification_type: notification.notification_type.clone(),
+            title: notification.title.clone(),
+            message: notification.message.clone(),
+            created_at: notification.created_at,
+        };
+        record_event(&mut tx, &event).await?;
+        tx.commit().await?;
+
+        Ok(notification)
+    }
+
+    async fn get_notifications(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Notification>> {
+        let notifications = sqlx::query_as(&format!(
+            r#"
+            SELECT {}
+            FROM notifications
+            WHERE user_id = ?1
+            ORDER BY created_at DESC
+            LIMIT ?2 OFFSET ?3
+            "#,
+            NOTIFICATION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(notifications)
+    }
+
+    async fn get_notifications_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Notification>> {
+        let (created_at, id) = cursor_bounds(page);
+        let notifications: Vec<Notification> = sqlx::query_as(&format!(
+            "SELECT {} FROM notifications n WHERE n.user_id = ?1 AND {} LIMIT ?4",
+            NOTIFICATION_COLUMNS,
+            keyset("n", 2, page)
+        ))
+        .bind(user_id)
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(CursorPage::from_rows(notifications, page, |notification| {
+            (notification.created_at, notification.id)
+        }))
+    }
+
+    async fn mark_notification_read(
+        &self,
+        notification_id: &Uuid,
+        user_id: &Uuid,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query("UPDATE notifications SET read = TRUE WHERE id = ?1 AND user_id = ?2")
+            .bind(notification_id)
+            .bind(user_id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl SearchRepository for SqliteDatabase {
+    async fn search(
+        &self,
+        user_id: &Uuid,
+        request: &SearchRequest,
+    ) -> anyhow::Result<CursorPage<SearchHit>> {
+        let (rank, id) = request.cursor.map(|cursor| (cursor.rank, cursor.id)).unzip();
+        let query = fts_query(&request.query);
+        let mut hits = Vec::new();
+
+        for kind in &request.kinds {
+            let sql = search_sql(*kind);
+            let mut rows = sqlx::query_as::<_, SearchHitRow>(&sql)
+                .bind(&query)
+                .bind(rank)
+                .bind(id)
+                .bind(request.fetch_limit());
+            if *kind == SearchKind::Message {
+                rows = rows.bind(user_id);
+            }
+
+            let rows = rows.fetch_all(&mut *self.conn().await?).await?;
+            hits.extend(rows.into_iter().map(|row| row.into_hit(*kind)));
+        }
+
+        Ok(CursorPage::from_hits(hits, request))
+    }
+}
+
+// Writers are serialized, so a plain UPDATE … RETURNING claims a batch
+// without racing another relay.
+#[async_trait]
+impl OutboxRepository for SqliteDatabase {
+    async fn claim_outbox_events(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> anyhow::Result<Vec<OutboxRow>> {
+        let mut rows: Vec<OutboxRow> = sqlx::query_as(&format!(
+            r#"
+            UPDATE outbox
+            SET
+                locked_until = strftime('%Y-%m-%dT%H:%M:%fZ', 'now', ?2),
+                attempts = attempts + 1
+            WHERE id IN (
+                SELECT id
+                FROM outbox
+                WHERE published_at IS NULL AND (locked_until IS NULL OR locked_until < {})
+                ORDER BY id
+                LIMIT ?1
+            )
+            RETURNING id, dedup_key, payload, created_at
+            "#,
+            NOW
+        ))
+        .bind(limit)
+        .bind(format!("+{} seconds", lease.as_secs_f64()))
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        rows.sort_by_key(|row| row.id);
+        Ok(rows)
+    }
+
+    async fn mark_outbox_published(&self, event_ids: &[i64]) -> anyhow::Result<()> {
+        if event_ids.is_empty() {
+            return Ok(());
+        }
+
+        let placeholders: Vec<String> =
+            (1..=event_ids.len()).map(|n| format!("?{}", n)).collect();
+        let query = format!(
+            "UPDATE outbox SET published_at = {}, locked_until = NULL WHERE id IN ({})",
+            NOW,
+            placeholders.join(", ")
+        );
+
+        let mut query = sqlx::query(&query);
+        for event_id in event_ids {
+            query = query.bind(event_id);
+        }
+        query.execute(&mut *self.conn().await?).await?;
+
+        Ok(())
+    }
+
+    async fn purge_outbox(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let result = sqlx::query("DELETE FROM outbox WHERE published_at < ?1")
+            .bind(timestamp(&cutoff))
+            .execute(&mut *self.conn().await?)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl AuditRepository for SqliteDatabase {
+    async fn append_audit_event(&self, event: &NewAuditEvent) -> anyhow::Result<AuditEvent> {
+        // SQLite has a single writer; an append racing another one fails on
+        // the unique `seq` (or SQLITE_BUSY) instead of forking the chain
+        let mut conn = self.conn().await?;
+        let mut tx = conn.begin().await?;
+
+        let previous: Option<(i64, String)> =
+            sqlx::query_as("SELECT seq, hash FROM audit_events ORDER BY seq DESC LIMIT 1")
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        let event = event.link(previous);
+        sqlx::query(
+            r#"
+            INSERT INTO audit_events (
+                id, seq, occurred_at, action, actor_id, target_type, target_id, ip,
+                user_agent, request_id, before, after, prev_hash, hash
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+            "#,
+        )
+        .bind(event.id)
+        .bind(event.seq)
+        .bind(timestamp(&event.occurred_at))
+        .bind(&event.action)
+        .bind(event.actor_id)
+        .bind(&event.target_type)
+        .bind(event.target_id)
+        .bind(&event.ip)
+        .bind(&event.user_agent)
+        .bind(&event.request_id)
+        .bind(&event.before)
+        .bind(&event.after)
+        .bind(&event.prev_hash)
+        .bind(&event.hash)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(event)
+    }
+
+    async fn get_audit_events_page(
+        &self,
+        filter: &AuditFilter,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<AuditEvent>> {
+        let (created_at, id) = cursor_bounds(page);
+        let events: Vec<AuditEvent> = sqlx::query_as(&format!(
+            r#"
+            SELECT {}
+            FROM audit_events
+            WHERE (?1 IS NULL OR actor_id = ?1)
+                AND (?2 IS NULL OR target_id = ?2)
+                AND (?3 IS NULL OR action = ?3)
+                AND (?4 IS NULL OR occurred_at >= ?4)
+                AND (?5 IS NULL OR occurred_at < ?5)
+                AND {}
+            LIMIT ?8
+            "#,
+            AUDIT_EVENT_COLUMNS,
+            keyset_on("occurred_at", "id", 6, page)
+        ))
+        .bind(filter.actor_id)
+        .bind(filter.target_id)
+        .bind(&filter.action)
+        .bind(filter.since.as_ref().map(timestamp))
+        .bind(filter.until.as_ref().map(timestamp))
+        .bind(created_at)
+        .bind(id)
+        .bind(page.fetch_limit())
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(CursorPage::from_rows(events, page, |event| (event.occurred_at, event.id)))
+    }
+
+    async fn get_audit_chain(&self, after_seq: i64, limit: i64) -> anyhow::Result<Vec<AuditEvent>> {
+        let events: Vec<AuditEvent> = sqlx::query_as(&format!(
+            "SELECT {} FROM audit_events WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+            AUDIT_EVENT_COLUMNS
+        ))
+        .bind(after_seq)
+        .bind(limit)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+// SQLite runs one write transaction at a time, so there are no serialization
+// failures to retry, and every transaction is serializable whatever
+// `isolation` asks for
+#[async_trait]
+impl TransactionRepository for SqliteDatabase {
+    async fn transaction_with(
+        &self,
+        _isolation: Isolation,
+        work: UnitOfWork<'_>,
+    ) -> anyhow::Result<()> {
+        match &self.scope {
+            Some(scope) => self.savepoint(scope, work).await,
+            None => self.begin_work(work).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        error::{AppError, ConstraintKind},
+        testing::{self, create_post, create_user},
+    };
+
+    // A database file of its own, removed on drop. `sqlite::memory:` would
+    // pin the pool to a single connection and hide lock contention.
+    struct TempDatabase {
+        path: PathBuf,
+        database: SqliteDatabase,
+    }
+
+    impl TempDatabase {
+        async fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("backend-test-{}.db", Uuid::new_v4()));
+            let database = SqliteDatabase::new(&format!("sqlite://{}", path.display()))
+                .await
+                .unwrap();
+            database.migrate().await.unwrap();
+
+            Self { path, database }
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn creating_a_user_releases_its_write_lock() {
+        let temp = TempDatabase::new().await;
+
+        // Each post is written on whichever pooled connection is free, which
+        // used to fail while the user insert still held the lock
+        for n in 0..10 {
+            let user = create_user(&temp.database, &format!("user{}", n)).await;
+            create_post(&temp.database, &user, "First post").await;
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_email_is_a_unique_violation() {
+        let temp = TempDatabase::new().await;
+        let user = create_user(&temp.database, "alice").await;
+
+        let duplicate = CreateUser {
+            id: Uuid::new_v4(),
+            email: user.email,
+            username: None,
+            full_name: None,
+            avatar_url: None,
+            bio: None,
+        };
+        let error = AppError::from(temp.database.create_user(&duplicate).await.unwrap_err());
+
+        let AppError::Constraint(violation) = error else {
+            panic!("expected a constraint violation, got {:?}", error);
+        };
+        assert_eq!(violation.kind, ConstraintKind::Unique);
+        assert_eq!(violation.table.as_deref(), Some("users"));
+        assert_eq!(violation.field.as_deref(), Some("email"));
+    }
+
+    #[tokio::test]
+    async fn edits_that_change_nothing_record_no_revision() {
+        let temp = TempDatabase::new().await;
+        let alice = create_user(&temp.database, "alice").await;
+        let post_id = create_post(&temp.database, &alice, "First").await;
+
+        let retitle = |title: &str| UpdatePost {
+            title: Some(title.to_string()),
+            content: None,
+        };
+        assert!(temp
+            .database
+            .update_post(&post_id, &alice.id, &retitle("First"))
+            .await
+            .unwrap());
+        assert!(temp
+            .database
+            .get_post_revisions(&post_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(temp
+            .database
+            .update_post(&post_id, &alice.id, &retitle("Second"))
+            .await
+            .unwrap());
+        assert_eq!(
+            temp.database
+                .get_post_revisions(&post_id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // A missing post is still reported as such
+        let missing = Uuid::new_v4();
+        assert!(!temp
+            .database
+            .update_post(&missing, &alice.id, &retitle("First"))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn search_snippets_are_escaped() {
+        let temp = TempDatabase::new().await;
+        let alice = create_user(&temp.database, "alice").await;
+        let post = CreatePost {
+            id: Uuid::new_v4(),
+            title: "Injected".to_string(),
+            content: "<script>alert('rust')</script>".to_string(),
+            author_id: alice.id,
+        };
+        temp.database.create_post(&post).await.unwrap();
+
+        let request = SearchRequest {
+            query: "alert".to_string(),
+            kinds: vec![SearchKind::Post],
+            cursor: None,
+            limit: 10,
+        };
+        let hits = temp.database.search(&alice.id, &request).await.unwrap();
+
+        let snippet = &hits.data[0].snippet;
+        assert!(snippet.contains("<mark>alert</mark>"), "{}", snippet);
+        assert!(!snippet.contains("<script>"), "{}", snippet);
+        assert!(snippet.contains("&lt;script&gt;"), "{}", snippet);
+    }
+
+    #[tokio::test]
+    async fn transactions_commit_or_roll_back() {
+        let temp = TempDatabase::new().await;
+
+        testing::committed_work_is_kept(&temp.database).await;
+        testing::failed_work_is_rolled_back(&temp.database).await;
+        testing::nested_work_rolls_back_on_its_own(&temp.database).await;
+    }
+
+    #[tokio::test]
+    async fn keyset_pages_walk_both_ways() {
+        testing::posts_page_by_keyset(&TempDatabase::new().await.database).await;
+    }
+
+    #[tokio::test]
+    async fn comment_trees_nest_and_slice() {
+        testing::comment_trees_nest_and_slice(&TempDatabase::new().await.database).await;
+    }
+
+    #[tokio::test]
+    async fn counter_triggers_follow_likes_and_comments() {
+        let temp = TempDatabase::new().await;
+        testing::post_counters_follow_likes_and_comments(&temp.database).await;
+    }
+
+    #[tokio::test]
+    async fn audit_appends_extend_the_chain() {
+        testing::audit_appends_extend_the_chain(&TempDatabase::new().await.database).await;
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    fmt,
+    future::Future,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+    time::Duration,
+};
+
+use rand::Rng;
+use sqlx::{pool::PoolConnection, PgConnection, Postgres, Transaction};
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::Database;
+
+// Attempts per top-level transaction when Postgres aborts it as a
+// serialization failure or deadlock
+const MAX_ATTEMPTS: u32 = 3;
+
+// First retry waits up to this long, doubling after each further failure
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(20);
+
+// SQLSTATEs that mean "nothing was wrong with the transaction, run it again"
+const SERIALIZATION_FAILURE: &str = "40001";
+const DEADLOCK_DETECTED: &str = "40P01";
+
+/// Isolation level of a top-level transaction. Nested transactions run at
+/// whatever level their outermost transaction picked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Isolation {
+    #[default]
+    ReadCommitted,
+    Serializable,
+}
+
+impl Isolation {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Isolation::ReadCommitted => "READ COMMITTED",
+            Isolation::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+// The open transaction a `Database` handle is scoped to. Queries take turns
+// on it; `depth` counts the savepoints nested inside it.
+#[derive(Clone)]
+pub(super) struct Scope {
+    tx: Arc<Mutex<Transaction<'static, Postgres>>>,
+    depth: usize,
+}
+
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope").field("depth", &self.depth).finish()
+    }
+}
+
+/// Where a `Database` method runs its queries: a connection from the pool,
+/// or the transaction the handle is scoped to.
+pub(super) enum Conn<'a> {
+    // Boxed: a pooled connection is far bigger than a guard
+    Pooled(Box<PoolConnection<Postgres>>),
+    Scoped(MutexGuard<'a, Transaction<'static, Postgres>>),
+}
+
+impl Deref for Conn<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        match self {
+            Conn::Pooled(conn) => conn,
+            Conn::Scoped(tx) => tx,
+        }
+    }
+}
+
+impl DerefMut for Conn<'_> {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        match self {
+            Conn::Pooled(conn) => conn,
+            Conn::Scoped(tx) => tx,
+        }
+    }
+}
+
+fn is_retryable(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<sqlx::Error>())
+        .filter_map(|error| error.as_database_error()?.code())
+        .any(|code| code == SERIALIZATION_FAILURE || code == DEADLOCK_DETECTED)
+}
+
+async fn backoff(attempt: u32) {
+    let ceiling = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+    let delay = rand::thread_rng().gen_range(Duration::ZERO..=ceiling);
+    tokio::time::sleep(delay).await;
+}
+
+impl Database {
+    // Connection for a query that writes; the request reads from the primary
+    // for a while afterwards
+    pub(super) async fn conn(&self) -> sqlx::Result<Conn<'_>> {
+        if let Some(replicas) = &self.replicas {
+            replicas.record_write();
+        }
+        self.primary_conn().await
+    }
+
+    // Waits its turn inside a transaction
+    pub(super) async fn primary_conn(&self) -> sqlx::Result<Conn<'_>> {
+        match &self.scope {
+            Some(scope) => Ok(Conn::Scoped(scope.tx.lock().await)),
+            None => Ok(Conn::Pooled(Box::new(self.pool.acquire().await?))),
+        }
+    }
+
+    fn scoped(&self, scope: Scope) -> Database {
+        Database {
+            scope: Some(scope),
+            ..self.clone()
+        }
+    }
+
+    /// Runs `f` with a `Database` whose repository methods all execute in
+    /// a single transaction, committed if `f` succeeds and rolled back if it
+    /// fails. Serialization failures and deadlocks re-run `f` from the start
+    /// (up to three attempts), so `f` must not have side effects outside the
+    /// database. Called on a handle that is already in a transaction, `f`
+    /// runs under a savepoint instead: its failure only undoes its own
+    /// writes, and retrying is left to the outermost transaction.
+    ///
+    /// The handle passed to `f` must be dropped by the time its future
+    /// completes; one that escapes (say, into a spawned task) fails the
+    /// transaction.
+    pub async fn transaction_with<T, F, Fut>(
+        &self,
+        isolation: Isolation,
+        f: F,
+    ) -> anyhow::Result<T>
+    where
+        F: Fn(Database) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        if let Some(scope) = &self.scope {
+            return self.savepoint(scope, f).await;
+        }
+
+        let mut attempt = 1;
+        loop {
+            let result = self.attempt(isolation, &f).await;
+            match result {
+                Err(e) if attempt < MAX_ATTEMPTS && is_retryable(&e) => {
+                    tracing::debug!(attempt, "Retrying transaction: {}", e);
+                    backoff(attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn attempt<T, F, Fut>(&self, isolation: Isolation, f: &F) -> anyhow::Result<T>
+    where
+        F: Fn(Database) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut tx = self.pool.begin().await?;
+        if isolation != Isolation::ReadCommitted {
+            sqlx::query(&format!("SET TRANSACTION ISOLATION LEVEL {}", isolation.as_sql()))
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let tx = Arc::new(Mutex::new(tx));
+        let result = f(self.scoped(Scope {
+            tx: tx.clone(),
+            depth: 0,
+        }))
+        .await;
+
+        // Dropping a transaction without committing rolls it back
+        let tx = Arc::try_unwrap(tx)
+            .map_err(|_| anyhow::anyhow!("Transaction handle outlived its closure"))?
+            .into_inner();
+
+        match result {
+            Ok(value) => {
+                tx.commit().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                if let Err(rollback) = tx.rollback().await {
+                    tracing::warn!("Transaction rollback failed: {}", rollback);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn savepoint<T, F, Fut>(&self, scope: &Scope, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(Database) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let depth = scope.depth + 1;
+        let name = format!("unit_of_work_{}", depth);
+        let run = |sql: String| async move {
+            sqlx::query(&sql).execute(&mut *self.conn().await?).await
+        };
+
+        run(format!("SAVEPOINT {}", name)).await?;
+        let result = f(self.scoped(Scope {
+            tx: scope.tx.clone(),
+            depth,
+        }))
+        .await;
+
+        match result {
+            Ok(value) => {
+                run(format!("RELEASE SAVEPOINT {}", name)).await?;
+                Ok(value)
+            }
+            Err(e) => {
+                run(format!("ROLLBACK TO SAVEPOINT {}", name)).await?;
+                run(format!("RELEASE SAVEPOINT {}", name)).await?;
+                Err(e)
+            }
+        }
+    }
+}
@@ -1,17 +1,21 @@
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
+use sqlx::{error::ErrorKind, postgres::PgDatabaseError};
 use thiserror::Error;
 
-use crate::models::ErrorResponse;
+use crate::{i18n, models::ErrorResponse, request_id};
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Bad request: {0}")]
-    BadRequest(String),
+    BadRequest(ErrorMessage),
 
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
@@ -20,22 +24,28 @@ pub enum AppError {
     Forbidden(String),
 
     #[error("Not found: {0}")]
-    NotFound(String),
+    NotFound(ErrorMessage),
 
     #[error("Conflict: {0}")]
-    Conflict(String),
+    Conflict(ErrorMessage),
 
     #[error("Unprocessable entity: {0}")]
-    UnprocessableEntity(String),
+    UnprocessableEntity(ErrorMessage),
 
     #[error("Too many requests: {0}")]
     TooManyRequests(String),
 
+    #[error("Constraint violation: {0}")]
+    Constraint(ConstraintViolation),
+
+    #[error("Retryable error: {0}")]
+    Retryable(String),
+
     #[error("Internal server error: {0}")]
     InternalServer(String),
 
     #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
+    Database(sqlx::Error),
 
     #[error("Redis error: {0}")]
     Redis(#[from] redis::RedisError),
@@ -59,9 +69,78 @@ pub enum AppError {
     Parse(#[from] uuid::Error),
 
     #[error("Generic error: {0}")]
-    Generic(#[from] anyhow::Error),
+    Generic(anyhow::Error),
+}
+
+// Catalog key for a handler-supplied message, plus the values for its placeholders
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorMessage {
+    pub key: String,
+    pub args: Vec<(&'static str, String)>,
+}
+
+impl ErrorMessage {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+}
+
+impl From<&str> for ErrorMessage {
+    fn from(key: &str) -> Self {
+        Self::new(key)
+    }
+}
+
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key)?;
+        for (name, value) in &self.args {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintKind {
+    Unique,
+    ForeignKey,
+    Check,
+    NotNull,
+}
+
+// Database constraint that rejected a write, with the offending field when known
+#[derive(Debug, Clone)]
+pub struct ConstraintViolation {
+    pub kind: ConstraintKind,
+    pub table: Option<String>,
+    pub constraint: Option<String>,
+    pub field: Option<String>,
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.kind)?;
+        if let Some(constraint) = &self.constraint {
+            write!(f, " ({})", constraint)?;
+        }
+        if let Some(table) = &self.table {
+            write!(f, " on {}", table)?;
+        }
+        Ok(())
+    }
 }
 
+impl std::error::Error for ConstraintViolation {}
+
 impl AppError {
     fn status_code(&self) -> StatusCode {
         match self {
@@ -74,6 +153,11 @@ impl AppError {
                 StatusCode::UNPROCESSABLE_ENTITY
             }
             AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Constraint(violation) => match violation.kind {
+                ConstraintKind::Unique => StatusCode::CONFLICT,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            },
+            AppError::Retryable(_) => StatusCode::SERVICE_UNAVAILABLE,
             AppError::InternalServer(_)
             | AppError::Database(_)
             | AppError::Redis(_)
@@ -96,6 +180,11 @@ impl AppError {
             AppError::UnprocessableEntity(_) => "UNPROCESSABLE_ENTITY",
             AppError::Validation(_) => "VALIDATION_ERROR",
             AppError::TooManyRequests(_) => "TOO_MANY_REQUESTS",
+            AppError::Constraint(violation) => match violation.kind {
+                ConstraintKind::Unique => "CONFLICT",
+                _ => "UNPROCESSABLE_ENTITY",
+            },
+            AppError::Retryable(_) => "RETRYABLE_ERROR",
             AppError::InternalServer(_) => "INTERNAL_SERVER_ERROR",
             AppError::Database(_) => "DATABASE_ERROR",
             AppError::Redis(_) => "REDIS_ERROR",
@@ -108,21 +197,55 @@ impl AppError {
         }
     }
 
-    fn user_message(&self) -> String {
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AppError::Retryable(_) | AppError::TooManyRequests(_))
+    }
+
+    // Messages passed in by handlers are catalog keys; without one the generic
+    // message comes from the catalog under `error.<ERROR_CODE>`
+    fn user_message(&self, locale: &str) -> String {
+        let message = |key: &str| i18n::translate(locale, key, &[]);
+
         match self {
-            AppError::BadRequest(msg) => msg.clone(),
-            AppError::Unauthorized(_) => "Authentication required".to_string(),
-            AppError::Forbidden(_) => "Access denied".to_string(),
-            AppError::NotFound(msg) => msg.clone(),
-            AppError::Conflict(msg) => msg.clone(),
-            AppError::UnprocessableEntity(msg) => msg.clone(),
-            AppError::TooManyRequests(_) => "Too many requests. Please try again later".to_string(),
-            AppError::Validation(_) => "Validation failed".to_string(),
-            _ => "An internal error occurred".to_string(),
+            AppError::BadRequest(msg)
+            | AppError::NotFound(msg)
+            | AppError::Conflict(msg)
+            | AppError::UnprocessableEntity(msg)
+                if !msg.key.is_empty() =>
+            {
+                i18n::translate(locale, &msg.key, &msg.args)
+            }
+            AppError::BadRequest(_) => message("error.BAD_REQUEST"),
+            AppError::Unauthorized(_) => message("error.UNAUTHORIZED"),
+            AppError::Forbidden(_) => message("error.FORBIDDEN"),
+            AppError::NotFound(_) => message("error.NOT_FOUND"),
+            AppError::Conflict(_) => message("error.CONFLICT"),
+            AppError::UnprocessableEntity(_) => message("error.UNPROCESSABLE_ENTITY"),
+            AppError::TooManyRequests(_) => message("error.TOO_MANY_REQUESTS"),
+            AppError::Validation(_) => message("error.VALIDATION_ERROR"),
+            AppError::Constraint(violation) => {
+                let kind = match violation.kind {
+                    ConstraintKind::Unique => "unique",
+                    ConstraintKind::ForeignKey => "foreign_key",
+                    ConstraintKind::Check => "check",
+                    ConstraintKind::NotNull => "not_null",
+                };
+                match &violation.field {
+                    Some(field) if violation.kind != ConstraintKind::ForeignKey => i18n::translate(
+                        locale,
+                        &format!("constraint.{}_field", kind),
+                        &[("field", field.clone())],
+                    ),
+                    _ => message(&format!("constraint.{}", kind)),
+                }
+            }
+            AppError::Retryable(_) => message("error.RETRYABLE_ERROR"),
+            _ => message("error.INTERNAL_SERVER_ERROR"),
         }
     }
 
-    fn details(&self) -> Option<serde_json::Value> {
+    fn details(&self, locale: &str) -> Option<serde_json::Value> {
         match self {
             AppError::Validation(errors) => {
                 let mut error_map = std::collections::HashMap::new();
@@ -130,47 +253,342 @@ impl AppError {
                 for (field, field_errors) in errors.field_errors() {
                     let messages: Vec<String> = field_errors
                         .iter()
-                        .filter_map(|e| e.message.as_ref().map(|m| m.to_string()))
+                        .map(|e| match &e.message {
+                            Some(message) => message.to_string(),
+                            None => validation_message(locale, e),
+                        })
                         .collect();
                     error_map.insert(field.to_string(), messages);
                 }
                 
                 Some(json!(error_map))
             }
+            AppError::Constraint(violation) => Some(json!({
+                "field": violation.field,
+                "constraint": violation.constraint,
+            })),
             _ => None,
         }
     }
 }
 
+// One row of the error catalog published to API clients
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorCatalogEntry {
+    pub code: &'static str,
+    pub status: u16,
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl AppError {
+    /// Every error code the API can return, with its status, default message
+    /// in `locale` and retryability. Built once per bundled locale from the
+    /// variants themselves, so it cannot drift from `status_code`/`error_code`.
+    pub fn catalog(locale: &str) -> &'static [ErrorCatalogEntry] {
+        static CATALOGS: OnceLock<HashMap<&'static str, Vec<ErrorCatalogEntry>>> = OnceLock::new();
+
+        let catalogs = CATALOGS.get_or_init(|| {
+            i18n::locales()
+                .map(|locale| (locale, Self::build_catalog(locale)))
+                .collect()
+        });
+        let locale = i18n::supported_locale(locale).unwrap_or(i18n::FALLBACK_LOCALE);
+        &catalogs[locale]
+    }
+
+    fn build_catalog(locale: &str) -> Vec<ErrorCatalogEntry> {
+        let mut entries: Vec<ErrorCatalogEntry> = Vec::new();
+
+        for error in Self::representatives() {
+            let code = error.error_code();
+            // Constraint violations share codes with Conflict/UnprocessableEntity
+            if entries.iter().any(|entry| entry.code == code) {
+                continue;
+            }
+            entries.push(ErrorCatalogEntry {
+                code,
+                status: error.status_code().as_u16(),
+                message: error.user_message(locale),
+                retryable: error.is_retryable(),
+            });
+        }
+
+        entries
+    }
+
+    // One value of every variant (and constraint kind), without handler messages
+    fn representatives() -> Vec<AppError> {
+        let constraint = |kind| {
+            AppError::Constraint(ConstraintViolation {
+                kind,
+                table: None,
+                constraint: None,
+                field: None,
+            })
+        };
+
+        let mut errors = vec![
+            AppError::BadRequest(ErrorMessage::default()),
+            AppError::Unauthorized(String::new()),
+            AppError::Forbidden(String::new()),
+            AppError::NotFound(ErrorMessage::default()),
+            AppError::Conflict(ErrorMessage::default()),
+            AppError::UnprocessableEntity(ErrorMessage::default()),
+            AppError::Validation(validator::ValidationErrors::new()),
+            AppError::TooManyRequests(String::new()),
+            constraint(ConstraintKind::Unique),
+            constraint(ConstraintKind::ForeignKey),
+            constraint(ConstraintKind::Check),
+            constraint(ConstraintKind::NotNull),
+            AppError::Retryable(String::new()),
+            AppError::InternalServer(String::new()),
+            AppError::Database(sqlx::Error::RowNotFound),
+            AppError::Redis(redis::RedisError::from((redis::ErrorKind::IoError, "catalog"))),
+            AppError::Json(serde_json::Error::io(std::io::Error::other("catalog"))),
+            AppError::Io(std::io::Error::other("catalog")),
+            AppError::Jwt(jsonwebtoken::errors::ErrorKind::InvalidToken.into()),
+            AppError::Generic(anyhow::anyhow!("catalog")),
+        ];
+        // Neither crate exposes a constructor for its error type
+        if let Err(error) = reqwest::Client::new().get("/").build() {
+            errors.push(AppError::Http(error));
+        }
+        if let Err(error) = uuid::Uuid::parse_str("") {
+            errors.push(AppError::Parse(error));
+        }
+
+        // Exhaustive on purpose: a new variant stops compiling here until it
+        // is given a representative above
+        for error in &errors {
+            match error {
+                AppError::BadRequest(_)
+                | AppError::Unauthorized(_)
+                | AppError::Forbidden(_)
+                | AppError::NotFound(_)
+                | AppError::Conflict(_)
+                | AppError::UnprocessableEntity(_)
+                | AppError::TooManyRequests(_)
+                | AppError::Constraint(_)
+                | AppError::Retryable(_)
+                | AppError::InternalServer(_)
+                | AppError::Database(_)
+                | AppError::Redis(_)
+                | AppError::Validation(_)
+                | AppError::Json(_)
+                | AppError::Io(_)
+                | AppError::Jwt(_)
+                | AppError::Http(_)
+                | AppError::Parse(_)
+                | AppError::Generic(_) => {}
+            }
+        }
+
+        errors
+    }
+}
+
+// Attached to error responses so middleware can re-render them (e.g. as problem+json)
+#[derive(Debug, Clone)]
+pub struct ErrorParts {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+    pub request_id: Option<String>,
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let status_code = self.status_code();
+        let request_id = request_id::current();
+        let locale = i18n::current_locale();
         
-        // Log internal errors
+        // Log internal errors, reporting them to Sentry tagged with the request id
         if status_code.is_server_error() {
-            tracing::error!("Internal error: {:?}", self);
+            let event_id = sentry::with_scope(
+                |scope| {
+                    if let Some(request_id) = &request_id {
+                        scope.set_tag("request_id", request_id);
+                    }
+                },
+                || sentry::capture_error(&self),
+            );
+            let sentry_event_id = (!event_id.is_nil()).then(|| event_id.simple().to_string());
+
+            // request_id is already recorded on the request span
+            tracing::error!(
+                sentry_event_id = sentry_event_id.as_deref(),
+                "Internal error: {:?}",
+                self
+            );
         } else {
             tracing::warn!("Client error: {:?}", self);
         }
 
+        let parts = ErrorParts {
+            status: status_code,
+            code: self.error_code(),
+            message: self.user_message(locale),
+            details: self.details(locale),
+            request_id,
+        };
+
         let error_response = ErrorResponse {
-            message: self.user_message(),
-            code: Some(self.error_code().to_string()),
-            details: self.details(),
+            message: parts.message.clone(),
+            code: Some(parts.code.to_string()),
+            details: parts.details.clone(),
             success: false,
+            request_id: parts.request_id.clone(),
         };
 
-        (status_code, Json(error_response)).into_response()
+        let mut response = (status_code, Json(error_response)).into_response();
+        if matches!(self, AppError::Retryable(_)) {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+        }
+        response.extensions_mut().insert(parts);
+        response
     }
 }
 
+// Catalog message for a validator error code, e.g. `validation.length_min`
+// when only `min` was set on a `length` rule
+fn validation_message(locale: &str, error: &validator::ValidationError) -> String {
+    let args: Vec<(&str, String)> = error
+        .params
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            (name.as_ref(), value)
+        })
+        .collect();
+
+    let code = error.code.as_ref();
+    let key = match code {
+        "length" | "range" => {
+            let has = |param: &str| error.params.contains_key(param);
+            let bounds = if has("equal") {
+                "equal"
+            } else if has("min") && has("max") {
+                "min_max"
+            } else if has("min") {
+                "min"
+            } else {
+                "max"
+            };
+            format!("validation.{}_{}", code, bounds)
+        }
+        _ => format!("validation.{}", code),
+    };
+
+    i18n::lookup(locale, &key, &args)
+        .unwrap_or_else(|| i18n::translate(locale, "validation.invalid", &args))
+}
+
+// Postgres SQLSTATEs for conflicts that succeed when retried
+const SERIALIZATION_FAILURE: &str = "40001";
+const DEADLOCK_DETECTED: &str = "40P01";
+// SQLite's extended result codes for a write lock held past busy_timeout
+const SQLITE_BUSY: &str = "5";
+const SQLITE_BUSY_SNAPSHOT: &str = "517";
+
+// Translate constraint violations and transient conflicts into client-facing errors
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        let Some(db_error) = error.as_database_error() else {
+            return AppError::Database(error);
+        };
+
+        if matches!(
+            db_error.code().as_deref(),
+            Some(SERIALIZATION_FAILURE | DEADLOCK_DETECTED | SQLITE_BUSY | SQLITE_BUSY_SNAPSHOT)
+        ) {
+            return AppError::Retryable(db_error.message().to_string());
+        }
+
+        let kind = match db_error.kind() {
+            ErrorKind::UniqueViolation => ConstraintKind::Unique,
+            ErrorKind::ForeignKeyViolation => ConstraintKind::ForeignKey,
+            ErrorKind::CheckViolation => ConstraintKind::Check,
+            ErrorKind::NotNullViolation => ConstraintKind::NotNull,
+            _ => return AppError::Database(error),
+        };
+
+        let sqlite_target = sqlite_constraint_target(db_error.message());
+        let table = db_error
+            .table()
+            .map(String::from)
+            .or_else(|| sqlite_target.as_ref().map(|(table, _)| table.clone()));
+        let constraint = db_error.constraint().map(String::from);
+        let field = db_error
+            .try_downcast_ref::<PgDatabaseError>()
+            .and_then(PgDatabaseError::column)
+            .map(String::from)
+            .or_else(|| {
+                constraint
+                    .as_deref()
+                    .and_then(|c| field_from_constraint(table.as_deref(), c))
+            })
+            .or_else(|| sqlite_target.map(|(_, column)| column));
+
+        AppError::Constraint(ConstraintViolation {
+            kind,
+            table,
+            constraint,
+            field,
+        })
+    }
+}
+
+// Repository methods return anyhow errors; recover the sqlx error or (from
+// `InMemoryRepository`) constraint violation when there is one
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<sqlx::Error>() {
+            Ok(sqlx_error) => return sqlx_error.into(),
+            Err(error) => error,
+        };
+
+        match error.downcast::<ConstraintViolation>() {
+            Ok(violation) => AppError::Constraint(violation),
+            Err(error) => AppError::Generic(error),
+        }
+    }
+}
+
+// Postgres default names: users_email_key, posts_author_id_fkey, ...
+fn field_from_constraint(table: Option<&str>, constraint: &str) -> Option<String> {
+    let columns = match table {
+        Some(table) => constraint.strip_prefix(table)?.strip_prefix('_')?,
+        None => constraint,
+    };
+
+    ["_key", "_fkey", "_check"]
+        .iter()
+        .find_map(|suffix| columns.strip_suffix(suffix))
+        .map(String::from)
+}
+
+// SQLite has no constraint names and only reports the (first) column in its
+// message, e.g. "UNIQUE constraint failed: users.email"
+fn sqlite_constraint_target(message: &str) -> Option<(String, String)> {
+    let (_, columns) = message.split_once(" constraint failed: ")?;
+    let (table, column) = columns.split(", ").next()?.split_once('.')?;
+    Some((table.to_string(), column.to_string()))
+}
+
 // Helper type for results
 pub type AppResult<T> = Result<T, AppError>;
 
 // Helper functions for common errors
 impl AppError {
-    pub fn bad_request<T: ToString>(msg: T) -> Self {
-        Self::BadRequest(msg.to_string())
+    pub fn bad_request(msg: impl Into<ErrorMessage>) -> Self {
+        Self::BadRequest(msg.into())
     }
 
     pub fn unauthorized<T: ToString>(msg: T) -> Self {
@@ -181,16 +599,16 @@ impl AppError {
         Self::Forbidden(msg.to_string())
     }
 
-    pub fn not_found<T: ToString>(msg: T) -> Self {
-        Self::NotFound(msg.to_string())
+    pub fn not_found(msg: impl Into<ErrorMessage>) -> Self {
+        Self::NotFound(msg.into())
     }
 
-    pub fn conflict<T: ToString>(msg: T) -> Self {
-        Self::Conflict(msg.to_string())
+    pub fn conflict(msg: impl Into<ErrorMessage>) -> Self {
+        Self::Conflict(msg.into())
     }
 
-    pub fn unprocessable<T: ToString>(msg: T) -> Self {
-        Self::UnprocessableEntity(msg.to_string())
+    pub fn unprocessable(msg: impl Into<ErrorMessage>) -> Self {
+        Self::UnprocessableEntity(msg.into())
     }
 
     pub fn internal<T: ToString>(msg: T) -> Self {
@@ -207,4 +625,121 @@ impl From<validator::ValidationErrors> for AppError {
     fn from(errors: validator::ValidationErrors) -> Self {
         AppError::Validation(errors)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body;
+    use serde_json::Value;
+
+    use super::*;
+
+    fn violation(kind: ConstraintKind, field: Option<&str>) -> ConstraintViolation {
+        ConstraintViolation {
+            kind,
+            table: Some("users".to_string()),
+            constraint: None,
+            field: field.map(String::from),
+        }
+    }
+
+    async fn render(error: AppError) -> (StatusCode, Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[test]
+    fn postgres_constraint_names_give_the_field() {
+        assert_eq!(
+            field_from_constraint(Some("users"), "users_email_key").as_deref(),
+            Some("email")
+        );
+        assert_eq!(
+            field_from_constraint(Some("posts"), "posts_author_id_fkey").as_deref(),
+            Some("author_id")
+        );
+        assert_eq!(
+            field_from_constraint(Some("posts"), "users_email_key"),
+            None
+        );
+        assert_eq!(field_from_constraint(None, "custom_name"), None);
+    }
+
+    #[test]
+    fn sqlite_messages_give_the_first_column() {
+        assert_eq!(
+            sqlite_constraint_target("UNIQUE constraint failed: likes.post_id, likes.user_id"),
+            Some(("likes".to_string(), "post_id".to_string()))
+        );
+        assert_eq!(sqlite_constraint_target("database is locked"), None);
+    }
+
+    #[tokio::test]
+    async fn unique_violations_are_conflicts_naming_the_field() {
+        let error = AppError::from(anyhow::Error::new(violation(
+            ConstraintKind::Unique,
+            Some("email"),
+        )));
+
+        let (status, body) = render(error).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "CONFLICT");
+        assert_eq!(body["message"], "A record with this email already exists");
+        assert_eq!(body["details"]["field"], "email");
+    }
+
+    #[tokio::test]
+    async fn other_violations_are_unprocessable() {
+        // Which reference is missing says nothing useful to the client
+        let error = AppError::Constraint(violation(ConstraintKind::ForeignKey, Some("author_id")));
+        let (status, body) = render(error).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["message"], "A referenced record does not exist");
+
+        let error = AppError::Constraint(violation(ConstraintKind::NotNull, None));
+        let (status, body) = render(error).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["message"], "A required value is missing");
+    }
+
+    #[test]
+    fn handler_messages_are_translated_with_their_arguments() {
+        assert_eq!(
+            AppError::not_found("post.not_found").user_message("fr"),
+            "Publication introuvable"
+        );
+        assert_eq!(
+            AppError::bad_request(ErrorMessage::new("search.unknown_type").arg("type", "tags"))
+                .user_message("en"),
+            "Unknown search type 'tags'"
+        );
+        assert_eq!(
+            AppError::not_found(ErrorMessage::default()).user_message("en"),
+            "Resource not found"
+        );
+    }
+
+    #[test]
+    fn the_catalog_is_built_once_per_locale() {
+        assert!(std::ptr::eq(AppError::catalog("fr"), AppError::catalog("fr-CA")));
+        assert!(std::ptr::eq(AppError::catalog("xx"), AppError::catalog("en")));
+        assert!(!std::ptr::eq(AppError::catalog("fr"), AppError::catalog("en")));
+    }
+
+    #[test]
+    fn other_errors_are_not_mistaken_for_violations() {
+        assert!(matches!(
+            AppError::from(sqlx::Error::RowNotFound),
+            AppError::Database(_)
+        ));
+        assert!(matches!(
+            AppError::from(anyhow::anyhow!("boom")),
+            AppError::Generic(_)
+        ));
+    }
+}
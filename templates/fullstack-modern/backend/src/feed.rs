@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthUser,
+    config::{Config, FeedStrategy},
+    error::AppResult,
+    models::{
+        ApiResponse, CursorDirection, CursorPage, CursorParams, FeedEntry, PageRequest, Post,
+    },
+    repository::Repository,
+    routing::Route,
+    services::Services,
+};
+
+// Newest entries kept in each precomputed feed; older posts drop off the end
+const FEED_CACHE_SIZE: i64 = 800;
+
+// Feeds nobody has read for a day expire and are rebuilt on the next read
+const FEED_CACHE_TTL_SECS: i64 = 24 * 60 * 60;
+
+// Adds a post to a feed only if the feed exists, so fan-out never creates a
+// partial feed that would hide everything published before it
+const PUSH_ENTRY: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    redis.call('ZADD', KEYS[1], ARGV[1], ARGV[2])
+    redis.call('ZREMRANGEBYRANK', KEYS[1], 0, -tonumber(ARGV[3]) - 1)
+end
+return 0
+";
+
+// Home feeds: a user's own posts plus those of everyone they follow and
+// haven't muted, newest first. With `feed_strategy = "read"` every page is a
+// query over the follow graph. With "write" each user's feed is a Redis
+// sorted set of post ids scored by creation time: built from the database on
+// first read, extended as followed users publish, and dropped whenever the
+// user's follow graph changes. Posts are always loaded from the database, so
+// deleted posts never show up and edits are never stale.
+
+// The caller's home feed, mounted at `/api/feed`
+pub fn routes() -> Vec<Route> {
+    vec![("GET", "/", get(home_feed))]
+}
+
+async fn home_feed(
+    State(services): State<Services>,
+    user: AuthUser,
+    Query(params): Query<CursorParams>,
+) -> AppResult<Json<ApiResponse<CursorPage<Post>>>> {
+    let page = services
+        .feed
+        .page(services.repository.as_ref(), &user.user_id, &params)
+        .await?;
+
+    Ok(Json(ApiResponse::success(page)))
+}
+
+#[derive(Clone)]
+pub struct HomeFeed {
+    // Only connected for fan-out on write
+    cache: Option<ConnectionManager>,
+}
+
+fn feed_key(user_id: &Uuid) -> String {
+    format!("feed:{}", user_id)
+}
+
+// Sorted-set scores are doubles; microseconds stay exact for the next two centuries
+fn score(created_at: &DateTime<Utc>) -> f64 {
+    created_at.timestamp_micros() as f64
+}
+
+fn entry(member: &str, score: f64) -> Option<FeedEntry> {
+    Some(FeedEntry {
+        post_id: Uuid::parse_str(member).ok()?,
+        created_at: DateTime::from_timestamp_micros(score as i64)?,
+    })
+}
+
+// The entries `page` covers, nearest to the cursor first; `entries` are newest first
+fn page_entries(entries: Vec<FeedEntry>, page: &PageRequest) -> Vec<FeedEntry> {
+    let key = |entry: &FeedEntry| (entry.created_at, entry.post_id);
+
+    match page.cursor {
+        Some(cursor) if cursor.direction == CursorDirection::Before => {
+            let bound = (cursor.created_at, cursor.id);
+            entries.into_iter().rev().filter(|entry| key(entry) > bound).collect()
+        }
+        Some(cursor) => {
+            let bound = (cursor.created_at, cursor.id);
+            entries.into_iter().filter(|entry| key(entry) < bound).collect()
+        }
+        None => entries,
+    }
+}
+
+impl HomeFeed {
+    pub async fn connect(config: &Config) -> anyhow::Result<Self> {
+        let cache = match config.feed_strategy {
+            FeedStrategy::Read => None,
+            FeedStrategy::Write => {
+                let client = redis::Client::open(config.redis_url.expose())?;
+                Some(client.get_connection_manager().await?)
+            }
+        };
+
+        Ok(Self { cache })
+    }
+
+    /// A page of `user_id`'s home feed.
+    pub async fn page(
+        &self,
+        repository: &dyn Repository,
+        user_id: &Uuid,
+        params: &CursorParams,
+    ) -> AppResult<CursorPage<Post>> {
+        let page = params.page_request()?;
+        let Some(cache) = &self.cache else {
+            return Ok(repository.get_home_feed_page(user_id, &page).await?);
+        };
+
+        let entries = cached_entries(cache.clone(), repository, user_id).await?;
+        let candidates = page_entries(entries, &page);
+
+        // Deleted posts are skipped, so keep loading until the page is full
+        let wanted = usize::try_from(page.fetch_limit()).unwrap_or(0);
+        let mut posts = Vec::with_capacity(wanted);
+        for chunk in candidates.chunks(wanted.max(1)) {
+            let ids: Vec<Uuid> = chunk.iter().map(|entry| entry.post_id).collect();
+            let mut loaded: HashMap<Uuid, Post> = repository
+                .get_posts_by_ids(&ids, Some(user_id))
+                .await?
+                .into_iter()
+                .map(|post| (post.id, post))
+                .collect();
+
+            posts.extend(ids.iter().filter_map(|id| loaded.remove(id)));
+            if posts.len() >= wanted {
+                break;
+            }
+        }
+        posts.truncate(wanted);
+
+        Ok(CursorPage::from_rows(posts, &page, |post| (post.created_at, post.id)))
+    }
+
+    /// Pushes a new post into the feeds of its author and their followers.
+    /// Feeds that aren't built yet are left alone; they pick the post up
+    /// when first read. Failures are logged rather than returned: the post
+    /// exists either way and shows up once the feed is rebuilt.
+    pub async fn publish(&self, repository: &dyn Repository, post: &Post) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+
+        if let Err(e) = push_to_subscribers(cache.clone(), repository, post).await {
+            tracing::warn!(post_id = %post.id, "Feed fan-out failed: {}", e);
+        }
+    }
+
+    /// Drops precomputed feeds after a follow graph change so the next
+    /// read rebuilds them. The change is already committed, so a failure is
+    /// only logged; the stale feeds expire on their own.
+    pub async fn invalidate(&self, user_ids: &[Uuid]) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+
+        let keys: Vec<String> = user_ids.iter().map(feed_key).collect();
+        let deleted: redis::RedisResult<()> = cache.clone().del(keys).await;
+        if let Err(e) = deleted {
+            tracing::warn!(?user_ids, "Feed invalidation failed: {}", e);
+        }
+    }
+}
+
+// `user_id`'s feed entries, newest first, rebuilding the sorted set if it has expired
+async fn cached_entries(
+    mut cache: ConnectionManager,
+    repository: &dyn Repository,
+    user_id: &Uuid,
+) -> AppResult<Vec<FeedEntry>> {
+    let key = feed_key(user_id);
+
+    let members: Vec<(String, f64)> = cache.zrevrange_withscores(&key, 0, -1).await?;
+    if !members.is_empty() {
+        let _: () = cache.expire(&key, FEED_CACHE_TTL_SECS).await?;
+        return Ok(members
+            .iter()
+            .filter_map(|(member, score)| entry(member, *score))
+            .collect());
+    }
+
+    // An empty feed isn't stored; rebuilding it is a cheap query that finds nothing
+    let entries = repository.get_home_feed_entries(user_id, FEED_CACHE_SIZE).await?;
+    if !entries.is_empty() {
+        let members: Vec<(f64, String)> = entries
+            .iter()
+            .map(|entry| (score(&entry.created_at), entry.post_id.to_string()))
+            .collect();
+
+        let _: () = redis::pipe()
+            .atomic()
+            .del(&key)
+            .zadd_multiple(&key, &members)
+            .expire(&key, FEED_CACHE_TTL_SECS)
+            .query_async(&mut cache)
+            .await?;
+    }
+
+    Ok(entries)
+}
+
+async fn push_to_subscribers(
+    mut cache: ConnectionManager,
+    repository: &dyn Repository,
+    post: &Post,
+) -> AppResult<()> {
+    let mut user_ids = repository.get_feed_subscribers(&post.author_id).await?;
+    user_ids.push(post.author_id);
+
+    let script = Script::new(PUSH_ENTRY);
+    let member = post.id.to_string();
+    for user_id in &user_ids {
+        let _: i64 = script
+            .key(feed_key(user_id))
+            .arg(score(&post.created_at))
+            .arg(&member)
+            .arg(FEED_CACHE_SIZE)
+            .invoke_async(&mut cache)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::models::{Cursor, CursorDirection};
+
+    // Newest first, a second apart
+    fn entries(count: i64) -> Vec<FeedEntry> {
+        let now = Utc::now();
+        (0..count)
+            .map(|n| FeedEntry {
+                post_id: Uuid::new_v4(),
+                created_at: now - Duration::seconds(n),
+            })
+            .collect()
+    }
+
+    fn from(entry: &FeedEntry, direction: CursorDirection) -> PageRequest {
+        PageRequest {
+            cursor: Some(Cursor {
+                created_at: entry.created_at,
+                id: entry.post_id,
+                direction,
+            }),
+            limit: 2,
+        }
+    }
+
+    fn ids(entries: &[FeedEntry]) -> Vec<Uuid> {
+        entries.iter().map(|entry| entry.post_id).collect()
+    }
+
+    #[test]
+    fn cached_entries_page_from_the_cursor() {
+        let all = entries(5);
+
+        let older = page_entries(all.clone(), &from(&all[1], CursorDirection::After));
+        assert_eq!(ids(&older), ids(&all[2..]));
+
+        // Nearest to the cursor first, as repositories return them
+        let newer = page_entries(all.clone(), &from(&all[3], CursorDirection::Before));
+        assert_eq!(ids(&newer), [2, 1, 0].map(|n| all[n].post_id));
+
+        assert_eq!(
+            ids(&page_entries(all.clone(), &PageRequest::first(2))),
+            ids(&all)
+        );
+    }
+
+    #[test]
+    fn entries_survive_their_sorted_set_score() {
+        let created_at = DateTime::from_timestamp_micros(Utc::now().timestamp_micros()).unwrap();
+        let post_id = Uuid::new_v4();
+
+        let decoded = entry(&post_id.to_string(), score(&created_at)).unwrap();
+        assert_eq!(decoded.post_id, post_id);
+        assert_eq!(decoded.created_at, created_at);
+        assert!(entry("not-a-uuid", 0.0).is_none());
+    }
+}
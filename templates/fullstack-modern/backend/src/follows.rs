@@ -0,0 +1,436 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{delete, get, post},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthUser,
+    database::Isolation,
+    error::{AppError, AppResult},
+    feed::HomeFeed,
+    models::{ApiResponse, CursorPage, CursorParams, FollowKind, FollowUser, Relationship},
+    repository::Repository,
+    routing::Route,
+    services::Services,
+};
+
+// Follows, mutes and blocks between users. Every edge is one-directional and
+// setting or clearing one is idempotent. A mute keeps the follow but hides
+// the muted user's posts from the home feed; a block drops follows both ways
+// and stops either user from following the other until it's lifted.
+
+async fn ensure_user(repository: &dyn Repository, user_id: &Uuid) -> AppResult<()> {
+    repository
+        .get_user_by_id(user_id)
+        .await?
+        .map(|_| ())
+        .ok_or_else(|| AppError::not_found("user.not_found"))
+}
+
+/// How `user_id` and `other_id` relate, from `user_id`'s side.
+pub async fn relationship(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    other_id: &Uuid,
+) -> AppResult<Relationship> {
+    ensure_user(repository, other_id).await?;
+
+    let edges = repository.get_follow_edges(user_id, other_id).await?;
+    Ok(Relationship::from_edges(user_id, &edges))
+}
+
+async fn set_edge(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    user_id: &Uuid,
+    target_id: &Uuid,
+    kind: FollowKind,
+    present: bool,
+) -> AppResult<Relationship> {
+    if user_id == target_id {
+        return Err(AppError::bad_request("follow.self"));
+    }
+    ensure_user(repository, target_id).await?;
+
+    // Serializable, so a block committed between the check and the insert
+    // can't leave a follow behind it. `None` means the follow was refused.
+    let changed = repository
+        .transaction_at(Isolation::Serializable, |tx| {
+            Box::pin(async move {
+                if present && kind == FollowKind::Follow {
+                    let edges = tx.get_follow_edges(user_id, target_id).await?;
+                    if Relationship::from_edges(user_id, &edges).is_blocked() {
+                        return Ok(None);
+                    }
+                }
+
+                let changed = if present {
+                    tx.add_follow(user_id, target_id, kind).await?
+                } else {
+                    tx.remove_follow(user_id, target_id, kind).await?
+                };
+                Ok(Some(changed))
+            })
+        })
+        .await?;
+    let Some(changed) = changed else {
+        return Err(AppError::forbidden("You can't follow this user"));
+    };
+
+    if changed {
+        // A block also removes the target's follow of this user
+        let affected = match kind {
+            FollowKind::Block => vec![*user_id, *target_id],
+            FollowKind::Follow | FollowKind::Mute => vec![*user_id],
+        };
+        feed.invalidate(&affected).await;
+    }
+
+    relationship(repository, user_id, target_id).await
+}
+
+pub async fn follow(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    user_id: &Uuid,
+    target_id: &Uuid,
+) -> AppResult<Relationship> {
+    set_edge(repository, feed, user_id, target_id, FollowKind::Follow, true).await
+}
+
+pub async fn unfollow(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    user_id: &Uuid,
+    target_id: &Uuid,
+) -> AppResult<Relationship> {
+    set_edge(repository, feed, user_id, target_id, FollowKind::Follow, false).await
+}
+
+pub async fn mute(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    user_id: &Uuid,
+    target_id: &Uuid,
+) -> AppResult<Relationship> {
+    set_edge(repository, feed, user_id, target_id, FollowKind::Mute, true).await
+}
+
+pub async fn unmute(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    user_id: &Uuid,
+    target_id: &Uuid,
+) -> AppResult<Relationship> {
+    set_edge(repository, feed, user_id, target_id, FollowKind::Mute, false).await
+}
+
+pub async fn block(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    user_id: &Uuid,
+    target_id: &Uuid,
+) -> AppResult<Relationship> {
+    set_edge(repository, feed, user_id, target_id, FollowKind::Block, true).await
+}
+
+pub async fn unblock(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    user_id: &Uuid,
+    target_id: &Uuid,
+) -> AppResult<Relationship> {
+    set_edge(repository, feed, user_id, target_id, FollowKind::Block, false).await
+}
+
+/// Users following `user_id`, most recent first.
+pub async fn followers(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    params: &CursorParams,
+) -> AppResult<CursorPage<FollowUser>> {
+    let page = params.page_request()?;
+    ensure_user(repository, user_id).await?;
+
+    Ok(repository.get_followers_page(user_id, &page).await?)
+}
+
+/// Users `user_id` follows, most recent first.
+pub async fn following(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    params: &CursorParams,
+) -> AppResult<CursorPage<FollowUser>> {
+    let page = params.page_request()?;
+    ensure_user(repository, user_id).await?;
+
+    Ok(repository.get_following_page(user_id, &page).await?)
+}
+
+// Follow graph of other users, mounted under `/api/users`. Setting and
+// clearing an edge both answer with the resulting relationship.
+pub fn routes() -> Vec<Route> {
+    vec![
+        ("GET", "/:id/relationship", get(show_relationship)),
+        ("POST", "/:id/follow", post(set_follow)),
+        ("DELETE", "/:id/follow", delete(clear_follow)),
+        ("POST", "/:id/mute", post(set_mute)),
+        ("DELETE", "/:id/mute", delete(clear_mute)),
+        ("POST", "/:id/block", post(set_block)),
+        ("DELETE", "/:id/block", delete(clear_block)),
+        ("GET", "/:id/followers", get(list_followers)),
+        ("GET", "/:id/following", get(list_following)),
+    ]
+}
+
+async fn show_relationship(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(other_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Relationship>>> {
+    let relationship = relationship(services.repository.as_ref(), &user.user_id, &other_id).await?;
+
+    Ok(Json(ApiResponse::success(relationship)))
+}
+
+async fn set_follow(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(target_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Relationship>>> {
+    let repository = services.repository.as_ref();
+    let relationship = follow(repository, &services.feed, &user.user_id, &target_id).await?;
+
+    Ok(Json(ApiResponse::success(relationship)))
+}
+
+async fn clear_follow(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(target_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Relationship>>> {
+    let repository = services.repository.as_ref();
+    let relationship = unfollow(repository, &services.feed, &user.user_id, &target_id).await?;
+
+    Ok(Json(ApiResponse::success(relationship)))
+}
+
+async fn set_mute(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(target_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Relationship>>> {
+    let repository = services.repository.as_ref();
+    let relationship = mute(repository, &services.feed, &user.user_id, &target_id).await?;
+
+    Ok(Json(ApiResponse::success(relationship)))
+}
+
+async fn clear_mute(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(target_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Relationship>>> {
+    let repository = services.repository.as_ref();
+    let relationship = unmute(repository, &services.feed, &user.user_id, &target_id).await?;
+
+    Ok(Json(ApiResponse::success(relationship)))
+}
+
+async fn set_block(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(target_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Relationship>>> {
+    let repository = services.repository.as_ref();
+    let relationship = block(repository, &services.feed, &user.user_id, &target_id).await?;
+
+    Ok(Json(ApiResponse::success(relationship)))
+}
+
+async fn clear_block(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(target_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Relationship>>> {
+    let repository = services.repository.as_ref();
+    let relationship = unblock(repository, &services.feed, &user.user_id, &target_id).await?;
+
+    Ok(Json(ApiResponse::success(relationship)))
+}
+
+async fn list_followers(
+    State(services): State<Services>,
+    _user: AuthUser,
+    Path(user_id): Path<Uuid>,
+    Query(params): Query<CursorParams>,
+) -> AppResult<Json<ApiResponse<CursorPage<FollowUser>>>> {
+    let followers = followers(services.repository.as_ref(), &user_id, &params).await?;
+
+    Ok(Json(ApiResponse::success(followers)))
+}
+
+async fn list_following(
+    State(services): State<Services>,
+    _user: AuthUser,
+    Path(user_id): Path<Uuid>,
+    Query(params): Query<CursorParams>,
+) -> AppResult<Json<ApiResponse<CursorPage<FollowUser>>>> {
+    let following = following(services.repository.as_ref(), &user_id, &params).await?;
+
+    Ok(Json(ApiResponse::success(following)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{Method, StatusCode},
+        Router,
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        feed,
+        models::User,
+        repository::memory::InMemoryRepository,
+        testing::{self, create_post, create_user},
+    };
+
+    async fn router(repository: &InMemoryRepository) -> Router {
+        let services = testing::services(repository).await;
+        testing::router(services.clone(), "/api/users", routes()).merge(testing::router(
+            services,
+            "/api/feed",
+            feed::routes(),
+        ))
+    }
+
+    async fn edge(
+        router: &Router,
+        method: Method,
+        user: &User,
+        target: &User,
+        kind: &str,
+    ) -> (StatusCode, Value) {
+        let uri = format!("/api/users/{}/{}", target.id, kind);
+        testing::send(router, method, &uri, Some(user), None).await
+    }
+
+    async fn feed_titles(router: &Router, user: &User) -> Vec<String> {
+        let (status, body) =
+            testing::send(router, Method::GET, "/api/feed", Some(user), None).await;
+        assert_eq!(status, StatusCode::OK);
+        body["data"]["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|post| post["title"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn following_is_idempotent_and_one_directional() {
+        let repository = InMemoryRepository::new();
+        let router = router(&repository).await;
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+
+        for _ in 0..2 {
+            let (status, body) = edge(&router, Method::POST, &alice, &bob, "follow").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["data"]["following"], true);
+            assert_eq!(body["data"]["followed_by"], false);
+        }
+
+        let uri = format!("/api/users/{}/relationship", alice.id);
+        let (_, body) = testing::send(&router, Method::GET, &uri, Some(&bob), None).await;
+        assert_eq!(body["data"]["followed_by"], true);
+
+        let uri = format!("/api/users/{}/followers", bob.id);
+        let (_, body) = testing::send(&router, Method::GET, &uri, Some(&bob), None).await;
+        assert_eq!(body["data"]["data"][0]["username"], "alice");
+        assert!(body["data"]["data"][0].get("email").is_none());
+
+        let (status, _) = edge(&router, Method::POST, &alice, &alice, "follow").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        for _ in 0..2 {
+            let (status, body) = edge(&router, Method::DELETE, &alice, &bob, "follow").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["data"]["following"], false);
+        }
+    }
+
+    #[tokio::test]
+    async fn blocking_drops_follows_both_ways() {
+        let repository = InMemoryRepository::new();
+        let router = router(&repository).await;
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+
+        edge(&router, Method::POST, &alice, &bob, "follow").await;
+        edge(&router, Method::POST, &bob, &alice, "follow").await;
+
+        let (status, body) = edge(&router, Method::POST, &alice, &bob, "block").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["blocking"], true);
+        assert_eq!(body["data"]["following"], false);
+        assert_eq!(body["data"]["followed_by"], false);
+
+        // Neither side can follow again while the block stands
+        let (status, _) = edge(&router, Method::POST, &bob, &alice, "follow").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = edge(&router, Method::POST, &alice, &bob, "follow").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        edge(&router, Method::DELETE, &alice, &bob, "block").await;
+        let (status, body) = edge(&router, Method::POST, &bob, &alice, "follow").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["following"], true);
+    }
+
+    #[tokio::test]
+    async fn home_feeds_follow_the_graph() {
+        let repository = InMemoryRepository::new();
+        let router = router(&repository).await;
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        let carol = create_user(&repository, "carol").await;
+
+        create_post(&repository, &alice, "Alice's").await;
+        create_post(&repository, &bob, "Bob's").await;
+        create_post(&repository, &carol, "Carol's").await;
+
+        assert_eq!(feed_titles(&router, &alice).await, ["Alice's"]);
+
+        edge(&router, Method::POST, &alice, &bob, "follow").await;
+        edge(&router, Method::POST, &alice, &carol, "follow").await;
+        assert_eq!(
+            feed_titles(&router, &alice).await,
+            ["Carol's", "Bob's", "Alice's"]
+        );
+
+        // Muting hides the posts but keeps the follow
+        let (_, body) = edge(&router, Method::POST, &alice, &carol, "mute").await;
+        assert_eq!(body["data"]["following"], true);
+        assert_eq!(body["data"]["muting"], true);
+        assert_eq!(feed_titles(&router, &alice).await, ["Bob's", "Alice's"]);
+
+        edge(&router, Method::POST, &bob, &alice, "block").await;
+        assert_eq!(feed_titles(&router, &alice).await, ["Alice's"]);
+    }
+
+    #[tokio::test]
+    async fn unknown_users_are_not_found() {
+        let repository = InMemoryRepository::new();
+        let router = router(&repository).await;
+        let alice = create_user(&repository, "alice").await;
+
+        let uri = format!("/api/users/{}/follow", Uuid::new_v4());
+        let (status, _) = testing::send(&router, Method::POST, &uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{error::ErrorParts, services::Services};
+
+// Locale used when a message is missing from the negotiated catalog
+pub const FALLBACK_LOCALE: &str = "en";
+
+// Message catalogs bundled into the binary, one JSON object per locale
+const CATALOG_SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("es", include_str!("../locales/es.json")),
+    ("fr", include_str!("../locales/fr.json")),
+];
+
+type Messages = HashMap<String, String>;
+
+tokio::task_local! {
+    static LOCALE: &'static str;
+}
+
+fn catalogs() -> &'static HashMap<&'static str, Messages> {
+    static CATALOGS: OnceLock<HashMap<&'static str, Messages>> = OnceLock::new();
+
+    CATALOGS.get_or_init(|| {
+        CATALOG_SOURCES
+            .iter()
+            .map(|(locale, source)| {
+                let messages = serde_json::from_str(source).unwrap_or_else(|e| {
+                    tracing::error!("Invalid message catalog for '{}': {}", locale, e);
+                    Messages::new()
+                });
+                (*locale, messages)
+            })
+            .collect()
+    })
+}
+
+/// Returns the bundled locale matching `tag` exactly or by primary subtag
+/// (`fr-CA` -> `fr`), ignoring case.
+pub fn supported_locale(tag: &str) -> Option<&'static str> {
+    let tag = tag.trim().to_ascii_lowercase();
+    let primary = tag.split(['-', '_']).next().unwrap_or_default();
+
+    locales()
+        .find(|locale| *locale == tag)
+        .or_else(|| locales().find(|locale| *locale == primary))
+}
+
+/// Every bundled locale.
+pub fn locales() -> impl Iterator<Item = &'static str> {
+    CATALOG_SOURCES.iter().map(|(locale, _)| *locale)
+}
+
+/// Locale of the request being handled on this task, or the fallback locale.
+pub fn current_locale() -> &'static str {
+    LOCALE.try_with(|locale| *locale).unwrap_or(FALLBACK_LOCALE)
+}
+
+/// Looks `key` up in `locale`, then in the fallback locale, and fills in
+/// `{name}` placeholders from `args`.
+pub fn lookup(locale: &str, key: &str, args: &[(&str, String)]) -> Option<String> {
+    let catalogs = catalogs();
+    let template = catalogs
+        .get(locale)
+        .and_then(|messages| messages.get(key))
+        .or_else(|| catalogs.get(FALLBACK_LOCALE)?.get(key))?;
+
+    Some(args.iter().fold(template.clone(), |message, (name, value)| {
+        message.replace(&format!("{{{}}}", name), value)
+    }))
+}
+
+/// Like `lookup`, but returns the key itself when no catalog has it.
+pub fn translate(locale: &str, key: &str, args: &[(&str, String)]) -> String {
+    lookup(locale, key, args).unwrap_or_else(|| key.to_string())
+}
+
+/// Picks the best bundled locale from an `Accept-Language` header, honouring
+/// q-values, or `default_locale` when nothing matches.
+pub fn negotiate(headers: &HeaderMap, default_locale: &str) -> &'static str {
+    let mut ranges: Vec<(&str, f32)> = headers
+        .get_all(header::ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+
+    // Stable sort keeps header order between equal q-values
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| supported_locale(tag))
+        .or_else(|| supported_locale(default_locale))
+        .unwrap_or(FALLBACK_LOCALE)
+}
+
+/// Negotiates the response locale for `AppError` messages and marks error
+/// responses with `Content-Language`.
+pub async fn negotiate_locale(
+    State(services): State<Services>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let locale = negotiate(request.headers(), &services.runtime.current().default_locale);
+
+    let mut response = LOCALE.scope(locale, next.run(request)).await;
+    if response.extensions().get::<ErrorParts>().is_some() {
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale));
+        headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{self, Body},
+        http::StatusCode,
+        middleware::from_fn_with_state,
+        routing::get,
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{error::AppError, repository::memory::InMemoryRepository, testing};
+
+    fn accepting(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn every_catalog_has_every_message() {
+        let english = &catalogs()[FALLBACK_LOCALE];
+        assert!(!english.is_empty());
+
+        for (locale, messages) in catalogs() {
+            let mut missing: Vec<&String> = english
+                .keys()
+                .filter(|key| !messages.contains_key(*key))
+                .collect();
+            missing.sort();
+            assert!(missing.is_empty(), "{} is missing {:?}", locale, missing);
+        }
+    }
+
+    #[test]
+    fn locales_match_by_primary_subtag() {
+        assert_eq!(supported_locale("fr-CA"), Some("fr"));
+        assert_eq!(supported_locale(" EN_gb "), Some("en"));
+        assert_eq!(supported_locale("de"), None);
+    }
+
+    #[test]
+    fn the_best_weighted_locale_wins() {
+        assert_eq!(negotiate(&accepting("de, fr;q=0.5, es;q=0.8"), "en"), "es");
+        assert_eq!(negotiate(&accepting("fr;q=0, *"), "es"), "es");
+        assert_eq!(negotiate(&HeaderMap::new(), "xx"), FALLBACK_LOCALE);
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let args = [("field", "email".to_string())];
+
+        assert_eq!(
+            translate("fr", "constraint.unique_field", &args),
+            "Un enregistrement avec ce email existe déjà"
+        );
+        assert_eq!(translate("fr", "no.such.key", &[]), "no.such.key");
+    }
+
+    #[tokio::test]
+    async fn error_responses_use_the_negotiated_locale() {
+        let services = testing::services(&InMemoryRepository::new()).await;
+        let router = Router::new()
+            .route(
+                "/missing",
+                get(|| async { Err::<(), _>(AppError::not_found("")) }),
+            )
+            .layer(from_fn_with_state(services.clone(), negotiate_locale))
+            .with_state(services);
+        let request = Request::builder()
+            .uri("/missing")
+            .header(header::ACCEPT_LANGUAGE, "fr-FR")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_LANGUAGE], "fr");
+        assert_eq!(response.headers()[header::VARY], "accept-language");
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["message"], "Ressource introuvable");
+    }
+}
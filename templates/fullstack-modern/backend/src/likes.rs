@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{delete, get, post},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthUser,
+    error::{AppError, AppResult},
+    models::{ApiResponse, CursorPage, CursorParams, Post, PostLiker},
+    repository::Repository,
+    routing::Route,
+    services::Services,
+};
+
+// Likes on live posts. Liking and unliking are idempotent: repeating either
+// returns the post as it already is. `likes_count` is maintained by the
+// storage backend; `repair-counters` recomputes it if it ever drifts.
+
+fn not_found() -> AppError {
+    AppError::not_found("post.not_found")
+}
+
+async fn ensure_live_post(repository: &dyn Repository, post_id: &Uuid) -> AppResult<()> {
+    repository
+        .get_post_status(post_id)
+        .await?
+        .filter(|status| status.deleted_at.is_none())
+        .map(|_| ())
+        .ok_or_else(not_found)
+}
+
+// The post as `user_id` now sees it, with updated `likes_count` and `is_liked`
+async fn liked_post(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    post_id: &Uuid,
+) -> AppResult<Post> {
+    repository
+        .get_post_by_id(post_id, Some(user_id))
+        .await?
+        .ok_or_else(not_found)
+}
+
+pub async fn like_post(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    post_id: &Uuid,
+) -> AppResult<Post> {
+    ensure_live_post(repository, post_id).await?;
+    repository.like_post(post_id, user_id).await?;
+
+    liked_post(repository, user_id, post_id).await
+}
+
+pub async fn unlike_post(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    post_id: &Uuid,
+) -> AppResult<Post> {
+    ensure_live_post(repository, post_id).await?;
+    repository.unlike_post(post_id, user_id).await?;
+
+    liked_post(repository, user_id, post_id).await
+}
+
+/// Users who liked a post, most recent first.
+pub async fn list_likers(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+    params: &CursorParams,
+) -> AppResult<CursorPage<PostLiker>> {
+    let page = params.page_request()?;
+    ensure_live_post(repository, post_id).await?;
+
+    Ok(repository.get_post_likers(post_id, &page).await?)
+}
+
+// Liking posts, mounted under `/api/posts`
+pub fn routes() -> Vec<Route> {
+    vec![
+        ("POST", "/:id/like", post(like)),
+        ("DELETE", "/:id/like", delete(unlike)),
+        ("GET", "/:id/likers", get(likers)),
+    ]
+}
+
+async fn like(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(post_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Post>>> {
+    let post = like_post(services.repository.as_ref(), &user.user_id, &post_id).await?;
+
+    Ok(Json(ApiResponse::success(post)))
+}
+
+async fn unlike(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(post_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Post>>> {
+    let post = unlike_post(services.repository.as_ref(), &user.user_id, &post_id).await?;
+
+    Ok(Json(ApiResponse::success(post)))
+}
+
+async fn likers(
+    State(services): State<Services>,
+    _user: AuthUser,
+    Path(post_id): Path<Uuid>,
+    Query(params): Query<CursorParams>,
+) -> AppResult<Json<ApiResponse<CursorPage<PostLiker>>>> {
+    let likers = list_likers(services.repository.as_ref(), &post_id, &params).await?;
+
+    Ok(Json(ApiResponse::success(likers)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use super::*;
+    use crate::{
+        repository::{memory::InMemoryRepository, PostRepository},
+        testing::{self, create_post, create_user},
+    };
+
+    #[tokio::test]
+    async fn liking_and_unliking_are_idempotent() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/posts", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        let post_id = create_post(&repository, &alice, "Likeable").await;
+        let uri = format!("/api/posts/{}/like", post_id);
+
+        for _ in 0..2 {
+            let (status, body) = testing::send(&router, Method::POST, &uri, Some(&bob), None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["data"]["likes_count"], 1);
+            assert_eq!(body["data"]["is_liked"], true);
+        }
+
+        // Others see the count, but not as their own like
+        let post = like_post(&repository, &alice.id, &post_id).await.unwrap();
+        assert_eq!(post.likes_count, 2);
+        let post = unlike_post(&repository, &alice.id, &post_id).await.unwrap();
+        assert_eq!(post.likes_count, 1);
+        assert!(!post.is_liked);
+
+        for _ in 0..2 {
+            let (status, body) =
+                testing::send(&router, Method::DELETE, &uri, Some(&bob), None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["data"]["likes_count"], 0);
+            assert_eq!(body["data"]["is_liked"], false);
+        }
+    }
+
+    #[tokio::test]
+    async fn likers_are_listed_most_recent_first() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/posts", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "Popular").await;
+        for name in ["bob", "carol", "dave"] {
+            let fan = create_user(&repository, name).await;
+            like_post(&repository, &fan.id, &post_id).await.unwrap();
+        }
+
+        let uri = format!("/api/posts/{}/likers?limit=2", post_id);
+        let (status, body) = testing::send(&router, Method::GET, &uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["data"][0]["username"], "dave");
+        assert_eq!(body["data"]["data"][1]["username"], "carol");
+        // Other users' emails stay private
+        assert!(body["data"]["data"][0].get("email").is_none());
+
+        let cursor = body["data"]["next_cursor"].as_str().unwrap();
+        let uri = format!("/api/posts/{}/likers?limit=2&cursor={}", post_id, cursor);
+        let (_, body) = testing::send(&router, Method::GET, &uri, Some(&alice), None).await;
+        assert_eq!(body["data"]["data"][0]["username"], "bob");
+        assert!(body["data"]["next_cursor"].is_null());
+    }
+
+    #[tokio::test]
+    async fn deleted_posts_cant_be_liked() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "Short-lived").await;
+        repository.delete_post(&post_id).await.unwrap();
+
+        let error = like_post(&repository, &alice.id, &post_id)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::NotFound(_)));
+        let params = CursorParams {
+            cursor: None,
+            limit: None,
+        };
+        let error = list_likers(&repository, &post_id, &params)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::NotFound(_)));
+    }
+}
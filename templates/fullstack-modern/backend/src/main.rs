@@ -1,48 +1,96 @@
 mod api;
+mod audit;
 mod auth;
+mod cli;
+mod comments;
 mod config;
 mod database;
 mod error;
+mod feed;
+mod follows;
+mod i18n;
+mod likes;
+mod meta;
 mod middleware;
 mod models;
+mod outbox;
+mod post_lifecycle;
+mod problem;
+mod profiles;
+mod repository;
+mod request_id;
+mod routing;
+mod runtime;
+mod search;
+mod seed;
 mod services;
+#[cfg(test)]
+mod testing;
 mod websocket;
 
 use std::net::SocketAddr;
 
 use axum::{
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Request, State},
+    http::HeaderValue,
+    middleware::{from_fn, from_fn_with_state, Next},
+    response::Response,
+    routing::get,
     Router,
 };
-use tower::ServiceBuilder;
+use clap::Parser;
+use tower::{Layer, ServiceBuilder, ServiceExt};
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::{AllowOrigin, Any, CorsLayer},
     trace::TraceLayer,
 };
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt};
 
 use crate::{
     api::{auth as auth_routes, chat, posts, profile, upload, users},
+    cli::{Cli, Command},
     config::Config,
-    database::Database,
+    database::{read_your_writes, Backend},
     middleware::{auth::AuthLayer, rate_limit::RateLimitLayer},
+    routing::Route,
+    runtime::{LogFilterHandle, RuntimeSettings},
     services::Services,
     websocket::websocket_handler,
 };
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
+    let cli = Cli::parse();
+
+    // Initialize tracing (the filter can be swapped later by `log_filter` reloads)
+    let (log_filter, log_filter_handle) = reload::Layer::new(runtime::default_log_filter());
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "backend=debug,tower_http=debug".into()),
-        )
+        .with(log_filter)
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(log_filter_handle).await,
+        Command::Migrate { action } => cli::migrate(action).await,
+        Command::Seed(args) => cli::seed(args).await,
+        Command::CreateUser(args) => cli::create_user(args).await,
+        Command::IssueToken { user } => cli::issue_token(&user).await,
+        Command::RepairCounters => cli::repair_counters().await,
+        Command::CheckConfig => cli::check_config(),
+        Command::ErrorCatalog { locale } => cli::error_catalog(&locale),
+        Command::Routes => {
+            print_routes();
+            Ok(())
+        }
+    }
+}
+
+async fn serve(log_filter_handle: LogFilterHandle) -> anyhow::Result<()> {
     // Load configuration
-    let config = Config::from_env()?;
+    let config = Config::load()?;
+    config.validate()?;
+    tracing::debug!("Effective configuration:\n{}", config.describe());
+    auth::set_jwt_secret(config.jwt_secret.expose());
     
     // Initialize Sentry (optional)
     let _guard = if let Some(dsn) = &config.sentry_dsn {
@@ -58,19 +106,33 @@ async fn main() -> anyhow::Result<()> {
     };
 
     // Initialize database
-    let database = Database::new(&config.database_url).await?;
-    database.migrate().await?;
+    let backend = Backend::connect(&config).await?;
+    if config.auto_migrate {
+        backend.migrate().await?;
+    } else {
+        let pending = backend.pending_migrations().await?;
+        if !pending.is_empty() {
+            tracing::warn!(
+                "{} migrations are pending and auto_migrate is off; run `migrate up`",
+                pending.len()
+            );
+        }
+    }
+
+    // Runtime settings, reloaded on SIGHUP or config file changes
+    let runtime = RuntimeSettings::new(&config, Some(log_filter_handle))?;
+    runtime.spawn_watchers();
 
     // Initialize services
-    let services = Services::new(config.clone(), database).await?;
+    let services = Services::new(config.clone(), backend.repository(), runtime).await?;
+    services.audit.admin_roles(&config.admin_emails).await;
+    post_lifecycle::spawn_purge(services.repository.clone(), services.runtime.clone());
+    websocket::spawn_event_fan_out(&services.events, services.connection_manager.clone());
+    outbox::spawn_relay(services.repository.clone(), services.events.clone());
+    outbox::spawn_purge(services.repository.clone());
 
     // Build our application with routes
-    let app = Router::new()
-        // Health check
-        .route("/health", get(health_check))
-        
-        // WebSocket endpoint
-        .route("/ws", get(websocket_handler))
+    let app = routing::router(top_level_routes())
         
         // API routes
         .nest("/api", api_routes(services.clone()))
@@ -78,14 +140,14 @@ async fn main() -> anyhow::Result<()> {
         // Middleware
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(
-                    CorsLayer::new()
-                        .allow_origin(Any)
-                        .allow_methods(Any)
-                        .allow_headers(Any),
-                )
-                .layer(RateLimitLayer::new())
+                .layer(from_fn(request_id::request_id))
+                .layer(from_fn_with_state(services.clone(), audit::client_info))
+                .layer(from_fn(read_your_writes))
+                .layer(TraceLayer::new_for_http().make_span_with(request_id::make_span))
+                .layer(from_fn_with_state(services.clone(), problem::problem_details))
+                .layer(from_fn_with_state(services.clone(), i18n::negotiate_locale))
+                .layer(cors_layer(&config, services.runtime.clone()))
+                .layer(RateLimitLayer::new(services.runtime.clone()))
                 .layer(AuthLayer::new(services.clone())),
         )
         .with_state(services);
@@ -96,26 +158,138 @@ async fn main() -> anyhow::Result<()> {
 
     // Start server
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Peer addresses are recorded in the audit log
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
 
-fn api_routes(services: Services) -> Router<Services> {
-    Router::new()
+// Routes outside /api
+fn top_level_routes() -> Vec<Route> {
+    vec![
+        // Health check
+        ("GET", "/health", get(health_check)),
+        // WebSocket endpoint
+        ("GET", "/ws", get(websocket_handler)),
+    ]
+}
+
+// Route groups nested under /api, listed route by route
+fn api_route_groups() -> Vec<(&'static str, Vec<Route>)> {
+    vec![
+        // Public
+        ("/meta", meta::routes()),
+        // Protected routes
+        ("/posts", post_lifecycle::routes()),
+        ("/posts", comments::routes()),
+        ("/posts", likes::routes()),
+        ("/users", follows::routes()),
+        ("/feed", feed::routes()),
+        ("/search", search::routes()),
+        // Admin only
+        ("/admin/audit", audit::routes()),
+    ]
+}
+
+// Route groups built by the `api` modules, which hand back finished routers.
+// A group above with the same prefix is merged into them.
+fn api_module_groups() -> Vec<(&'static str, Router<Services>)> {
+    vec![
         // Authentication routes (public)
-        .nest("/auth", auth_routes::routes())
-        
+        ("/auth", auth_routes::routes()),
         // Protected routes
-        .nest("/profile", profile::routes())
-        .nest("/users", users::routes())
-        .nest("/posts", posts::routes())
-        .nest("/chat", chat::routes())
-        .nest("/upload", upload::routes())
-        
+        ("/profile", profile::routes()),
+        ("/users", users::routes()),
+        ("/posts", posts::routes()),
+        ("/chat", chat::routes()),
+        ("/upload", upload::routes()),
+    ]
+}
+
+fn api_routes(services: Services) -> Router<Services> {
+    let mut groups = api_module_groups();
+    for (prefix, routes) in api_route_groups() {
+        let routes = routing::router(routes);
+        match groups.iter_mut().find(|(existing, _)| *existing == prefix) {
+            Some((_, router)) => *router = std::mem::take(router).merge(routes),
+            None => groups.push((prefix, routes)),
+        }
+    }
+
+    // Capped per request, so a reloaded `max_file_size` applies at once
+    if let Some((_, router)) = groups.iter_mut().find(|(prefix, _)| *prefix == "/upload") {
+        let limit = from_fn_with_state(services.clone(), upload_limit);
+        *router = std::mem::take(router).layer(limit);
+    }
+
+    groups
+        .into_iter()
+        .fold(Router::new(), |router, (prefix, routes)| router.nest(prefix, routes))
         .with_state(services)
 }
 
+// Backs the `routes` subcommand, built from the same tables as the router
+fn print_routes() {
+    let mut routes: Vec<(&str, String)> = top_level_routes()
+        .into_iter()
+        .map(|(method, path, _)| (method, path.to_string()))
+        .collect();
+    for (prefix, group) in api_route_groups() {
+        let prefix = format!("/api{}", prefix);
+        routes.extend(
+            group
+                .into_iter()
+                .map(|(method, path, _)| (method, routing::full_path(&prefix, path))),
+        );
+    }
+    routes.sort_by(|a, b| (&a.1, a.0).cmp(&(&b.1, b.0)));
+
+    for (method, path) in routes {
+        println!("{:<6} {}", method, path);
+    }
+
+    // Routers from the `api` modules can't be listed route by route
+    for (prefix, _) in api_module_groups() {
+        println!("{:<6} /api{}/*", "*", prefix);
+    }
+}
+
+async fn upload_limit(State(services): State<Services>, request: Request, next: Next) -> Response {
+    let limit = DefaultBodyLimit::max(services.runtime.current().max_file_size);
+    match limit.layer(next).oneshot(request).await {
+        Ok(response) => response,
+        Err(infallible) => match infallible {},
+    }
+}
+
+// `cors_origins` is fixed at startup; `frontend_url` is allowed too and read
+// per request, so reloading it takes effect without a restart
+fn cors_layer(config: &Config, runtime: RuntimeSettings) -> CorsLayer {
+    let origins = if config.allows_any_origin() {
+        AllowOrigin::any()
+    } else {
+        let allowed: Vec<HeaderValue> = config
+            .cors_origins
+            .iter()
+            .filter_map(|origin| {
+                origin
+                    .parse()
+                    .map_err(|_| tracing::warn!("Ignoring invalid CORS origin '{}'", origin))
+                    .ok()
+            })
+            .collect();
+        AllowOrigin::predicate(move |origin, _| {
+            let current = runtime.current();
+            allowed.contains(origin) || *origin == *current.frontend_url.trim_end_matches('/')
+        })
+    };
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
+}
+
 async fn health_check() -> &'static str {
     "OK"
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+use axum::{extract::State, routing::get, Json};
+
+use crate::{
+    auth::{require_admin, AuthUserWithRole},
+    error::{AppError, AppResult, ErrorCatalogEntry},
+    i18n,
+    models::ApiResponse,
+    repository::cached::CacheStats,
+    routing::Route,
+    services::Services,
+};
+
+// Metadata about the API itself. The error catalog is public; cache stats
+// are for admins
+pub fn routes() -> Vec<Route> {
+    vec![
+        ("GET", "/errors", get(error_catalog)),
+        ("GET", "/cache", get(cache_stats)),
+    ]
+}
+
+/// Lists every error code with its status, default message (in the
+/// negotiated locale) and whether it is retryable.
+async fn error_catalog() -> Json<ApiResponse<Vec<ErrorCatalogEntry>>> {
+    Json(ApiResponse::success(AppError::catalog(i18n::current_locale()).to_vec()))
+}
+
+/// Hit and miss counts for the user and post cache since startup; `null`
+/// when the cache is off.
+async fn cache_stats(
+    State(services): State<Services>,
+    user: AuthUserWithRole,
+) -> AppResult<Json<ApiResponse<Option<CacheStats>>>> {
+    require_admin(&user)?;
+
+    let stats = services.cache.as_ref().map(|metrics| metrics.stats());
+    Ok(Json(ApiResponse::success(stats)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use axum::http::{Method, StatusCode};
+
+    use super::*;
+    use crate::{repository::memory::InMemoryRepository, testing};
+
+    #[tokio::test]
+    async fn the_error_catalog_lists_each_code_once() {
+        let services = testing::services(&InMemoryRepository::new()).await;
+        let router = testing::router(services, "/api/meta", routes());
+
+        let (status, body) =
+            testing::send(&router, Method::GET, "/api/meta/errors", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let entries = body["data"].as_array().unwrap();
+        let codes: HashSet<&str> = entries
+            .iter()
+            .map(|entry| entry["code"].as_str().unwrap())
+            .collect();
+        assert_eq!(codes.len(), entries.len());
+
+        let entry = |code: &str| {
+            entries
+                .iter()
+                .find(|entry| entry["code"] == code)
+                .unwrap_or_else(|| panic!("{} is listed", code))
+        };
+        assert_eq!(entry("CONFLICT")["status"], 409);
+        assert_eq!(entry("RETRYABLE_ERROR")["retryable"], true);
+        assert_eq!(entry("NOT_FOUND")["retryable"], false);
+        // A message missing from the catalog would come back as its key
+        for entry in entries {
+            let message = entry["message"].as_str().unwrap();
+            assert!(
+                !message.starts_with("error."),
+                "{} has no message",
+                entry["code"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_stats_are_for_admins_and_null_without_a_cache() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/meta", routes());
+        let admin = testing::create_user(&repository, "admin").await;
+        let member = testing::create_user(&repository, "member").await;
+
+        let uri = "/api/meta/cache";
+        let (status, _) = testing::send(&router, Method::GET, uri, None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = testing::send(&router, Method::GET, uri, Some(&member), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = testing::send(&router, Method::GET, uri, Some(&admin), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"].is_null());
+    }
+}
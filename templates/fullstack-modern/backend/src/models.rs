@@ -1,10 +1,15 @@
-use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::error::{AppError, AppResult, ErrorMessage};
+
 // User models
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
     pub id: Uuid,
     pub email: String,
@@ -16,6 +21,28 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
+/// What other users may see of a user: no email, no account timestamps.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PublicProfile {
+    pub id: Uuid,
+    pub username: Option<String>,
+    pub full_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+}
+
+impl From<User> for PublicProfile {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            full_name: user.full_name,
+            avatar_url: user.avatar_url,
+            bio: user.bio,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateUser {
     pub id: Uuid,
@@ -116,6 +143,14 @@ pub struct CreatePost {
     pub author_id: Uuid,
 }
 
+/// Ownership and lifecycle state of a post, soft-deleted ones included.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PostStatus {
+    pub id: Uuid,
+    pub author_id: Uuid,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct UpdatePost {
     #[validate(length(min = 1, max = 200))]
@@ -124,8 +159,614 @@ pub struct UpdatePost {
     pub content: Option<String>,
 }
 
-// Chat models
+/// The title and content a post had before its `revision`th edit.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PostRevision {
+    pub post_id: Uuid,
+    pub revision: i32,
+    pub editor_id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Unified diff between two versions of a post; `None` is the current version.
+#[derive(Debug, Serialize)]
+pub struct PostDiff {
+    pub post_id: Uuid,
+    pub from: Option<i32>,
+    pub to: Option<i32>,
+    pub diff: String,
+}
+
+/// `?from=` and `?to=` revision numbers; either left out means the current version.
+#[derive(Debug, Deserialize)]
+pub struct PostDiffParams {
+    pub from: Option<i32>,
+    pub to: Option<i32>,
+}
+
+/// A user who liked a post, and when.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostLiker {
+    #[serde(flatten)]
+    pub user: PublicProfile,
+    pub liked_at: DateTime<Utc>,
+}
+
+// Internal struct for liker queries
+#[derive(Debug, sqlx::FromRow)]
+pub struct PostLikerRow {
+    pub id: Uuid,
+    pub username: Option<String>,
+    pub full_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub liked_at: DateTime<Utc>,
+}
+
+impl From<PostLikerRow> for PostLiker {
+    fn from(row: PostLikerRow) -> Self {
+        Self {
+            user: PublicProfile {
+                id: row.id,
+                username: row.username,
+                full_name: row.full_name,
+                avatar_url: row.avatar_url,
+                bio: row.bio,
+            },
+            liked_at: row.liked_at,
+        }
+    }
+}
+
+// Comment models
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub author: PublicProfile,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Internal struct for database queries
+#[derive(Debug, sqlx::FromRow)]
+pub struct CommentWithAuthor {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub author_username: Option<String>,
+    pub author_full_name: Option<String>,
+    pub author_avatar_url: Option<String>,
+    pub author_bio: Option<String>,
+}
+
+impl From<CommentWithAuthor> for Comment {
+    fn from(comment: CommentWithAuthor) -> Self {
+        Self {
+            id: comment.id,
+            post_id: comment.post_id,
+            parent_id: comment.parent_id,
+            author_id: comment.author_id,
+            author: PublicProfile {
+                id: comment.author_id,
+                username: comment.author_username,
+                full_name: comment.author_full_name,
+                avatar_url: comment.author_avatar_url,
+                bio: comment.author_bio,
+            },
+            content: comment.content,
+            created_at: comment.created_at,
+            updated_at: comment.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateComment {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Uuid,
+    #[validate(length(min = 1, max = 5000))]
+    pub content: String,
+}
+
+/// Request body for a comment; the post and author come from the request.
+#[derive(Debug, Deserialize)]
+pub struct NewComment {
+    pub parent_id: Option<Uuid>,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateComment {
+    #[validate(length(min = 1, max = 5000))]
+    pub content: String,
+}
+
+/// Sibling order in a comment tree; `Top` puts the most replied-to first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentSort {
+    #[default]
+    Newest,
+    Oldest,
+    Top,
+}
+
+impl CommentSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentSort::Newest => "newest",
+            CommentSort::Oldest => "oldest",
+            CommentSort::Top => "top",
+        }
+    }
+}
+
+/// A slice of a post's comment tree: `limit` children of `parent_id` (the
+/// post itself when `None`) starting at `offset`, each with up to
+/// `replies_limit` replies per level, `max_depth` levels deep.
+#[derive(Debug, Clone)]
+pub struct CommentTreeRequest {
+    pub parent_id: Option<Uuid>,
+    pub sort: CommentSort,
+    pub max_depth: i32,
+    pub limit: i64,
+    pub offset: i64,
+    pub replies_limit: i64,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CommentTreeParams {
+    pub parent_id: Option<Uuid>,
+    #[serde(default)]
+    pub sort: CommentSort,
+    #[validate(range(min = 1, max = 10))]
+    pub depth: Option<i32>,
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+    #[validate(range(min = 0))]
+    pub offset: Option<i64>,
+    #[validate(range(min = 0, max = 100))]
+    pub replies: Option<i64>,
+}
+
+impl CommentTreeParams {
+    pub const DEFAULT_DEPTH: i32 = 3;
+    pub const DEFAULT_LIMIT: i64 = 20;
+    pub const DEFAULT_REPLIES: i64 = 3;
+
+    pub fn tree_request(&self) -> AppResult<CommentTreeRequest> {
+        self.validate()?;
+
+        Ok(CommentTreeRequest {
+            parent_id: self.parent_id,
+            sort: self.sort,
+            max_depth: self.depth.unwrap_or(Self::DEFAULT_DEPTH),
+            limit: self.limit.unwrap_or(Self::DEFAULT_LIMIT),
+            offset: self.offset.unwrap_or(0),
+            replies_limit: self.replies.unwrap_or(Self::DEFAULT_REPLIES),
+        })
+    }
+}
+
+// Internal struct for comment tree queries: one node, flattened, with its
+// place in the tree
+#[derive(Debug, sqlx::FromRow)]
+pub struct CommentTreeRow {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub author_username: Option<String>,
+    pub author_full_name: Option<String>,
+    pub author_avatar_url: Option<String>,
+    pub author_bio: Option<String>,
+    /// 1 for children of the requested parent.
+    pub depth: i32,
+    /// 1-based rank among its siblings in the requested sort order.
+    pub position: i64,
+    pub sibling_count: i64,
+    pub reply_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentNode {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub depth: i32,
+    pub reply_count: i64,
+    pub replies: Vec<CommentNode>,
+    /// Replies not included below this node, by the depth or per-level limit.
+    pub more_replies: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentTree {
+    pub comments: Vec<CommentNode>,
+    /// Children of the requested parent after this slice.
+    pub more_comments: i64,
+}
+
+impl CommentTree {
+    /// Assembles the rows a repository fetched for `request`, in any order.
+    pub fn from_rows(rows: Vec<CommentTreeRow>, request: &CommentTreeRequest) -> Self {
+        let mut children: HashMap<Option<Uuid>, Vec<CommentTreeRow>> = HashMap::new();
+        for row in rows {
+            children.entry(row.parent_id).or_default().push(row);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by_key(|row| row.position);
+        }
+
+        let roots = children.remove(&request.parent_id).unwrap_or_default();
+        let more_comments = roots.last().map_or(0, |last| last.sibling_count - last.position);
+
+        Self {
+            comments: Self::nodes(roots, &mut children),
+            more_comments,
+        }
+    }
+
+    fn nodes(
+        rows: Vec<CommentTreeRow>,
+        children: &mut HashMap<Option<Uuid>, Vec<CommentTreeRow>>,
+    ) -> Vec<CommentNode> {
+        rows.into_iter()
+            .map(|row| {
+                let replies = children.remove(&Some(row.id)).unwrap_or_default();
+                let replies = Self::nodes(replies, children);
+                let more_replies = row.reply_count - replies.len() as i64;
+
+                CommentNode {
+                    depth: row.depth,
+                    reply_count: row.reply_count,
+                    more_replies,
+                    replies,
+                    comment: Comment {
+                        id: row.id,
+                        post_id: row.post_id,
+                        parent_id: row.parent_id,
+                        author_id: row.author_id,
+                        author: PublicProfile {
+                            id: row.author_id,
+                            username: row.author_username,
+                            full_name: row.author_full_name,
+                            avatar_url: row.author_avatar_url,
+                            bio: row.author_bio,
+                        },
+                        content: row.content,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+// Follow models
+
+/// A directed edge from one user to another. A user can follow, mute and
+/// block the same target independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "follow_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FollowKind {
+    Follow,
+    Mute,
+    Block,
+}
+
+// Internal struct for relationship queries
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FollowEdge {
+    pub user_id: Uuid,
+    pub target_id: Uuid,
+    pub kind: FollowKind,
+}
+
+/// How another user relates to `user_id`, from `user_id`'s side.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Relationship {
+    pub following: bool,
+    pub followed_by: bool,
+    pub muting: bool,
+    pub blocking: bool,
+    pub blocked_by: bool,
+}
+
+impl Relationship {
+    /// Folds the edges between `user_id` and one other user, in either direction.
+    pub fn from_edges(user_id: &Uuid, edges: &[FollowEdge]) -> Self {
+        let mut relationship = Self::default();
+        for edge in edges {
+            let outgoing = edge.user_id == *user_id;
+            match (edge.kind, outgoing) {
+                (FollowKind::Follow, true) => relationship.following = true,
+                (FollowKind::Follow, false) => relationship.followed_by = true,
+                (FollowKind::Mute, true) => relationship.muting = true,
+                // Whether someone muted you is theirs to know
+                (FollowKind::Mute, false) => {}
+                (FollowKind::Block, true) => relationship.blocking = true,
+                (FollowKind::Block, false) => relationship.blocked_by = true,
+            }
+        }
+        relationship
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.blocking || self.blocked_by
+    }
+}
+
+/// A follower or followed user, and when the follow began.
+#[derive(Debug, Clone, Serialize)]
+pub struct FollowUser {
+    #[serde(flatten)]
+    pub user: PublicProfile,
+    pub followed_at: DateTime<Utc>,
+}
+
+// Internal struct for follower queries
+#[derive(Debug, sqlx::FromRow)]
+pub struct FollowUserRow {
+    pub id: Uuid,
+    pub username: Option<String>,
+    pub full_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub followed_at: DateTime<Utc>,
+}
+
+impl From<FollowUserRow> for FollowUser {
+    fn from(row: FollowUserRow) -> Self {
+        Self {
+            user: PublicProfile {
+                id: row.id,
+                username: row.username,
+                full_name: row.full_name,
+                avatar_url: row.avatar_url,
+                bio: row.bio,
+            },
+            followed_at: row.followed_at,
+        }
+    }
+}
+
+/// One post in a home feed, by its keyset key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::FromRow)]
+pub struct FeedEntry {
+    pub post_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+// Search models
+
+/// What a search hit points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchKind {
+    Post,
+    User,
+    Message,
+}
+
+impl SearchKind {
+    pub const ALL: [SearchKind; 3] = [SearchKind::Post, SearchKind::User, SearchKind::Message];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchKind::Post => "post",
+            SearchKind::User => "user",
+            SearchKind::Message => "message",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == s)
+    }
+}
+
+/// Position after a hit in a `(rank, id)`-ordered result list. Like
+/// `Cursor`, clients only see the opaque string from `encode`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchCursor {
+    pub rank: f64,
+    pub id: Uuid,
+}
+
+impl SearchCursor {
+    // Rank bits and id, both hex, so the rank round-trips exactly
+    pub fn encode(&self) -> String {
+        format!("{:016x}{}", self.rank.to_bits(), self.id.simple())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let rank = f64::from_bits(u64::from_str_radix(cursor.get(..16)?, 16).ok()?);
+        let id = Uuid::try_parse(cursor.get(16..)?).ok()?;
+
+        rank.is_finite().then_some(Self { rank, id })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchRequest {
+    pub query: String,
+    pub kinds: Vec<SearchKind>,
+    pub cursor: Option<SearchCursor>,
+    pub limit: i64,
+}
+
+impl SearchRequest {
+    // Each kind fetches one extra hit to learn whether another page follows
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit.saturating_add(1)
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchParams {
+    #[validate(length(min = 1, max = 200))]
+    pub q: String,
+    /// Comma-separated kinds to search, e.g. `post,user`; all when absent
+    #[serde(rename = "type")]
+    pub kinds: Option<String>,
+    pub cursor: Option<String>,
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+}
+
+impl SearchParams {
+    pub fn search_request(&self) -> AppResult<SearchRequest> {
+        self.validate()?;
+
+        let query = self.q.trim();
+        if query.is_empty() {
+            return Err(AppError::bad_request("search.empty_query"));
+        }
+
+        let kinds = match &self.kinds {
+            Some(kinds) => {
+                let mut parsed = Vec::new();
+                for kind in kinds.split(',').map(str::trim).filter(|kind| !kind.is_empty()) {
+                    let kind = SearchKind::parse(kind).ok_or_else(|| {
+                        AppError::bad_request(
+                            ErrorMessage::new("search.unknown_type").arg("type", kind),
+                        )
+                    })?;
+                    if !parsed.contains(&kind) {
+                        parsed.push(kind);
+                    }
+                }
+                parsed
+            }
+            None => SearchKind::ALL.to_vec(),
+        };
+        if kinds.is_empty() {
+            return Err(AppError::bad_request("search.no_type"));
+        }
+
+        let cursor = match &self.cursor {
+            Some(cursor) => Some(
+                SearchCursor::decode(cursor).ok_or_else(|| AppError::bad_request("cursor.invalid"))?,
+            ),
+            None => None,
+        };
+
+        Ok(SearchRequest {
+            query: query.to_string(),
+            kinds,
+            cursor,
+            limit: self.limit.unwrap_or(CursorParams::DEFAULT_LIMIT),
+        })
+    }
+}
+
+/// One search result. `snippet` is the matching text, HTML-escaped, with
+/// the query terms wrapped in `<mark>` tags.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    pub id: Uuid,
+    /// Post title, user's display name or message sender's username
+    pub title: Option<String>,
+    pub snippet: String,
+    /// Chat a message hit was sent in
+    pub chat_id: Option<Uuid>,
+    /// Relevance between 0 and 1; higher is better
+    pub rank: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+// Internal struct for the per-kind search queries
+#[derive(Debug, sqlx::FromRow)]
+pub struct SearchHitRow {
+    pub id: Uuid,
+    pub title: Option<String>,
+    pub snippet: String,
+    pub chat_id: Option<Uuid>,
+    pub rank: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Brackets a match in a raw snippet. The databases highlight with these
+/// private-use characters rather than tags, so the text around them can be
+/// escaped before it becomes HTML.
+pub const MATCH_START: char = '\u{E000}';
+pub const MATCH_END: char = '\u{E001}';
+
+/// A raw snippet as HTML: user content escaped, matches in `<mark>` tags.
+pub fn mark_snippet(raw: &str) -> String {
+    let mut html = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+impl SearchHitRow {
+    pub fn into_hit(self, kind: SearchKind) -> SearchHit {
+        SearchHit {
+            kind,
+            id: self.id,
+            title: self.title,
+            snippet: mark_snippet(&self.snippet),
+            chat_id: self.chat_id,
+            rank: self.rank,
+            created_at: self.created_at,
+        }
+    }
+}
+
+impl CursorPage<SearchHit> {
+    /// Merges the hits each kind returned for `request` (up to
+    /// `fetch_limit()` per kind, past the cursor) into one page, best first.
+    pub fn from_hits(mut hits: Vec<SearchHit>, request: &SearchRequest) -> Self {
+        hits.sort_by(|a, b| b.rank.total_cmp(&a.rank).then_with(|| b.id.cmp(&a.id)));
+
+        let limit = usize::try_from(request.limit).unwrap_or(0);
+        let has_more = hits.len() > limit;
+        hits.truncate(limit);
+
+        let next_cursor = hits
+            .last()
+            .filter(|_| has_more)
+            .map(|hit| SearchCursor { rank: hit.rank, id: hit.id }.encode());
+
+        // Ranks aren't stable enough to page backwards; clients keep earlier pages
+        Self {
+            data: hits,
+            next_cursor,
+            prev_cursor: None,
+        }
+    }
+}
+
+// Chat models
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Chat {
     pub id: Uuid,
     pub name: Option<String>,
@@ -141,6 +782,17 @@ pub enum ChatType {
     Group,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateChat {
+    pub id: Uuid,
+    #[validate(length(min = 1, max = 100))]
+    pub name: Option<String>,
+    pub chat_type: ChatType,
+    pub created_by: Uuid,
+    // The creator is always added, whether or not they are listed here
+    pub participant_ids: Vec<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub id: Uuid,
@@ -161,6 +813,49 @@ pub enum MessageType {
     File,
 }
 
+// Internal struct for database queries
+#[derive(Debug, sqlx::FromRow)]
+pub struct MessageWithSender {
+    pub id: Uuid,
+    pub chat_id: Uuid,
+    pub sender_id: Uuid,
+    pub content: String,
+    pub message_type: MessageType,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+    pub sender_email: String,
+    pub sender_username: Option<String>,
+    pub sender_full_name: Option<String>,
+    pub sender_avatar_url: Option<String>,
+    pub sender_bio: Option<String>,
+    pub sender_created_at: DateTime<Utc>,
+    pub sender_updated_at: DateTime<Utc>,
+}
+
+impl From<MessageWithSender> for Message {
+    fn from(message: MessageWithSender) -> Self {
+        Self {
+            id: message.id,
+            chat_id: message.chat_id,
+            sender_id: message.sender_id,
+            sender: User {
+                id: message.sender_id,
+                email: message.sender_email,
+                username: message.sender_username,
+                full_name: message.sender_full_name,
+                avatar_url: message.sender_avatar_url,
+                bio: message.sender_bio,
+                created_at: message.sender_created_at,
+                updated_at: message.sender_updated_at,
+            },
+            content: message.content,
+            message_type: message.message_type,
+            metadata: message.metadata,
+            created_at: message.created_at,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateMessage {
     pub id: Uuid,
@@ -173,7 +868,7 @@ pub struct CreateMessage {
 }
 
 // Notification models
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Notification {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -198,6 +893,313 @@ pub struct CreateNotification {
     pub metadata: Option<serde_json::Value>,
 }
 
+// Outbox models
+/// A change other parts of the system may want to react to, recorded in the
+/// outbox in the same transaction as the change itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    PostCreated {
+        post_id: Uuid,
+        author_id: Uuid,
+        title: String,
+    },
+    MessageSent {
+        message_id: Uuid,
+        chat_id: Uuid,
+        sender_id: Uuid,
+        content: String,
+        created_at: DateTime<Utc>,
+    },
+    UserUpdated {
+        user_id: Uuid,
+        updated_at: DateTime<Utc>,
+    },
+    NotificationCreated {
+        notification_id: Uuid,
+        user_id: Uuid,
+        notification_type: String,
+        title: String,
+        message: String,
+        created_at: DateTime<Utc>,
+    },
+}
+
+impl DomainEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            DomainEvent::PostCreated { .. } => "post_created",
+            DomainEvent::MessageSent { .. } => "message_sent",
+            DomainEvent::UserUpdated { .. } => "user_updated",
+            DomainEvent::NotificationCreated { .. } => "notification_created",
+        }
+    }
+
+    /// Names the change rather than the delivery, so it is the same every
+    /// time the event is recorded or delivered.
+    pub fn dedup_key(&self) -> String {
+        match self {
+            DomainEvent::PostCreated { post_id, .. } => {
+                format!("{}:{}", self.event_type(), post_id)
+            }
+            DomainEvent::MessageSent { message_id, .. } => {
+                format!("{}:{}", self.event_type(), message_id)
+            }
+            DomainEvent::UserUpdated {
+                user_id,
+                updated_at,
+            } => format!(
+                "{}:{}:{}",
+                self.event_type(),
+                user_id,
+                updated_at.timestamp_micros()
+            ),
+            DomainEvent::NotificationCreated {
+                notification_id, ..
+            } => format!("{}:{}", self.event_type(), notification_id),
+        }
+    }
+}
+
+/// An outbox row as claimed by the relay; `payload` is a `DomainEvent`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OutboxRow {
+    pub id: i64,
+    pub dedup_key: String,
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OutboxRow {
+    pub fn into_event(self) -> serde_json::Result<OutboxEvent> {
+        Ok(OutboxEvent {
+            id: self.id,
+            dedup_key: self.dedup_key,
+            event: serde_json::from_value(self.payload)?,
+            created_at: self.created_at,
+        })
+    }
+}
+
+/// What consumers receive. Delivery is at least once: the same `dedup_key`
+/// can arrive more than once, and events from different transactions can
+/// arrive out of order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub dedup_key: String,
+    #[serde(flatten)]
+    pub event: DomainEvent,
+    pub created_at: DateTime<Utc>,
+}
+
+// Audit models
+/// What an audit entry records. Stored as `as_str()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Login,
+    TokenIssued,
+    UserCreated,
+    ProfileUpdated,
+    PostDeleted,
+    PostRestored,
+    CommentDeleted,
+    CountersRepaired,
+    AdminRolesChanged,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 9] = [
+        AuditAction::Login,
+        AuditAction::TokenIssued,
+        AuditAction::UserCreated,
+        AuditAction::ProfileUpdated,
+        AuditAction::PostDeleted,
+        AuditAction::PostRestored,
+        AuditAction::CommentDeleted,
+        AuditAction::CountersRepaired,
+        AuditAction::AdminRolesChanged,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "auth.login",
+            AuditAction::TokenIssued => "auth.token_issued",
+            AuditAction::UserCreated => "user.created",
+            AuditAction::ProfileUpdated => "user.profile_updated",
+            AuditAction::PostDeleted => "post.deleted",
+            AuditAction::PostRestored => "post.restored",
+            AuditAction::CommentDeleted => "comment.deleted",
+            AuditAction::CountersRepaired => "admin.counters_repaired",
+            AuditAction::AdminRolesChanged => "admin.roles_changed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.as_str() == value)
+    }
+}
+
+/// `prev_hash` of the first entry in the chain.
+pub const AUDIT_GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An entry waiting to be appended; the repository links it into the chain.
+#[derive(Debug, Clone)]
+pub struct NewAuditEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub action: AuditAction,
+    pub actor_id: Option<Uuid>,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl NewAuditEvent {
+    /// The entry after `previous`, the `(seq, hash)` of the newest entry
+    /// (`None` for an empty log), with its hash filled in.
+    pub fn link(&self, previous: Option<(i64, String)>) -> AuditEvent {
+        let (seq, prev_hash) = match previous {
+            Some((seq, hash)) => (seq + 1, hash),
+            None => (1, AUDIT_GENESIS_HASH.to_string()),
+        };
+
+        let mut event = AuditEvent {
+            id: Uuid::new_v4(),
+            seq,
+            occurred_at: self.occurred_at,
+            action: self.action.as_str().to_string(),
+            actor_id: self.actor_id,
+            target_type: self.target_type.clone(),
+            target_id: self.target_id,
+            ip: self.ip.clone(),
+            user_agent: self.user_agent.clone(),
+            request_id: self.request_id.clone(),
+            before: self.before.clone(),
+            after: self.after.clone(),
+            prev_hash,
+            hash: String::new(),
+        };
+        event.hash = event.compute_hash();
+        event
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    // Position in the hash chain, from 1
+    pub seq: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub action: String,
+    pub actor_id: Option<Uuid>,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEvent {
+    /// Hex SHA-256 of every other field, `seq` and `prev_hash` included, as
+    /// JSON with sorted keys. Timestamps are cut to milliseconds, which both
+    /// SQL backends store exactly.
+    pub fn compute_hash(&self) -> String {
+        let canonical = serde_json::json!({
+            "id": self.id,
+            "seq": self.seq,
+            "occurred_at": self.occurred_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "action": self.action,
+            "actor_id": self.actor_id,
+            "target_type": self.target_type,
+            "target_id": self.target_id,
+            "ip": self.ip,
+            "user_agent": self.user_agent,
+            "request_id": self.request_id,
+            "before": self.before,
+            "after": self.after,
+            "prev_hash": self.prev_hash,
+        });
+
+        format!("{:x}", Sha256::digest(canonical.to_string().as_bytes()))
+    }
+}
+
+/// Which audit entries to list; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        self.actor_id.is_none_or(|actor_id| event.actor_id == Some(actor_id))
+            && self.target_id.is_none_or(|target_id| event.target_id == Some(target_id))
+            && self.action.as_ref().is_none_or(|action| event.action == *action)
+            && self.since.is_none_or(|since| event.occurred_at >= since)
+            && self.until.is_none_or(|until| event.occurred_at < until)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditParams {
+    pub actor: Option<Uuid>,
+    pub target: Option<Uuid>,
+    pub action: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl AuditParams {
+    pub fn audit_query(&self) -> AppResult<(AuditFilter, PageRequest)> {
+        if let Some(action) = &self.action {
+            if AuditAction::parse(action).is_none() {
+                return Err(AppError::bad_request(
+                    ErrorMessage::new("audit.unknown_action").arg("action", action),
+                ));
+            }
+        }
+
+        let page = CursorParams {
+            cursor: self.cursor.clone(),
+            limit: self.limit,
+        }
+        .page_request()?;
+
+        let filter = AuditFilter {
+            actor_id: self.actor,
+            target_id: self.target,
+            action: self.action.clone(),
+            since: self.since,
+            until: self.until,
+        };
+        Ok((filter, page))
+    }
+}
+
+/// Result of walking the audit hash chain from the first entry.
+#[derive(Debug, Serialize)]
+pub struct AuditChainReport {
+    pub valid: bool,
+    pub checked: u64,
+    /// First entry whose hash or link to the previous entry doesn't match
+    pub first_broken_seq: Option<i64>,
+}
+
 // API Response models
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
@@ -230,6 +1232,9 @@ pub struct ErrorResponse {
     pub code: Option<String>,
     pub details: Option<serde_json::Value>,
     pub success: bool,
+    // Correlates the response with server logs and Sentry events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl ErrorResponse {
@@ -239,6 +1244,7 @@ impl ErrorResponse {
             code: None,
             details: None,
             success: false,
+            request_id: None,
         }
     }
 
@@ -248,6 +1254,7 @@ impl ErrorResponse {
             code: Some(code),
             details: None,
             success: false,
+            request_id: None,
         }
     }
 
@@ -257,6 +1264,7 @@ impl ErrorResponse {
             code: None,
             details: Some(details),
             success: false,
+            request_id: None,
         }
     }
 }
@@ -290,4 +1298,223 @@ impl PaginationMeta {
             has_prev: page > 1,
         }
     }
-}
\ No newline at end of file
+}
+
+// Keyset pagination over (created_at, id), newest first. Unlike page/limit it
+// is stable while rows are being inserted and needs no total count.
+
+/// Which way a cursor points from the row it was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorDirection {
+    /// Older rows, i.e. the next page
+    After,
+    /// Newer rows, i.e. the previous page
+    Before,
+}
+
+/// Position in a `(created_at, id)`-ordered list. Clients only ever see it
+/// as the opaque string from `encode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+    pub direction: CursorDirection,
+}
+
+impl Cursor {
+    // Direction tag, nanosecond timestamp and id, all hex
+    pub fn encode(&self) -> String {
+        let tag = match self.direction {
+            CursorDirection::After => 'a',
+            CursorDirection::Before => 'b',
+        };
+        let nanos = self.created_at.timestamp_nanos_opt().unwrap_or_default();
+        format!("{}{:016x}{}", tag, nanos, self.id.simple())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let direction = match cursor.get(..1)? {
+            "a" => CursorDirection::After,
+            "b" => CursorDirection::Before,
+            _ => return None,
+        };
+        let nanos = u64::from_str_radix(cursor.get(1..17)?, 16).ok()? as i64;
+        let id = Uuid::try_parse(cursor.get(17..)?).ok()?;
+
+        Some(Self {
+            created_at: DateTime::from_timestamp_nanos(nanos),
+            id,
+            direction,
+        })
+    }
+}
+
+/// One keyset page to fetch; the first page when `cursor` is `None`.
+#[derive(Debug, Clone, Copy)]
+pub struct PageRequest {
+    pub cursor: Option<Cursor>,
+    pub limit: i64,
+}
+
+impl PageRequest {
+    pub fn first(limit: i64) -> Self {
+        Self {
+            cursor: None,
+            limit,
+        }
+    }
+
+    // Repositories fetch one extra row to learn whether another page follows
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit.saturating_add(1)
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CursorParams {
+    pub cursor: Option<String>,
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+}
+
+impl CursorParams {
+    pub const DEFAULT_LIMIT: i64 = 20;
+
+    pub fn page_request(&self) -> AppResult<PageRequest> {
+        self.validate()?;
+
+        let cursor = match &self.cursor {
+            Some(cursor) => {
+                Some(Cursor::decode(cursor).ok_or_else(|| AppError::bad_request("cursor.invalid"))?)
+            }
+            None => None,
+        };
+
+        Ok(PageRequest {
+            cursor,
+            limit: self.limit.unwrap_or(Self::DEFAULT_LIMIT),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CursorPage<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+impl<T> CursorPage<T> {
+    /// Builds the page from the rows a repository fetched for `request`:
+    /// up to `fetch_limit()` of them, nearest to the cursor first.
+    pub fn from_rows(
+        mut rows: Vec<T>,
+        request: &PageRequest,
+        key: impl Fn(&T) -> (DateTime<Utc>, Uuid),
+    ) -> Self {
+        let limit = usize::try_from(request.limit).unwrap_or(0);
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+
+        let direction = request.cursor.map(|cursor| cursor.direction);
+        if direction == Some(CursorDirection::Before) {
+            rows.reverse();
+        }
+
+        // Coming from one side means there is at least one row on that side
+        let (older, newer) = match direction {
+            None => (has_more, false),
+            Some(CursorDirection::After) => (has_more, true),
+            Some(CursorDirection::Before) => (true, has_more),
+        };
+
+        let cursor = |row: &T, direction| {
+            let (created_at, id) = key(row);
+            Cursor {
+                created_at,
+                id,
+                direction,
+            }
+            .encode()
+        };
+
+        Self {
+            next_cursor: rows
+                .last()
+                .filter(|_| older)
+                .map(|row| cursor(row, CursorDirection::After)),
+            prev_cursor: rows
+                .first()
+                .filter(|_| newer)
+                .map(|row| cursor(row, CursorDirection::Before)),
+            data: rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(direction: CursorDirection) -> Cursor {
+        Cursor {
+            created_at: Utc::now(),
+            id: Uuid::new_v4(),
+            direction,
+        }
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        for direction in [CursorDirection::After, CursorDirection::Before] {
+            let cursor = cursor(direction);
+            assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let encoded = cursor(CursorDirection::After).encode();
+
+        for malformed in [
+            String::new(),
+            format!("c{}", &encoded[1..]),
+            encoded[..20].to_string(),
+            format!("{}0", encoded),
+            encoded.replacen('a', "a-", 1),
+        ] {
+            assert_eq!(Cursor::decode(&malformed), None, "{}", malformed);
+
+            let params = CursorParams {
+                cursor: Some(malformed),
+                limit: None,
+            };
+            assert!(matches!(
+                params.page_request(),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn page_limits_are_bounded() {
+        for limit in [0, 101] {
+            let params = CursorParams {
+                cursor: None,
+                limit: Some(limit),
+            };
+            assert!(matches!(
+                params.page_request(),
+                Err(AppError::Validation(_))
+            ));
+        }
+
+        let params = CursorParams {
+            cursor: None,
+            limit: None,
+        };
+        let page = params.page_request().unwrap();
+        assert_eq!(page.limit, CursorParams::DEFAULT_LIMIT);
+        assert!(page.cursor.is_none());
+    }
+}
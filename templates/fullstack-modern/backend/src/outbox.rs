@@ -0,0 +1,360 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use redis::{
+    aio::ConnectionManager,
+    streams::{StreamMaxlen, StreamRangeReply, StreamReadOptions, StreamReadReply},
+    AsyncCommands,
+};
+use tokio::sync::broadcast;
+
+use crate::{
+    config::{Config, EventSink},
+    models::OutboxEvent,
+    repository::Repository,
+};
+
+// Transactional outbox. Repository writes record a `DomainEvent` in the
+// `outbox` table in the same transaction as the change itself; the relay
+// claims undelivered events, publishes them on the `EventBus` and marks them
+// published. An event is published again if the relay stops or publishing
+// fails before it is marked, so delivery is at least once and consumers drop
+// repeats with `Dedup`. Any number of instances can relay at once.
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const BATCH_SIZE: i64 = 100;
+
+// A claimed batch is handed to another relay if not published within this long
+const LEASE: Duration = Duration::from_secs(30);
+
+// Published events are kept this long for inspection, then purged
+const RETENTION_HOURS: i64 = 72;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Redis stream the "redis" sink publishes to. Entries carry `type`,
+/// `dedup_key` and `event` (the `OutboxEvent` as JSON).
+pub const STREAM: &str = "events";
+
+// The stream is trimmed to roughly this many entries
+const STREAM_MAX_LEN: usize = 100_000;
+
+// How long one XREAD waits for new entries
+const STREAM_BLOCK_MS: usize = 5_000;
+
+// Subscribers this far behind skip ahead, missing events
+const BUS_CAPACITY: usize = 1024;
+
+/// Delivers relayed events to subscribers in this process. With
+/// `event_sink = "redis"` events go through the Redis stream first, so every
+/// instance sees every event whichever instance relayed it.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<OutboxEvent>,
+    stream: Option<ConnectionManager>,
+}
+
+impl EventBus {
+    pub async fn connect(config: &Config) -> anyhow::Result<Self> {
+        let (sender, _) = broadcast::channel(BUS_CAPACITY);
+
+        let stream = match config.event_sink {
+            EventSink::Memory => None,
+            EventSink::Redis => {
+                let client = redis::Client::open(config.redis_url.expose())?;
+                // Blocking reads get a connection of their own
+                spawn_stream_reader(client.get_connection_manager().await?, sender.clone());
+                Some(client.get_connection_manager().await?)
+            }
+        };
+
+        Ok(Self { sender, stream })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OutboxEvent> {
+        self.sender.subscribe()
+    }
+
+    async fn publish(&self, event: &OutboxEvent) -> anyhow::Result<()> {
+        match &self.stream {
+            Some(redis) => {
+                let json = serde_json::to_string(event)?;
+                let fields = [
+                    ("type", event.event.event_type()),
+                    ("dedup_key", event.dedup_key.as_str()),
+                    ("event", json.as_str()),
+                ];
+                let _: String = redis
+                    .clone()
+                    .xadd_maxlen(STREAM, StreamMaxlen::Approx(STREAM_MAX_LEN), "*", &fields)
+                    .await?;
+            }
+            None => {
+                // Fails only when nobody is subscribed, which is fine
+                let _ = self.sender.send(event.clone());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Forwards entries added to the stream from now on to local subscribers
+fn spawn_stream_reader(mut redis: ConnectionManager, sender: broadcast::Sender<OutboxEvent>) {
+    tokio::spawn(async move {
+        // Start after the newest entry rather than at "$", which would skip
+        // whatever arrives between two reads
+        let newest: redis::RedisResult<StreamRangeReply> =
+            redis.xrevrange_count(STREAM, "+", "-", 1).await;
+        let mut last_id = newest
+            .ok()
+            .and_then(|reply| reply.ids.into_iter().next())
+            .map_or_else(|| "0-0".to_string(), |entry| entry.id);
+
+        let options = StreamReadOptions::default()
+            .block(STREAM_BLOCK_MS)
+            .count(BATCH_SIZE as usize);
+
+        loop {
+            let reply: redis::RedisResult<StreamReadReply> =
+                redis.xread_options(&[STREAM], &[&last_id], &options).await;
+
+            let reply = match reply {
+                Ok(reply) => reply,
+                Err(e) => {
+                    tracing::warn!("Failed to read the event stream: {}", e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            for entry in reply.keys.into_iter().flat_map(|key| key.ids) {
+                let event = entry
+                    .get::<String>("event")
+                    .and_then(|json| serde_json::from_str::<OutboxEvent>(&json).ok());
+                match event {
+                    Some(event) => {
+                        let _ = sender.send(event);
+                    }
+                    None => tracing::warn!("Skipping malformed event stream entry {}", entry.id),
+                }
+                last_id = entry.id;
+            }
+        }
+    });
+}
+
+/// Remembers the last `capacity` keys seen, e.g. the dedup keys of events a
+/// consumer handled, so it can drop redeliveries.
+pub struct Dedup {
+    seen: HashSet<String>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Dedup {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            seen: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// False if `key` was already seen.
+    pub fn first_time(&mut self, key: &str) -> bool {
+        if self.seen.contains(key) {
+            return false;
+        }
+
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen.insert(key.to_string());
+        self.order.push_back(key.to_string());
+        true
+    }
+}
+
+// Publishes one claimed batch in order, stopping at the first failure.
+// Returns how many events were claimed.
+async fn relay_batch(repository: &dyn Repository, bus: &EventBus) -> anyhow::Result<usize> {
+    let rows = repository.claim_outbox_events(BATCH_SIZE, LEASE).await?;
+    let claimed = rows.len();
+
+    let mut published = Vec::with_capacity(claimed);
+    let mut failure = None;
+    for row in rows {
+        let id = row.id;
+        match row.into_event() {
+            Ok(event) => {
+                if let Err(e) = bus.publish(&event).await {
+                    // The rest goes out again once the lease runs out
+                    failure = Some(e);
+                    break;
+                }
+            }
+            // Retrying won't make this build able to read it
+            Err(e) => tracing::error!("Dropping unreadable outbox event {}: {}", id, e),
+        }
+        published.push(id);
+    }
+
+    if !published.is_empty() {
+        repository.mark_outbox_published(&published).await?;
+    }
+
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(claimed),
+    }
+}
+
+/// Relays new outbox events every `POLL_INTERVAL`, batch after batch while
+/// there is a backlog.
+pub fn spawn_relay(repository: Arc<dyn Repository>, bus: EventBus) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match relay_batch(repository.as_ref(), &bus).await {
+                    Ok(claimed) if claimed == BATCH_SIZE as usize => {}
+                    Ok(_) => break,
+                    Err(e) => {
+                        tracing::error!("Failed to relay outbox events: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Deletes events published more than `RETENTION_HOURS` ago, hourly.
+pub fn spawn_purge(repository: Arc<dyn Repository>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let cutoff = Utc::now() - chrono::Duration::hours(RETENTION_HOURS);
+            match repository.purge_outbox(cutoff).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} published outbox events", purged),
+                Err(e) => tracing::error!("Failed to purge the outbox: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::DomainEvent,
+        repository::{memory::InMemoryRepository, OutboxRepository},
+        testing::{self, create_post, create_user},
+    };
+
+    #[test]
+    fn dedup_forgets_the_oldest_key() {
+        let mut dedup = Dedup::new(2);
+
+        assert!(dedup.first_time("a"));
+        assert!(dedup.first_time("b"));
+        assert!(!dedup.first_time("a"));
+
+        // "a" makes way for "c", so it's new again
+        assert!(dedup.first_time("c"));
+        assert!(dedup.first_time("a"));
+        assert!(!dedup.first_time("c"));
+    }
+
+    #[tokio::test]
+    async fn relayed_events_are_published_once() {
+        let repository = InMemoryRepository::new();
+        let author = create_user(&repository, "author").await;
+        let post_id = create_post(&repository, &author, "Outbox").await;
+        let bus = EventBus::connect(&testing::config()).await.unwrap();
+        let mut events = bus.subscribe();
+
+        assert_eq!(relay_batch(&repository, &bus).await.unwrap(), 1);
+        assert_eq!(relay_batch(&repository, &bus).await.unwrap(), 0);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.dedup_key, format!("post_created:{}", post_id));
+        assert!(matches!(
+            event.event,
+            DomainEvent::PostCreated { author_id, .. } if author_id == author.id
+        ));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn claimed_events_wait_out_their_lease() {
+        let repository = InMemoryRepository::new();
+        let author = create_user(&repository, "author").await;
+        create_post(&repository, &author, "Leased").await;
+
+        let claimed = repository.claim_outbox_events(10, LEASE).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert!(repository
+            .claim_outbox_events(10, LEASE)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // A relay that never marks its batch leaves it to the next one
+        let repository = InMemoryRepository::new();
+        let author = create_user(&repository, "author").await;
+        create_post(&repository, &author, "Abandoned").await;
+
+        repository
+            .claim_outbox_events(10, Duration::ZERO)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(
+            repository
+                .claim_outbox_events(10, LEASE)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn only_published_events_are_purged() {
+        let repository = InMemoryRepository::new();
+        let author = create_user(&repository, "author").await;
+        create_post(&repository, &author, "Published").await;
+        let published = repository.claim_outbox_events(10, LEASE).await.unwrap();
+        repository
+            .mark_outbox_published(&[published[0].id])
+            .await
+            .unwrap();
+        create_post(&repository, &author, "Pending").await;
+
+        let later = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(repository.purge_outbox(later).await.unwrap(), 1);
+        assert_eq!(
+            repository
+                .claim_outbox_events(10, LEASE)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
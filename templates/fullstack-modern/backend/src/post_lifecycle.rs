@@ -0,0 +1,693 @@
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use axum::{
+    extract::{Path, Query, State},
+    routing::{delete, get, patch, post},
+    Json,
+};
+use chrono::{Duration, Utc};
+use similar::TextDiff;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    auth::{AuthUser, AuthUserWithRole, Role},
+    error::{AppError, AppResult},
+    feed::HomeFeed,
+    models::{
+        ApiResponse, AuditAction, CreatePost, Post, PostDiff, PostDiffParams, PostRevision,
+        PostStatus, UpdatePost,
+    },
+    repository::Repository,
+    routing::Route,
+    runtime::{ReloadableSettings, RuntimeSettings},
+    services::Services,
+};
+
+// How often soft-deleted posts past their retention are purged
+const PURGE_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+
+// Edit, revision, delete, restore and purge rules for posts. Every edit keeps
+// the replaced title and content as a numbered revision. Deletes are soft: the
+// post disappears from every read, its author or an admin can restore it
+// within `post_restore_window_hours`, and the purge task removes it for good
+// after `post_retention_days`.
+
+// Settings are unsigned; anything chrono can't represent is effectively forever
+fn duration(value: u64, unit: fn(i64) -> Option<Duration>) -> Duration {
+    i64::try_from(value).ok().and_then(unit).unwrap_or(Duration::MAX)
+}
+
+/// How long after deletion `restore_post` still succeeds.
+pub fn restore_window(settings: &ReloadableSettings) -> Duration {
+    duration(settings.post_restore_window_hours, Duration::try_hours)
+}
+
+/// How long deleted posts are kept before `purge_deleted_posts` removes them.
+pub fn retention(settings: &ReloadableSettings) -> Duration {
+    duration(settings.post_retention_days, Duration::try_days)
+}
+
+fn not_found() -> AppError {
+    AppError::not_found("post.not_found")
+}
+
+fn can_moderate(status: &PostStatus, user_id: &Uuid, role: &Role) -> bool {
+    status.author_id == *user_id || *role == Role::Admin
+}
+
+async fn live_status(repository: &dyn Repository, post_id: &Uuid) -> AppResult<PostStatus> {
+    repository
+        .get_post_status(post_id)
+        .await?
+        .filter(|status| status.deleted_at.is_none())
+        .ok_or_else(not_found)
+}
+
+/// Publishes a new post and pushes it into its readers' home feeds.
+pub async fn create_post(
+    repository: &dyn Repository,
+    feed: &HomeFeed,
+    post: &CreatePost,
+) -> AppResult<Post> {
+    post.validate()?;
+
+    let post_id = repository.create_post(post).await?;
+    let post = repository
+        .get_post_by_id(&post_id, Some(&post.author_id))
+        .await?
+        .ok_or_else(not_found)?;
+
+    feed.publish(repository, &post).await;
+    Ok(post)
+}
+
+/// Applies `updates` to a post; only its author may edit it.
+pub async fn update_post(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    post_id: &Uuid,
+    updates: &UpdatePost,
+) -> AppResult<Post> {
+    updates.validate()?;
+
+    let status = live_status(repository, post_id).await?;
+    if status.author_id != *user_id {
+        return Err(AppError::forbidden("Only the author can edit this post"));
+    }
+
+    // False when the post was deleted since the check above
+    if !repository.update_post(post_id, user_id, updates).await? {
+        return Err(not_found());
+    }
+
+    repository
+        .get_post_by_id(post_id, Some(user_id))
+        .await?
+        .ok_or_else(not_found)
+}
+
+/// A post's revisions, newest first.
+pub async fn list_revisions(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+) -> AppResult<Vec<PostRevision>> {
+    live_status(repository, post_id).await?;
+
+    Ok(repository.get_post_revisions(post_id).await?)
+}
+
+async fn revision(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+    revision: i32,
+) -> AppResult<PostRevision> {
+    repository
+        .get_post_revision(post_id, revision)
+        .await?
+        .ok_or_else(|| AppError::not_found("revision.not_found"))
+}
+
+// A revision, or the post as it is now, as diffable text with the title on
+// the first line
+async fn version_text(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+    version: Option<i32>,
+) -> AppResult<String> {
+    let (title, content) = match version {
+        Some(number) => {
+            let revision = revision(repository, post_id, number).await?;
+            (revision.title, revision.content)
+        }
+        None => {
+            let post = repository
+                .get_post_by_id(post_id, None)
+                .await?
+                .ok_or_else(not_found)?;
+            (post.title, post.content)
+        }
+    };
+
+    let mut text = format!("{}\n\n{}", title, content);
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+fn version_label(version: Option<i32>) -> String {
+    match version {
+        Some(number) => format!("revision {}", number),
+        None => "current".to_string(),
+    }
+}
+
+/// Unified diff between two versions of a post, each a revision number or
+/// `None` for the current title and content.
+pub async fn diff_revisions(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+    from: Option<i32>,
+    to: Option<i32>,
+) -> AppResult<PostDiff> {
+    live_status(repository, post_id).await?;
+
+    let old = version_text(repository, post_id, from).await?;
+    let new = version_text(repository, post_id, to).await?;
+    let diff = TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .header(&version_label(from), &version_label(to))
+        .to_string();
+
+    Ok(PostDiff {
+        post_id: *post_id,
+        from,
+        to,
+        diff,
+    })
+}
+
+/// Makes a revision's title and content current again. This is an edit like
+/// any other, so it records a revision of its own and can be reverted too.
+pub async fn revert_post(
+    repository: &dyn Repository,
+    user_id: &Uuid,
+    post_id: &Uuid,
+    revision_number: i32,
+) -> AppResult<Post> {
+    live_status(repository, post_id).await?;
+    let revision = revision(repository, post_id, revision_number).await?;
+
+    let updates = UpdatePost {
+        title: Some(revision.title),
+        content: Some(revision.content),
+    };
+    update_post(repository, user_id, post_id, &updates).await
+}
+
+/// Soft-deletes a post on behalf of its author or an admin.
+pub async fn delete_post(
+    repository: &dyn Repository,
+    audit: &AuditLog,
+    user_id: &Uuid,
+    role: &Role,
+    post_id: &Uuid,
+) -> AppResult<()> {
+    let status = live_status(repository, post_id).await?;
+    if !can_moderate(&status, user_id, role) {
+        return Err(AppError::forbidden("Only the author or an admin can delete this post"));
+    }
+
+    let post = repository.get_post_by_id(post_id, None).await?;
+    if !repository.delete_post(post_id).await? {
+        return Err(not_found());
+    }
+
+    let mut entry = AuditEntry::new(AuditAction::PostDeleted)
+        .actor(*user_id)
+        .target("post", *post_id);
+    if let Some(post) = &post {
+        entry = entry.before(post);
+    }
+    audit.record(entry).await;
+
+    Ok(())
+}
+
+/// Undoes `delete_post` if the restore window hasn't closed yet.
+pub async fn restore_post(
+    repository: &dyn Repository,
+    audit: &AuditLog,
+    user_id: &Uuid,
+    role: &Role,
+    post_id: &Uuid,
+    window: Duration,
+) -> AppResult<Post> {
+    let status = repository
+        .get_post_status(post_id)
+        .await?
+        .ok_or_else(not_found)?;
+    if !can_moderate(&status, user_id, role) {
+        return Err(AppError::forbidden("Only the author or an admin can restore this post"));
+    }
+
+    let Some(deleted_at) = status.deleted_at else {
+        return Err(AppError::conflict("post.not_deleted"));
+    };
+    if Utc::now() - deleted_at > window {
+        return Err(AppError::conflict("post.restore_window_closed"));
+    }
+
+    // False when another request restored it first, which is fine (and
+    // already recorded)
+    let restored = repository.restore_post(post_id).await?;
+
+    let post = repository
+        .get_post_by_id(post_id, Some(user_id))
+        .await?
+        .ok_or_else(not_found)?;
+
+    if restored {
+        let entry = AuditEntry::new(AuditAction::PostRestored)
+            .actor(*user_id)
+            .target("post", *post_id)
+            .after(&post);
+        audit.record(entry).await;
+    }
+
+    Ok(post)
+}
+
+/// Permanently removes posts that were soft-deleted more than `retention` ago.
+pub async fn purge_deleted_posts(
+    repository: &dyn Repository,
+    retention: Duration,
+) -> anyhow::Result<u64> {
+    // A retention reaching back further than chrono can represent keeps everything
+    let Some(cutoff) = Utc::now().checked_sub_signed(retention) else {
+        return Ok(0);
+    };
+
+    repository.purge_deleted_posts(cutoff).await
+}
+
+/// Runs `purge_deleted_posts` every `PURGE_INTERVAL`, picking up reloads of
+/// `post_retention_days`.
+pub fn spawn_purge(repository: Arc<dyn Repository>, runtime: RuntimeSettings) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let retention = retention(&runtime.current());
+            match purge_deleted_posts(repository.as_ref(), retention).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} deleted posts", purged),
+                Err(e) => tracing::error!("Failed to purge deleted posts: {}", e),
+            }
+        }
+    });
+}
+
+// Editing, deleting and restoring posts and their revisions, mounted under
+// `/api/posts`
+pub fn routes() -> Vec<Route> {
+    vec![
+        ("PATCH", "/:id", patch(edit)),
+        ("DELETE", "/:id", delete(remove)),
+        ("POST", "/:id/restore", post(restore)),
+        ("GET", "/:id/revisions", get(revisions)),
+        ("GET", "/:id/revisions/diff", get(diff)),
+        ("POST", "/:id/revisions/:revision/revert", post(revert)),
+    ]
+}
+
+async fn edit(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path(post_id): Path<Uuid>,
+    Json(updates): Json<UpdatePost>,
+) -> AppResult<Json<ApiResponse<Post>>> {
+    let post = update_post(
+        services.repository.as_ref(),
+        &user.user_id,
+        &post_id,
+        &updates,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(post)))
+}
+
+async fn remove(
+    State(services): State<Services>,
+    user: AuthUserWithRole,
+    Path(post_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<()>>> {
+    delete_post(
+        services.repository.as_ref(),
+        &services.audit,
+        &user.user.user_id,
+        &user.role,
+        &post_id,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// Fails with 409 once `post_restore_window_hours` have passed since the delete.
+async fn restore(
+    State(services): State<Services>,
+    user: AuthUserWithRole,
+    Path(post_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Post>>> {
+    let window = restore_window(&services.runtime.current());
+    let post = restore_post(
+        services.repository.as_ref(),
+        &services.audit,
+        &user.user.user_id,
+        &user.role,
+        &post_id,
+        window,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(post)))
+}
+
+async fn revisions(
+    State(services): State<Services>,
+    _user: AuthUser,
+    Path(post_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Vec<PostRevision>>>> {
+    let revisions = list_revisions(services.repository.as_ref(), &post_id).await?;
+
+    Ok(Json(ApiResponse::success(revisions)))
+}
+
+async fn diff(
+    State(services): State<Services>,
+    _user: AuthUser,
+    Path(post_id): Path<Uuid>,
+    Query(params): Query<PostDiffParams>,
+) -> AppResult<Json<ApiResponse<PostDiff>>> {
+    let diff = diff_revisions(
+        services.repository.as_ref(),
+        &post_id,
+        params.from,
+        params.to,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(diff)))
+}
+
+async fn revert(
+    State(services): State<Services>,
+    user: AuthUser,
+    Path((post_id, revision)): Path<(Uuid, i32)>,
+) -> AppResult<Json<ApiResponse<Post>>> {
+    let post = revert_post(
+        services.repository.as_ref(),
+        &user.user_id,
+        &post_id,
+        revision,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(post)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use super::*;
+    use crate::{
+        repository::{memory::InMemoryRepository, PostRepository},
+        testing::{self, create_post, create_user},
+    };
+
+    fn audit(repository: &InMemoryRepository) -> AuditLog {
+        AuditLog::new(Arc::new(repository.clone()))
+    }
+
+    fn retitle(title: &str) -> UpdatePost {
+        UpdatePost {
+            title: Some(title.to_string()),
+            content: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_author_edits_a_post() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        let post_id = create_post(&repository, &alice, "Draft").await;
+
+        let error = update_post(&repository, &bob.id, &post_id, &retitle("Hijacked"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::Forbidden(_)));
+
+        let post = update_post(&repository, &alice.id, &post_id, &retitle("Final"))
+            .await
+            .unwrap();
+        assert_eq!(post.title, "Final");
+        assert_eq!(post.content, "Draft content");
+    }
+
+    #[tokio::test]
+    async fn deleted_posts_are_restored_by_their_author_or_an_admin() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/posts", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        let admin = create_user(&repository, "admin").await;
+        let post_id = create_post(&repository, &alice, "Oops").await;
+        let post_uri = format!("/api/posts/{}", post_id);
+        let restore_uri = format!("/api/posts/{}/restore", post_id);
+
+        let (status, _) = testing::send(&router, Method::DELETE, &post_uri, Some(&bob), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) =
+            testing::send(&router, Method::DELETE, &post_uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(repository
+            .get_post_by_id(&post_id, None)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Already deleted
+        let (status, _) =
+            testing::send(&router, Method::DELETE, &post_uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) =
+            testing::send(&router, Method::POST, &restore_uri, Some(&bob), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) =
+            testing::send(&router, Method::POST, &restore_uri, Some(&admin), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["title"], "Oops");
+
+        let (status, _) =
+            testing::send(&router, Method::POST, &restore_uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn restoring_fails_once_the_window_closes() {
+        let repository = InMemoryRepository::new();
+        let audit = audit(&repository);
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "Gone").await;
+
+        delete_post(&repository, &audit, &alice.id, &Role::User, &post_id)
+            .await
+            .unwrap();
+        tokio::time::sleep(StdDuration::from_millis(5)).await;
+
+        let window = Duration::milliseconds(1);
+        let error = restore_post(
+            &repository,
+            &audit,
+            &alice.id,
+            &Role::User,
+            &post_id,
+            window,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, AppError::Conflict(_)));
+        assert!(repository
+            .get_post_by_id(&post_id, None)
+            .await
+            .unwrap()
+            .is_none());
+
+        let window = Duration::hours(1);
+        restore_post(
+            &repository,
+            &audit,
+            &alice.id,
+            &Role::User,
+            &post_id,
+            window,
+        )
+        .await
+        .unwrap();
+        assert!(repository
+            .get_post_by_id(&post_id, None)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn purging_waits_out_the_retention() {
+        let repository = InMemoryRepository::new();
+        let audit = audit(&repository);
+        let alice = create_user(&repository, "alice").await;
+        let deleted = create_post(&repository, &alice, "Deleted").await;
+        let kept = create_post(&repository, &alice, "Kept").await;
+
+        delete_post(&repository, &audit, &alice.id, &Role::User, &deleted)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            purge_deleted_posts(&repository, Duration::days(30))
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(repository
+            .get_post_status(&deleted)
+            .await
+            .unwrap()
+            .is_some());
+
+        tokio::time::sleep(StdDuration::from_millis(5)).await;
+        assert_eq!(
+            purge_deleted_posts(&repository, Duration::zero())
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(repository
+            .get_post_status(&deleted)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repository.get_post_status(&kept).await.unwrap().is_some());
+
+        // A retention reaching back further than chrono can represent keeps everything
+        assert_eq!(
+            purge_deleted_posts(&repository, Duration::MAX)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn edits_keep_numbered_revisions() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "First").await;
+
+        for title in ["Second", "Third"] {
+            update_post(&repository, &alice.id, &post_id, &retitle(title))
+                .await
+                .unwrap();
+        }
+
+        let revisions = list_revisions(&repository, &post_id).await.unwrap();
+        let numbered: Vec<(i32, &str)> = revisions
+            .iter()
+            .map(|revision| (revision.revision, revision.title.as_str()))
+            .collect();
+        assert_eq!(numbered, [(2, "Second"), (1, "First")]);
+
+        let diff = diff_revisions(&repository, &post_id, Some(1), None)
+            .await
+            .unwrap();
+        assert!(diff.diff.contains("--- revision 1\n+++ current\n"));
+        assert!(diff.diff.contains("\n-First\n+Third\n"));
+    }
+
+    #[tokio::test]
+    async fn edits_that_change_nothing_record_no_revision() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "First").await;
+        let before = repository
+            .get_post_by_id(&post_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let after = update_post(&repository, &alice.id, &post_id, &retitle("First"))
+            .await
+            .unwrap();
+
+        assert_eq!(after.updated_at, before.updated_at);
+        assert!(list_revisions(&repository, &post_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn reverting_is_an_edit_of_its_own() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/posts", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        let post_id = create_post(&repository, &alice, "Original").await;
+        update_post(&repository, &alice.id, &post_id, &retitle("Edited"))
+            .await
+            .unwrap();
+        let revert_uri =
+            |revision: i32| format!("/api/posts/{}/revisions/{}/revert", post_id, revision);
+
+        let (status, _) =
+            testing::send(&router, Method::POST, &revert_uri(1), Some(&bob), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) =
+            testing::send(&router, Method::POST, &revert_uri(7), Some(&alice), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) =
+            testing::send(&router, Method::POST, &revert_uri(1), Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["title"], "Original");
+
+        // The revert itself can be reverted
+        let revisions_uri = format!("/api/posts/{}/revisions", post_id);
+        let (status, body) =
+            testing::send(&router, Method::GET, &revisions_uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["revision"], 2);
+        assert_eq!(body["data"][0]["title"], "Edited");
+
+        let (status, body) =
+            testing::send(&router, Method::POST, &revert_uri(2), Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["title"], "Edited");
+    }
+}
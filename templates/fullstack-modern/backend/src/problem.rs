@@ -0,0 +1,227 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+
+use crate::{error::ErrorParts, services::Services};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+// RFC 7807 Problem Details body
+#[derive(Debug, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    // Extension members
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ProblemDetails {
+    pub fn from_parts(parts: &ErrorParts, type_base: &str, instance: Option<String>) -> Self {
+        Self {
+            problem_type: problem_type_uri(type_base, parts.code),
+            title: parts
+                .status
+                .canonical_reason()
+                .unwrap_or("Error")
+                .to_string(),
+            status: parts.status.as_u16(),
+            detail: parts.message.clone(),
+            instance,
+            code: parts.code.to_string(),
+            errors: parts.details.clone(),
+            request_id: parts.request_id.clone(),
+        }
+    }
+}
+
+// e.g. ("/errors", "VALIDATION_ERROR") -> "/errors/validation-error"
+pub fn problem_type_uri(type_base: &str, code: &str) -> String {
+    format!(
+        "{}/{}",
+        type_base.trim_end_matches('/'),
+        code.to_lowercase().replace('_', "-")
+    )
+}
+
+fn accepts_problem_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| {
+            media_type
+                .split(';')
+                .next()
+                .map(str::trim)
+                .is_some_and(|media_type| media_type.eq_ignore_ascii_case(PROBLEM_JSON))
+        })
+}
+
+/// Re-renders `AppError` responses as `application/problem+json` when the
+/// client asks for it via `Accept` or `problem_details` is enabled in config.
+pub async fn problem_details(
+    State(services): State<Services>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let settings = services.runtime.current();
+    let wants_problem = settings.problem_details || accepts_problem_json(request.headers());
+    let instance = request.uri().path().to_string();
+
+    let response = next.run(request).await;
+    if !wants_problem {
+        return response;
+    }
+
+    let Some(error) = response.extensions().get::<ErrorParts>().cloned() else {
+        return response;
+    };
+
+    let problem = ProblemDetails::from_parts(&error, &settings.problem_type_base, Some(instance));
+    let body = match serde_json::to_vec(&problem) {
+        Ok(body) => body,
+        Err(_) => return response,
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body,
+        http::{Request, StatusCode},
+        middleware::from_fn_with_state,
+        routing::get,
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        config::Config, error::AppError, repository::memory::InMemoryRepository,
+        runtime::RuntimeSettings, testing,
+    };
+
+    async fn router(always: bool) -> Router {
+        let mut services = testing::services(&InMemoryRepository::new()).await;
+        let config = Config {
+            problem_details: always,
+            problem_type_base: "https://api.example/errors/".to_string(),
+            ..testing::config()
+        };
+        services.runtime = RuntimeSettings::new(&config, None).unwrap();
+
+        Router::new()
+            .route(
+                "/posts/missing",
+                get(|| async { Err::<(), _>(AppError::not_found("post.not_found")) }),
+            )
+            .route("/ok", get(|| async { "fine" }))
+            .layer(from_fn_with_state(services.clone(), problem_details))
+            .with_state(services)
+    }
+
+    async fn get_with(router: &Router, uri: &str, accept: &str) -> (StatusCode, String, Value) {
+        let request = Request::builder()
+            .uri(uri)
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (
+            status,
+            content_type,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[test]
+    fn type_uris_are_built_from_the_code() {
+        assert_eq!(
+            problem_type_uri("/errors/", "VALIDATION_ERROR"),
+            "/errors/validation-error"
+        );
+    }
+
+    #[test]
+    fn problem_json_is_found_among_accepted_types() {
+        let accepts = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+            accepts_problem_json(&headers)
+        };
+
+        assert!(accepts("text/html, Application/Problem+JSON; q=0.9"));
+        assert!(!accepts("application/json"));
+        assert!(!accepts("application/problem+jsonp"));
+    }
+
+    #[tokio::test]
+    async fn errors_are_rendered_as_problems_when_asked() {
+        let router = router(false).await;
+
+        let (status, content_type, problem) =
+            get_with(&router, "/posts/missing", PROBLEM_JSON).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, PROBLEM_JSON);
+        assert_eq!(problem["type"], "https://api.example/errors/not-found");
+        assert_eq!(problem["title"], "Not Found");
+        assert_eq!(problem["status"], 404);
+        assert_eq!(problem["detail"], "Post not found");
+        assert_eq!(problem["instance"], "/posts/missing");
+        assert_eq!(problem["code"], "NOT_FOUND");
+
+        let (status, content_type, error) =
+            get_with(&router, "/posts/missing", "application/json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, "application/json");
+        assert_eq!(error["success"], false);
+        assert_eq!(error["code"], "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn config_can_make_problems_the_default() {
+        let router = router(true).await;
+
+        let (_, content_type, _) = get_with(&router, "/posts/missing", "*/*").await;
+        assert_eq!(content_type, PROBLEM_JSON);
+
+        // Successful responses pass through untouched
+        let (status, content_type, _) = get_with(&router, "/ok", PROBLEM_JSON).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/plain"));
+    }
+}
@@ -0,0 +1,37 @@
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    error::{AppError, AppResult},
+    models::{AuditAction, UpdateUser, User},
+    repository::Repository,
+};
+
+// Profile edits. Users only ever edit their own profile; every change is
+// recorded in the audit log with the profile as it was and as it is now.
+
+/// Applies `updates` to `user_id`'s profile.
+pub async fn update_profile(
+    repository: &dyn Repository,
+    audit: &AuditLog,
+    user_id: &Uuid,
+    updates: &UpdateUser,
+) -> AppResult<User> {
+    updates.validate()?;
+
+    let before = repository
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("user.not_found"))?;
+    let user = repository.update_user(user_id, updates).await?;
+
+    let entry = AuditEntry::new(AuditAction::ProfileUpdated)
+        .actor(*user_id)
+        .target("user", *user_id)
+        .before(&before)
+        .after(&user);
+    audit.record(entry).await;
+
+    Ok(user)
+}
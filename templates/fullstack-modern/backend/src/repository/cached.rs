@@ -0,0 +1,935 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use super::{
+    AuditRepository, ChatRepository, CommentRepository, FollowRepository,
+    NotificationRepository, OutboxRepository, PostRepository, Repository, SearchRepository,
+    TransactionRepository, UnitOfWork, UserRepository,
+};
+use crate::{
+    config::Config,
+    database::Isolation,
+    models::{
+        AuditEvent, AuditFilter, Chat, Comment, CommentTree, CommentTreeRequest, CreateChat,
+        CreateComment, CreateMessage, CreateNotification, CreatePost, CreateUser, CursorPage,
+        FeedEntry, FollowEdge, FollowKind, FollowUser, Message, NewAuditEvent, Notification,
+        OutboxRow, PageRequest, Post, PostLiker, PostRevision, PostStatus, SearchHit,
+        SearchRequest, UpdatePost, UpdateUser, User,
+    },
+};
+
+// Read-through Redis cache in front of another repository for
+// `get_user_by_id` and `get_post_by_id`. Users are cached as `user:{id}`.
+// Posts carry the viewer's `is_liked`, so each post is a hash `post:{id}`
+// with one field per viewer (`-` when anonymous) and one deletion drops
+// every variant. Writes made through this repository invalidate what they
+// change, counters included, and writes in a transaction once it commits;
+// anything else (cascades from deleting a user,
+// an author's new name on their posts, `repair-counters`) shows up once the
+// TTL runs out. Concurrent misses on a key in this process share one
+// database read, and TTLs are jittered so hot keys don't all expire at once.
+// Redis failures are logged and fall through to the database.
+
+// Extra random share of the TTL added to each entry
+const TTL_JITTER: f64 = 0.1;
+
+// Sets a viewer's copy of a post, starting the hash's TTL only when the
+// first copy is stored so repeat views don't keep stale copies alive
+const SET_POST: &str = r"
+redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+if redis.call('TTL', KEYS[1]) < 0 then
+    redis.call('EXPIRE', KEYS[1], ARGV[3])
+end
+return 0
+";
+
+fn user_key(user_id: &Uuid) -> String {
+    format!("user:{}", user_id)
+}
+
+fn post_key(post_id: &Uuid) -> String {
+    format!("post:{}", post_id)
+}
+
+fn viewer_field(user_id: Option<&Uuid>) -> String {
+    user_id.map_or_else(|| "-".to_string(), Uuid::to_string)
+}
+
+#[derive(Debug, Default)]
+struct Counter {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Counter {
+    fn stats(&self) -> CounterStats {
+        CounterStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Hit and miss counts since startup, shared with `/api/meta/cache`.
+#[derive(Debug, Default)]
+pub struct CacheMetrics {
+    users: Counter,
+    posts: Counter,
+    errors: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CounterStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CacheStats {
+    pub users: CounterStats,
+    pub posts: CounterStats,
+    /// Redis calls that failed and fell through to the database
+    pub errors: u64,
+}
+
+impl CacheMetrics {
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            users: self.users.stats(),
+            posts: self.posts.stats(),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Where an entry lives: a plain key, or a field of a hash
+struct Entry {
+    key: String,
+    field: Option<String>,
+    ttl: u64,
+}
+
+enum Lookup<T> {
+    Hit(T),
+    Miss,
+    // Redis is unreachable; don't queue behind other loads for it
+    Unavailable,
+}
+
+pub struct CachedRepository {
+    inner: Arc<dyn Repository>,
+    redis: ConnectionManager,
+    user_ttl: u64,
+    post_ttl: u64,
+    metrics: Arc<CacheMetrics>,
+    // Keys being loaded from the database right now
+    flights: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    // Inside a transaction: the keys its writes touched, invalidated once
+    // it commits. Reads skip the cache there.
+    touched: Option<Arc<Mutex<HashSet<String>>>>,
+}
+
+impl CachedRepository {
+    /// Wraps `inner` when either TTL is set and Redis is reachable;
+    /// otherwise returns it untouched, without metrics.
+    pub async fn wrap(
+        inner: Arc<dyn Repository>,
+        config: &Config,
+    ) -> (Arc<dyn Repository>, Option<Arc<CacheMetrics>>) {
+        if config.cache_user_ttl_secs == 0 && config.cache_post_ttl_secs == 0 {
+            return (inner, None);
+        }
+
+        let redis = match connect(config).await {
+            Ok(redis) => redis,
+            Err(e) => {
+                tracing::warn!("Redis unavailable, running without the user and post cache: {}", e);
+                return (inner, None);
+            }
+        };
+
+        let metrics = Arc::new(CacheMetrics::default());
+        let cached = Self {
+            inner,
+            redis,
+            user_ttl: config.cache_user_ttl_secs,
+            post_ttl: config.cache_post_ttl_secs,
+            metrics: metrics.clone(),
+            flights: Mutex::new(HashMap::new()),
+            touched: None,
+        };
+
+        (Arc::new(cached), Some(metrics))
+    }
+
+    // This cache in front of `tx`, recording what its writes touch in `touched`
+    fn within(&self, tx: Arc<dyn Repository>, touched: Arc<Mutex<HashSet<String>>>) -> Self {
+        Self {
+            inner: tx,
+            redis: self.redis.clone(),
+            user_ttl: self.user_ttl,
+            post_ttl: self.post_ttl,
+            metrics: self.metrics.clone(),
+            flights: Mutex::new(HashMap::new()),
+            touched: Some(touched),
+        }
+    }
+
+    fn failed(&self, e: redis::RedisError) {
+        self.metrics.errors.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!("Cache error: {}", e);
+    }
+
+    async fn lookup<T: DeserializeOwned>(&self, entry: &Entry) -> Lookup<T> {
+        let mut redis = self.redis.clone();
+        let cached: redis::RedisResult<Option<String>> = match &entry.field {
+            Some(field) => redis.hget(&entry.key, field).await,
+            None => redis.get(&entry.key).await,
+        };
+
+        match cached {
+            Ok(Some(json)) => match serde_json::from_str(&json) {
+                Ok(value) => Lookup::Hit(value),
+                // Written by an older build with a different shape
+                Err(_) => Lookup::Miss,
+            },
+            Ok(None) => Lookup::Miss,
+            Err(e) => {
+                self.failed(e);
+                Lookup::Unavailable
+            }
+        }
+    }
+
+    async fn store<T: Serialize>(&self, entry: &Entry, value: &T) {
+        let Ok(json) = serde_json::to_string(value) else {
+            return;
+        };
+        let jitter = rand::thread_rng().gen_range(0.0..=TTL_JITTER);
+        let ttl = entry.ttl + (entry.ttl as f64 * jitter) as u64;
+
+        let mut redis = self.redis.clone();
+        let stored: redis::RedisResult<()> = match &entry.field {
+            Some(field) => {
+                Script::new(SET_POST)
+                    .key(&entry.key)
+                    .arg(field)
+                    .arg(json)
+                    .arg(ttl)
+                    .invoke_async(&mut redis)
+                    .await
+            }
+            None => redis.set_ex(&entry.key, json, ttl).await,
+        };
+
+        if let Err(e) = stored {
+            self.failed(e);
+        }
+    }
+
+    async fn invalidate(&self, key: String) {
+        if let Some(touched) = &self.touched {
+            touched.lock().unwrap_or_else(PoisonError::into_inner).insert(key);
+            return;
+        }
+
+        let deleted: redis::RedisResult<()> = self.redis.clone().del(key).await;
+        if let Err(e) = deleted {
+            self.failed(e);
+        }
+    }
+
+    // Shared by every caller loading `key` at the same time
+    fn flight(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut flights = self.flights.lock().unwrap_or_else(PoisonError::into_inner);
+        flights.entry(key.to_string()).or_default().clone()
+    }
+
+    fn land(&self, key: &str) {
+        let mut flights = self.flights.lock().unwrap_or_else(PoisonError::into_inner);
+        flights.remove(key);
+    }
+
+    async fn read_through<T, F, Fut>(
+        &self,
+        entry: Entry,
+        counter: &Counter,
+        load: F,
+    ) -> anyhow::Result<Option<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<Option<T>>>,
+    {
+        // Uncommitted reads neither come from nor go to the cache
+        if self.touched.is_some() {
+            return load().await;
+        }
+
+        match self.lookup(&entry).await {
+            Lookup::Hit(value) => {
+                counter.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Some(value));
+            }
+            Lookup::Unavailable => {
+                counter.misses.fetch_add(1, Ordering::Relaxed);
+                return load().await;
+            }
+            Lookup::Miss => {}
+        }
+
+        // Whoever gets here first loads; the rest wait and find it cached
+        let flight = self.flight(&entry.key);
+        let _loading = flight.lock().await;
+        if let Lookup::Hit(value) = self.lookup(&entry).await {
+            counter.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(value));
+        }
+
+        counter.misses.fetch_add(1, Ordering::Relaxed);
+        let value = load().await;
+        if let Ok(Some(value)) = &value {
+            self.store(&entry, value).await;
+        }
+        self.land(&entry.key);
+        value
+    }
+}
+
+async fn connect(config: &Config) -> redis::RedisResult<ConnectionManager> {
+    redis::Client::open(config.redis_url.expose())?
+        .get_connection_manager()
+        .await
+}
+
+#[async_trait]
+impl UserRepository for CachedRepository {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> anyhow::Result<Option<User>> {
+        if self.user_ttl == 0 {
+            return self.inner.get_user_by_id(user_id).await;
+        }
+
+        let entry = Entry {
+            key: user_key(user_id),
+            field: None,
+            ttl: self.user_ttl,
+        };
+        self.read_through(entry, &self.metrics.users, || self.inner.get_user_by_id(user_id))
+            .await
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> anyhow::Result<Option<User>> {
+        self.inner.get_user_by_email(email).await
+    }
+
+    async fn create_user(&self, user: &CreateUser) -> anyhow::Result<User> {
+        self.inner.create_user(user).await
+    }
+
+    async fn update_user(&self, user_id: &Uuid, updates: &UpdateUser) -> anyhow::Result<User> {
+        let user = self.inner.update_user(user_id, updates).await?;
+        self.invalidate(user_key(user_id)).await;
+        Ok(user)
+    }
+
+    async fn delete_user(&self, user_id: &Uuid) -> anyhow::Result<bool> {
+        let deleted = self.inner.delete_user(user_id).await?;
+        self.invalidate(user_key(user_id)).await;
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl PostRepository for CachedRepository {
+    async fn get_posts(
+        &self,
+        limit: i64,
+        offset: i64,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>> {
+        self.inner.get_posts(limit, offset, user_id).await
+    }
+
+    async fn get_posts_page(
+        &self,
+        page: &PageRequest,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        self.inner.get_posts_page(page, user_id).await
+    }
+
+    async fn get_post_by_id(
+        &self,
+        post_id: &Uuid,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Option<Post>> {
+        if self.post_ttl == 0 {
+            return self.inner.get_post_by_id(post_id, user_id).await;
+        }
+
+        let entry = Entry {
+            key: post_key(post_id),
+            field: Some(viewer_field(user_id)),
+            ttl: self.post_ttl,
+        };
+        self.read_through(entry, &self.metrics.posts, || {
+            self.inner.get_post_by_id(post_id, user_id)
+        })
+        .await
+    }
+
+    async fn get_posts_by_ids(
+        &self,
+        post_ids: &[Uuid],
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>> {
+        self.inner.get_posts_by_ids(post_ids, user_id).await
+    }
+
+    async fn create_post(&self, post: &CreatePost) -> anyhow::Result<Uuid> {
+        self.inner.create_post(post).await
+    }
+
+    async fn get_post_status(&self, post_id: &Uuid) -> anyhow::Result<Option<PostStatus>> {
+        self.inner.get_post_status(post_id).await
+    }
+
+    async fn update_post(
+        &self,
+        post_id: &Uuid,
+        editor_id: &Uuid,
+        updates: &UpdatePost,
+    ) -> anyhow::Result<bool> {
+        let updated = self.inner.update_post(post_id, editor_id, updates).await?;
+        self.invalidate(post_key(post_id)).await;
+        Ok(updated)
+    }
+
+    async fn get_post_revisions(&self, post_id: &Uuid) -> anyhow::Result<Vec<PostRevision>> {
+        self.inner.get_post_revisions(post_id).await
+    }
+
+    async fn get_post_revision(
+        &self,
+        post_id: &Uuid,
+        revision: i32,
+    ) -> anyhow::Result<Option<PostRevision>> {
+        self.inner.get_post_revision(post_id, revision).await
+    }
+
+    async fn delete_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let deleted = self.inner.delete_post(post_id).await?;
+        self.invalidate(post_key(post_id)).await;
+        Ok(deleted)
+    }
+
+    async fn restore_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let restored = self.inner.restore_post(post_id).await?;
+        self.invalidate(post_key(post_id)).await;
+        Ok(restored)
+    }
+
+    async fn purge_deleted_posts(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        self.inner.purge_deleted_posts(cutoff).await
+    }
+
+    async fn like_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let liked = self.inner.like_post(post_id, user_id).await?;
+        self.invalidate(post_key(post_id)).await;
+        Ok(liked)
+    }
+
+    async fn unlike_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let unliked = self.inner.unlike_post(post_id, user_id).await?;
+        self.invalidate(post_key(post_id)).await;
+        Ok(unliked)
+    }
+
+    async fn get_post_likers(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<PostLiker>> {
+        self.inner.get_post_likers(post_id, page).await
+    }
+
+    async fn recount_post_counters(&self) -> anyhow::Result<u64> {
+        self.inner.recount_post_counters().await
+    }
+}
+
+#[async_trait]
+impl CommentRepository for CachedRepository {
+    async fn get_comments_page(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Comment>> {
+        self.inner.get_comments_page(post_id, page).await
+    }
+
+    async fn get_comment_tree(
+        &self,
+        post_id: &Uuid,
+        request: &CommentTreeRequest,
+    ) -> anyhow::Result<CommentTree> {
+        self.inner.get_comment_tree(post_id, request).await
+    }
+
+    async fn get_comment(&self, comment_id: &Uuid) -> anyhow::Result<Option<Comment>> {
+        self.inner.get_comment(comment_id).await
+    }
+
+    async fn create_comment(&self, comment: &CreateComment) -> anyhow::Result<Comment> {
+        let created = self.inner.create_comment(comment).await?;
+        self.invalidate(post_key(&comment.post_id)).await;
+        Ok(created)
+    }
+
+    async fn update_comment(&self, comment_id: &Uuid, content: &str) -> anyhow::Result<bool> {
+        self.inner.update_comment(comment_id, content).await
+    }
+
+    async fn delete_comment(&self, comment_id: &Uuid) -> anyhow::Result<bool> {
+        // The post whose `comments_count` is about to change
+        let post_id = self.inner.get_comment(comment_id).await?.map(|c| c.post_id);
+
+        let deleted = self.inner.delete_comment(comment_id).await?;
+        if let Some(post_id) = post_id {
+            self.invalidate(post_key(&post_id)).await;
+        }
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl FollowRepository for CachedRepository {
+    async fn add_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        self.inner.add_follow(user_id, target_id, kind).await
+    }
+
+    async fn remove_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        self.inner.remove_follow(user_id, target_id, kind).await
+    }
+
+    async fn get_follow_edges(
+        &self,
+        user_id: &Uuid,
+        other_id: &Uuid,
+    ) -> anyhow::Result<Vec<FollowEdge>> {
+        self.inner.get_follow_edges(user_id, other_id).await
+    }
+
+    async fn get_followers_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        self.inner.get_followers_page(user_id, page).await
+    }
+
+    async fn get_following_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        self.inner.get_following_page(user_id, page).await
+    }
+
+    async fn get_feed_subscribers(&self, author_id: &Uuid) -> anyhow::Result<Vec<Uuid>> {
+        self.inner.get_feed_subscribers(author_id).await
+    }
+
+    async fn get_home_feed_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        self.inner.get_home_feed_page(user_id, page).await
+    }
+
+    async fn get_home_feed_entries(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+    ) -> anyhow::Result<Vec<FeedEntry>> {
+        self.inner.get_home_feed_entries(user_id, limit).await
+    }
+}
+
+#[async_trait]
+impl ChatRepository for CachedRepository {
+    async fn create_chat(&self, chat: &CreateChat) -> anyhow::Result<Chat> {
+        self.inner.create_chat(chat).await
+    }
+
+    async fn get_chat(&self, chat_id: &Uuid) -> anyhow::Result<Option<Chat>> {
+        self.inner.get_chat(chat_id).await
+    }
+
+    async fn get_user_chats(&self, user_id: &Uuid) -> anyhow::Result<Vec<Chat>> {
+        self.inner.get_user_chats(user_id).await
+    }
+
+    async fn is_chat_participant(&self, chat_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        self.inner.is_chat_participant(chat_id, user_id).await
+    }
+
+    async fn create_message(&self, message: &CreateMessage) -> anyhow::Result<Message> {
+        self.inner.create_message(message).await
+    }
+
+    async fn get_messages(
+        &self,
+        chat_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Message>> {
+        self.inner.get_messages(chat_id, limit, offset).await
+    }
+
+    async fn get_messages_page(
+        &self,
+        chat_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Message>> {
+        self.inner.get_messages_page(chat_id, page).await
+    }
+}
+
+#[async_trait]
+impl NotificationRepository for CachedRepository {
+    async fn create_notification(
+        &self,
+        notification: &CreateNotification,
+    ) -> anyhow::Result<Notification> {
+        self.inner.create_notification(notification).await
+    }
+
+    async fn get_notifications(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Notification>> {
+        self.inner.get_notifications(user_id, limit, offset).await
+    }
+
+    async fn get_notifications_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Notification>> {
+        self.inner.get_notifications_page(user_id, page).await
+    }
+
+    async fn mark_notification_read(
+        &self,
+        notification_id: &Uuid,
+        user_id: &Uuid,
+    ) -> anyhow::Result<bool> {
+        self.inner.mark_notification_read(notification_id, user_id).await
+    }
+}
+
+#[async_trait]
+impl SearchRepository for CachedRepository {
+    async fn search(
+        &self,
+        user_id: &Uuid,
+        request: &SearchRequest,
+    ) -> anyhow::Result<CursorPage<SearchHit>> {
+        self.inner.search(user_id, request).await
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for CachedRepository {
+    async fn claim_outbox_events(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> anyhow::Result<Vec<OutboxRow>> {
+        self.inner.claim_outbox_events(limit, lease).await
+    }
+
+    async fn mark_outbox_published(&self, event_ids: &[i64]) -> anyhow::Result<()> {
+        self.inner.mark_outbox_published(event_ids).await
+    }
+
+    async fn purge_outbox(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        self.inner.purge_outbox(cutoff).await
+    }
+}
+
+#[async_trait]
+impl AuditRepository for CachedRepository {
+    async fn append_audit_event(&self, event: &NewAuditEvent) -> anyhow::Result<AuditEvent> {
+        self.inner.append_audit_event(event).await
+    }
+
+    async fn get_audit_events_page(
+        &self,
+        filter: &AuditFilter,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<AuditEvent>> {
+        self.inner.get_audit_events_page(filter, page).await
+    }
+
+    async fn get_audit_chain(&self, after_seq: i64, limit: i64) -> anyhow::Result<Vec<AuditEvent>> {
+        self.inner.get_audit_chain(after_seq, limit).await
+    }
+}
+
+// Work in a transaction reads through to the inner repository's
+// transaction: it sees its own uncommitted writes rather than cached copies,
+// and nothing it reads reaches the cache. The users and posts it writes are
+// invalidated once it commits; a nested transaction leaves that to the
+// outermost one. Keys touched by retried or rolled-back attempts are
+// invalidated too, which costs a miss at most.
+#[async_trait]
+impl TransactionRepository for CachedRepository {
+    async fn transaction_with(
+        &self,
+        isolation: Isolation,
+        work: UnitOfWork<'_>,
+    ) -> anyhow::Result<()> {
+        let outermost = self.touched.is_none();
+        let touched = self.touched.clone().unwrap_or_default();
+
+        let recorded = touched.clone();
+        let work = &work;
+        self.inner
+            .transaction_with(
+                isolation,
+                Box::new(move |tx| work(Arc::new(self.within(tx, recorded.clone())))),
+            )
+            .await?;
+
+        if outermost {
+            let keys = std::mem::take(&mut *touched.lock().unwrap_or_else(PoisonError::into_inner));
+            for key in keys {
+                self.invalidate(key).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Secret,
+        repository::memory::InMemoryRepository,
+        testing::{self, create_post, create_user},
+    };
+
+    // Nothing listens on port 1, so connecting is refused straight away
+    const UNREACHABLE: &str = "redis://127.0.0.1:1";
+
+    // A cache in front of `repository`, on the Redis server at
+    // `TEST_REDIS_URL`. Tests that need one pass when it is unset.
+    async fn cached(
+        repository: &InMemoryRepository,
+    ) -> Option<(Arc<dyn Repository>, Arc<CacheMetrics>)> {
+        let Ok(url) = std::env::var("TEST_REDIS_URL") else {
+            eprintln!("TEST_REDIS_URL is unset, skipping");
+            return None;
+        };
+        let config = Config {
+            redis_url: Secret::new(url),
+            cache_user_ttl_secs: 60,
+            cache_post_ttl_secs: 60,
+            ..testing::config()
+        };
+
+        let (wrapped, metrics) =
+            CachedRepository::wrap(Arc::new(repository.clone()), &config).await;
+        Some((wrapped, metrics.expect("TEST_REDIS_URL is reachable")))
+    }
+
+    fn rename(full_name: &str) -> UpdateUser {
+        UpdateUser {
+            username: None,
+            full_name: Some(full_name.to_string()),
+            avatar_url: None,
+            bio: None,
+        }
+    }
+
+    fn retitle(title: &str) -> UpdatePost {
+        UpdatePost {
+            title: Some(title.to_string()),
+            content: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn no_ttls_means_no_cache() {
+        let repository = InMemoryRepository::new();
+        let user = create_user(&repository, "uncached").await;
+
+        let (wrapped, metrics) =
+            CachedRepository::wrap(Arc::new(repository), &testing::config()).await;
+
+        assert!(metrics.is_none());
+        assert!(wrapped.get_user_by_id(&user.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn unreachable_redis_falls_back_to_the_database() {
+        let repository = InMemoryRepository::new();
+        let user = create_user(&repository, "uncached").await;
+        let config = Config {
+            redis_url: Secret::new(UNREACHABLE),
+            cache_user_ttl_secs: 60,
+            cache_post_ttl_secs: 60,
+            ..testing::config()
+        };
+
+        let (wrapped, metrics) = CachedRepository::wrap(Arc::new(repository), &config).await;
+
+        assert!(metrics.is_none());
+        assert!(wrapped.get_user_by_id(&user.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn a_miss_is_cached_and_then_hit() {
+        let repository = InMemoryRepository::new();
+        let Some((wrapped, metrics)) = cached(&repository).await else {
+            return;
+        };
+        let user = create_user(&repository, "cached").await;
+
+        let first = wrapped.get_user_by_id(&user.id).await.unwrap().unwrap();
+        assert_eq!(first.full_name, None);
+
+        // Changed behind the cache's back, so only a hit still has the old copy
+        repository
+            .update_user(&user.id, &rename("Behind"))
+            .await
+            .unwrap();
+        let second = wrapped.get_user_by_id(&user.id).await.unwrap().unwrap();
+        assert_eq!(second.full_name, None);
+
+        let stats = metrics.stats().users;
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn writes_invalidate_what_they_change() {
+        let repository = InMemoryRepository::new();
+        let Some((wrapped, metrics)) = cached(&repository).await else {
+            return;
+        };
+        let user = create_user(&repository, "writer").await;
+        let post_id = create_post(&repository, &user, "Before").await;
+
+        wrapped.get_user_by_id(&user.id).await.unwrap();
+        wrapped.get_post_by_id(&post_id, None).await.unwrap();
+        wrapped
+            .update_user(&user.id, &rename("After"))
+            .await
+            .unwrap();
+        wrapped
+            .update_post(&post_id, &user.id, &retitle("After"))
+            .await
+            .unwrap();
+
+        let user = wrapped.get_user_by_id(&user.id).await.unwrap().unwrap();
+        assert_eq!(user.full_name.as_deref(), Some("After"));
+        let post = wrapped
+            .get_post_by_id(&post_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(post.title, "After");
+        assert_eq!(metrics.stats().posts.misses, 2);
+    }
+
+    #[tokio::test]
+    async fn transactions_invalidate_what_they_wrote_once_committed() {
+        let repository = InMemoryRepository::new();
+        let Some((wrapped, _)) = cached(&repository).await else {
+            return;
+        };
+        let user = create_user(&repository, "transacting").await;
+        let post_id = create_post(&repository, &user, "Before").await;
+        wrapped.get_post_by_id(&post_id, None).await.unwrap();
+
+        wrapped
+            .transaction(|tx| {
+                let user_id = user.id;
+                Box::pin(async move {
+                    tx.update_post(&post_id, &user_id, &retitle("After"))
+                        .await?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap();
+
+        let post = wrapped
+            .get_post_by_id(&post_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(post.title, "After");
+
+        testing::committed_work_is_kept(wrapped.as_ref()).await;
+        testing::failed_work_is_rolled_back(wrapped.as_ref()).await;
+        testing::nested_work_rolls_back_on_its_own(wrapped.as_ref()).await;
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_share_one_load() {
+        let repository = InMemoryRepository::new();
+        let Some((wrapped, metrics)) = cached(&repository).await else {
+            return;
+        };
+        let user = create_user(&repository, "popular").await;
+
+        let reads = (0..10).map(|_| wrapped.get_user_by_id(&user.id));
+        for read in futures::future::join_all(reads).await {
+            assert!(read.unwrap().is_some());
+        }
+
+        // One read loaded from the repository; the rest waited for it
+        let stats = metrics.stats().users;
+        assert_eq!((stats.hits, stats.misses), (9, 1));
+    }
+
+    #[test]
+    fn posts_keep_one_field_per_viewer() {
+        let (post_id, viewer_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(post_key(&post_id), format!("post:{}", post_id));
+        assert_eq!(viewer_field(Some(&viewer_id)), viewer_id.to_string());
+        assert_eq!(viewer_field(None), "-");
+    }
+}
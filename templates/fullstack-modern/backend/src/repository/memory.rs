@@ -0,0 +1,1957 @@
+use std::{
+    cmp::Reverse,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::Duration,
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::{
+    AuditRepository, ChatRepository, CommentRepository, FollowRepository,
+    NotificationRepository, OutboxRepository, PostRepository, SearchRepository,
+    TransactionRepository, UnitOfWork, UserRepository,
+};
+use crate::{
+    database::Isolation,
+    error::{ConstraintKind, ConstraintViolation},
+    models::{
+        mark_snippet, AuditEvent, AuditFilter, Chat, Comment, CommentSort, CommentTree,
+        CommentTreeRequest, CommentTreeRow, CreateChat, CreateComment, CreateMessage,
+        CreateNotification, CreatePost, CreateUser, CursorDirection, CursorPage, DomainEvent,
+        FeedEntry, FollowEdge, FollowKind, FollowUser, Message, MessageType, NewAuditEvent,
+        Notification, OutboxRow, PageRequest, Post, PostLiker, PostRevision, PostStatus, SearchHit,
+        SearchKind, SearchRequest, UpdatePost, UpdateUser, User, MATCH_END, MATCH_START,
+    },
+};
+
+/// Process-local stand-in for `Database`. Enforces the same primary, unique
+/// and foreign keys as the Postgres schema (reporting them under the same
+/// constraint names) and cascades deletes the same way. Clones share state.
+#[derive(Default, Clone)]
+pub struct InMemoryRepository {
+    state: Arc<RwLock<State>>,
+}
+
+// Rows are kept in insertion order, which doubles as created_at order
+#[derive(Default, Clone)]
+struct State {
+    users: Vec<User>,
+    posts: Vec<PostRecord>,
+    likes: Vec<LikeRecord>,
+    comments: Vec<CommentRecord>,
+    follows: Vec<FollowRecord>,
+    chats: Vec<ChatRecord>,
+    participants: Vec<ParticipantRecord>,
+    messages: Vec<MessageRecord>,
+    notifications: Vec<Notification>,
+    outbox: Vec<OutboxRecord>,
+    // In chain order
+    audit_events: Vec<AuditEvent>,
+}
+
+#[derive(Clone)]
+struct PostRecord {
+    id: Uuid,
+    title: String,
+    content: String,
+    author_id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+    // Oldest first
+    revisions: Vec<PostRevision>,
+}
+
+#[derive(Clone)]
+struct LikeRecord {
+    post_id: Uuid,
+    user_id: Uuid,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+struct CommentRecord {
+    id: Uuid,
+    post_id: Uuid,
+    parent_id: Option<Uuid>,
+    author_id: Uuid,
+    content: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+struct FollowRecord {
+    edge: FollowEdge,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+struct ChatRecord {
+    chat: Chat,
+    created_by: Uuid,
+}
+
+#[derive(Clone)]
+struct ParticipantRecord {
+    chat_id: Uuid,
+    user_id: Uuid,
+    left_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+struct MessageRecord {
+    id: Uuid,
+    chat_id: Uuid,
+    sender_id: Uuid,
+    content: String,
+    message_type: MessageType,
+    metadata: Option<serde_json::Value>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+struct OutboxRecord {
+    row: OutboxRow,
+    locked_until: Option<DateTime<Utc>>,
+    published_at: Option<DateTime<Utc>>,
+}
+
+// Same names Postgres gives the constraints in migrations/0001_initial.up.sql
+fn primary_key_violation(table: &str) -> anyhow::Error {
+    ConstraintViolation {
+        kind: ConstraintKind::Unique,
+        table: Some(table.to_string()),
+        constraint: Some(format!("{}_pkey", table)),
+        field: None,
+    }
+    .into()
+}
+
+fn unique_violation(table: &str, column: &str) -> anyhow::Error {
+    ConstraintViolation {
+        kind: ConstraintKind::Unique,
+        table: Some(table.to_string()),
+        constraint: Some(format!("{}_{}_key", table, column)),
+        field: Some(column.to_string()),
+    }
+    .into()
+}
+
+fn foreign_key_violation(table: &str, column: &str) -> anyhow::Error {
+    ConstraintViolation {
+        kind: ConstraintKind::ForeignKey,
+        table: Some(table.to_string()),
+        constraint: Some(format!("{}_{}_fkey", table, column)),
+        field: Some(column.to_string()),
+    }
+    .into()
+}
+
+// LIMIT/OFFSET arguments; negative values select nothing / skip nothing
+fn window(limit: i64, offset: i64) -> (usize, usize) {
+    (
+        usize::try_from(limit).unwrap_or(0),
+        usize::try_from(offset).unwrap_or(0),
+    )
+}
+
+// The rows a keyset query would fetch for `page`, nearest to the cursor first
+fn keyset<'a, T>(
+    rows: impl Iterator<Item = &'a T>,
+    page: &PageRequest,
+    key: impl Fn(&T) -> (DateTime<Utc>, Uuid),
+) -> Vec<&'a T> {
+    let mut rows: Vec<&T> = match page.cursor {
+        Some(cursor) => {
+            let bound = (cursor.created_at, cursor.id);
+            rows.filter(|row| match cursor.direction {
+                CursorDirection::After => key(row) < bound,
+                CursorDirection::Before => key(row) > bound,
+            })
+            .collect()
+        }
+        None => rows.collect(),
+    };
+
+    rows.sort_by_key(|row| key(row));
+    if page.cursor.map(|cursor| cursor.direction) != Some(CursorDirection::Before) {
+        rows.reverse();
+    }
+    rows.truncate(usize::try_from(page.fetch_limit()).unwrap_or(0));
+    rows
+}
+
+// Crude stand-in for the SQL backends' full-text ranking: every word has to
+// occur in `text` (ignoring case), and more occurrences rank higher, on the
+// same 0..1 scale
+fn text_rank(text: &str, words: &[String]) -> Option<f64> {
+    let text = text.to_lowercase();
+    let mut occurrences = 0;
+    for word in words {
+        match text.matches(word.as_str()).count() {
+            0 => return None,
+            n => occurrences += n,
+        }
+    }
+
+    let occurrences = occurrences as f64;
+    Some(occurrences / (occurrences + 1.0))
+}
+
+// `text`, escaped, with each occurrence of `words` wrapped in `<mark>`
+fn highlight(text: &str, words: &[String]) -> String {
+    let lower = text.to_lowercase();
+    // Lowercasing changed byte offsets; leave such text unmarked
+    if lower.len() != text.len() {
+        return mark_snippet(text);
+    }
+
+    let mut marked = vec![false; text.len()];
+    for word in words {
+        for (start, _) in lower.match_indices(word.as_str()) {
+            marked[start..start + word.len()].fill(true);
+        }
+    }
+
+    let mut snippet = String::with_capacity(text.len());
+    let mut open = false;
+    for (i, c) in text.char_indices() {
+        if marked[i] != open {
+            snippet.push(if open { MATCH_END } else { MATCH_START });
+            open = marked[i];
+        }
+        snippet.push(c);
+    }
+    if open {
+        snippet.push(MATCH_END);
+    }
+    mark_snippet(&snippet)
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, State> {
+        self.state.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    // Like `ON CONFLICT (dedup_key) DO NOTHING`
+    fn record_event(&mut self, event: &DomainEvent) {
+        let dedup_key = event.dedup_key();
+        if self.outbox.iter().any(|record| record.row.dedup_key == dedup_key) {
+            return;
+        }
+
+        let id = self.outbox.last().map_or(1, |record| record.row.id + 1);
+        self.outbox.push(OutboxRecord {
+            row: OutboxRow {
+                id,
+                dedup_key,
+                payload: serde_json::to_value(event).unwrap_or_default(),
+                created_at: Utc::now(),
+            },
+            locked_until: None,
+            published_at: None,
+        });
+    }
+
+    fn user(&self, user_id: &Uuid) -> Option<&User> {
+        self.users.iter().find(|user| user.id == *user_id)
+    }
+
+    fn username_taken(&self, username: Option<&str>, except: Option<&Uuid>) -> bool {
+        // NULL usernames never collide, as in Postgres
+        let Some(username) = username else {
+            return false;
+        };
+        self.users.iter().any(|user| {
+            user.username.as_deref() == Some(username) && Some(&user.id) != except
+        })
+    }
+
+    fn has_follow(&self, user_id: &Uuid, target_id: &Uuid, kind: FollowKind) -> bool {
+        self.follows.iter().any(|record| {
+            record.edge.user_id == *user_id
+                && record.edge.target_id == *target_id
+                && record.edge.kind == kind
+        })
+    }
+
+    // Whether `record` belongs in `user_id`'s home feed
+    fn in_home_feed(&self, user_id: &Uuid, record: &PostRecord) -> bool {
+        record.deleted_at.is_none()
+            && (record.author_id == *user_id
+                || (self.has_follow(user_id, &record.author_id, FollowKind::Follow)
+                    && !self.has_follow(user_id, &record.author_id, FollowKind::Mute)))
+    }
+
+    // Users on one side of `user_id`'s follows, with when each began
+    fn follow_users(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+        followers: bool,
+    ) -> CursorPage<FollowUser> {
+        let edges = self.follows.iter().filter_map(|record| {
+            let edge = &record.edge;
+            if edge.kind != FollowKind::Follow {
+                return None;
+            }
+            match followers {
+                true if edge.target_id == *user_id => Some((record.created_at, edge.user_id)),
+                false if edge.user_id == *user_id => Some((record.created_at, edge.target_id)),
+                _ => None,
+            }
+        });
+        let edges: Vec<(DateTime<Utc>, Uuid)> = edges.collect();
+
+        let users = keyset(edges.iter(), page, |edge| *edge)
+            .into_iter()
+            .filter_map(|(followed_at, other_id)| {
+                Some(FollowUser {
+                    user: self.user(other_id)?.clone().into(),
+                    followed_at: *followed_at,
+                })
+            })
+            .collect();
+
+        CursorPage::from_rows(users, page, |user| (user.followed_at, user.user.id))
+    }
+
+    fn chat_exists(&self, chat_id: &Uuid) -> bool {
+        self.chats.iter().any(|record| record.chat.id == *chat_id)
+    }
+
+    // Soft-deleted posts read as missing; `viewer` fills in `is_liked`. The
+    // counters are counted on every read, so they never drift
+    fn post(&self, record: &PostRecord, viewer: Option<&Uuid>) -> Option<Post> {
+        if record.deleted_at.is_some() {
+            return None;
+        }
+
+        Some(Post {
+            id: record.id,
+            title: record.title.clone(),
+            content: record.content.clone(),
+            author_id: record.author_id,
+            author: self.user(&record.author_id)?.clone(),
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            likes_count: self
+                .likes
+                .iter()
+                .filter(|like| like.post_id == record.id)
+                .count() as i64,
+            comments_count: self
+                .comments
+                .iter()
+                .filter(|comment| comment.post_id == record.id)
+                .count() as i64,
+            is_liked: viewer.is_some_and(|viewer| {
+                self.likes
+                    .iter()
+                    .any(|like| like.post_id == record.id && like.user_id == *viewer)
+            }),
+        })
+    }
+
+    fn comment(&self, record: &CommentRecord) -> Option<Comment> {
+        Some(Comment {
+            id: record.id,
+            post_id: record.post_id,
+            parent_id: record.parent_id,
+            author_id: record.author_id,
+            author: self.user(&record.author_id)?.clone().into(),
+            content: record.content.clone(),
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        })
+    }
+
+    fn reply_count(&self, comment_id: &Uuid) -> i64 {
+        self.comments
+            .iter()
+            .filter(|record| record.parent_id == Some(*comment_id))
+            .count() as i64
+    }
+
+    // `parent_id`'s replies on `post_id` in `sort` order, each with its reply count
+    fn sorted_replies(
+        &self,
+        post_id: &Uuid,
+        parent_id: Option<Uuid>,
+        sort: CommentSort,
+    ) -> Vec<(&CommentRecord, i64)> {
+        let mut replies: Vec<(&CommentRecord, i64)> = self
+            .comments
+            .iter()
+            .filter(|record| record.post_id == *post_id && record.parent_id == parent_id)
+            .map(|record| (record, self.reply_count(&record.id)))
+            .collect();
+
+        replies.sort_by(|(a, a_replies), (b, b_replies)| {
+            let newest = (b.created_at, b.id).cmp(&(a.created_at, a.id));
+            match sort {
+                CommentSort::Newest => newest,
+                CommentSort::Oldest => newest.reverse(),
+                CommentSort::Top => b_replies.cmp(a_replies).then(newest),
+            }
+        });
+        replies
+    }
+
+    // The rows the SQL backends' recursive query returns for `request`
+    fn comment_tree_rows(
+        &self,
+        post_id: &Uuid,
+        request: &CommentTreeRequest,
+        parent_id: Option<Uuid>,
+        depth: i32,
+        rows: &mut Vec<CommentTreeRow>,
+    ) {
+        let replies = self.sorted_replies(post_id, parent_id, request.sort);
+        let sibling_count = replies.len() as i64;
+        let (limit, offset) = if depth == 1 {
+            window(request.limit, request.offset)
+        } else {
+            window(request.replies_limit, 0)
+        };
+
+        for (index, (record, reply_count)) in
+            replies.into_iter().enumerate().skip(offset).take(limit)
+        {
+            let Some(author) = self.user(&record.author_id) else {
+                continue;
+            };
+
+            rows.push(CommentTreeRow {
+                id: record.id,
+                post_id: record.post_id,
+                parent_id: record.parent_id,
+                author_id: record.author_id,
+                content: record.content.clone(),
+                created_at: record.created_at,
+                updated_at: record.updated_at,
+                author_username: author.username.clone(),
+                author_full_name: author.full_name.clone(),
+                author_avatar_url: author.avatar_url.clone(),
+                author_bio: author.bio.clone(),
+                depth,
+                position: index as i64 + 1,
+                sibling_count,
+                reply_count,
+            });
+
+            if depth < request.max_depth {
+                self.comment_tree_rows(post_id, request, Some(record.id), depth + 1, rows);
+            }
+        }
+    }
+
+    // Deletes the comments matching `doomed` along with every reply below
+    // them, as ON DELETE CASCADE on parent_id does
+    fn delete_comments(&mut self, doomed: impl Fn(&CommentRecord) -> bool) -> bool {
+        let mut removed: Vec<Uuid> = self
+            .comments
+            .iter()
+            .filter(|record| doomed(record))
+            .map(|record| record.id)
+            .collect();
+        if removed.is_empty() {
+            return false;
+        }
+
+        let mut index = 0;
+        while index < removed.len() {
+            let parent_id = removed[index];
+            removed.extend(
+                self.comments
+                    .iter()
+                    .filter(|record| record.parent_id == Some(parent_id))
+                    .map(|record| record.id),
+            );
+            index += 1;
+        }
+
+        self.comments.retain(|record| !removed.contains(&record.id));
+        true
+    }
+
+    fn message(&self, record: &MessageRecord) -> Option<Message> {
+        Some(Message {
+            id: record.id,
+            chat_id: record.chat_id,
+            sender_id: record.sender_id,
+            sender: self.user(&record.sender_id)?.clone(),
+            content: record.content.clone(),
+            message_type: record.message_type.clone(),
+            metadata: record.metadata.clone(),
+            created_at: record.created_at,
+        })
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryRepository {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> anyhow::Result<Option<User>> {
+        Ok(self.read().user(user_id).cloned())
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> anyhow::Result<Option<User>> {
+        Ok(self.read().users.iter().find(|user| user.email == email).cloned())
+    }
+
+    async fn create_user(&self, user: &CreateUser) -> anyhow::Result<User> {
+        let mut state = self.write();
+
+        if state.user(&user.id).is_some() {
+            return Err(primary_key_violation("users"));
+        }
+        if state.users.iter().any(|existing| existing.email == user.email) {
+            return Err(unique_violation("users", "email"));
+        }
+        if state.username_taken(user.username.as_deref(), None) {
+            return Err(unique_violation("users", "username"));
+        }
+
+        let now = Utc::now();
+        let created = User {
+            id: user.id,
+            email: user.email.clone(),
+            username: user.username.clone(),
+            full_name: user.full_name.clone(),
+            avatar_url: user.avatar_url.clone(),
+            bio: user.bio.clone(),
+            created_at: now,
+            updated_at: now,
+        };
+        state.users.push(created.clone());
+
+        Ok(created)
+    }
+
+    async fn update_user(&self, user_id: &Uuid, updates: &UpdateUser) -> anyhow::Result<User> {
+        let mut state = self.write();
+
+        if state.username_taken(updates.username.as_deref(), Some(user_id)) {
+            return Err(unique_violation("users", "username"));
+        }
+
+        let user = state
+            .users
+            .iter_mut()
+            .find(|user| user.id == *user_id)
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        if let Some(username) = &updates.username {
+            user.username = Some(username.clone());
+        }
+        if let Some(full_name) = &updates.full_name {
+            user.full_name = Some(full_name.clone());
+        }
+        if let Some(avatar_url) = &updates.avatar_url {
+            user.avatar_url = Some(avatar_url.clone());
+        }
+        if let Some(bio) = &updates.bio {
+            user.bio = Some(bio.clone());
+        }
+        user.updated_at = Utc::now();
+
+        let updated = user.clone();
+        state.record_event(&DomainEvent::UserUpdated {
+            user_id: updated.id,
+            updated_at: updated.updated_at,
+        });
+
+        Ok(updated)
+    }
+
+    async fn delete_user(&self, user_id: &Uuid) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        if state.user(user_id).is_none() {
+            return Ok(false);
+        }
+
+        let created_chats: Vec<Uuid> = state
+            .chats
+            .iter()
+            .filter(|record| record.created_by == *user_id)
+            .map(|record| record.chat.id)
+            .collect();
+
+        let authored_posts: Vec<Uuid> = state
+            .posts
+            .iter()
+            .filter(|post| post.author_id == *user_id)
+            .map(|post| post.id)
+            .collect();
+
+        state.users.retain(|user| user.id != *user_id);
+        state.follows.retain(|record| {
+            record.edge.user_id != *user_id && record.edge.target_id != *user_id
+        });
+        state.posts.retain(|post| post.author_id != *user_id);
+        state.likes.retain(|like| {
+            like.user_id != *user_id && !authored_posts.contains(&like.post_id)
+        });
+        state.delete_comments(|comment| {
+            comment.author_id == *user_id || authored_posts.contains(&comment.post_id)
+        });
+        for post in &mut state.posts {
+            post.revisions.retain(|revision| revision.editor_id != *user_id);
+        }
+        state.chats.retain(|record| record.created_by != *user_id);
+        state.participants.retain(|participant| {
+            participant.user_id != *user_id && !created_chats.contains(&participant.chat_id)
+        });
+        state.messages.retain(|message| {
+            message.sender_id != *user_id && !created_chats.contains(&message.chat_id)
+        });
+        state
+            .notifications
+            .retain(|notification| notification.user_id != *user_id);
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl PostRepository for InMemoryRepository {
+    async fn get_posts(
+        &self,
+        limit: i64,
+        offset: i64,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>> {
+        let state = self.read();
+        let (limit, offset) = window(limit, offset);
+
+        Ok(state
+            .posts
+            .iter()
+            .rev()
+            .filter(|record| record.deleted_at.is_none())
+            .skip(offset)
+            .take(limit)
+            .filter_map(|record| state.post(record, user_id))
+            .collect())
+    }
+
+    async fn get_posts_page(
+        &self,
+        page: &PageRequest,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        let state = self.read();
+
+        let live_posts = state
+            .posts
+            .iter()
+            .filter(|record| record.deleted_at.is_none());
+        let posts = keyset(live_posts, page, |record| (record.created_at, record.id))
+            .into_iter()
+            .filter_map(|record| state.post(record, user_id))
+            .collect();
+
+        Ok(CursorPage::from_rows(posts, page, |post| (post.created_at, post.id)))
+    }
+
+    async fn get_post_by_id(
+        &self,
+        post_id: &Uuid,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Option<Post>> {
+        let state = self.read();
+
+        Ok(state
+            .posts
+            .iter()
+            .find(|record| record.id == *post_id)
+            .and_then(|record| state.post(record, user_id)))
+    }
+
+    async fn get_posts_by_ids(
+        &self,
+        post_ids: &[Uuid],
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>> {
+        let state = self.read();
+
+        Ok(state
+            .posts
+            .iter()
+            .filter(|record| post_ids.contains(&record.id))
+            .filter_map(|record| state.post(record, user_id))
+            .collect())
+    }
+
+    async fn create_post(&self, post: &CreatePost) -> anyhow::Result<Uuid> {
+        let mut state = self.write();
+
+        if state.posts.iter().any(|record| record.id == post.id) {
+            return Err(primary_key_violation("posts"));
+        }
+        if state.user(&post.author_id).is_none() {
+            return Err(foreign_key_violation("posts", "author_id"));
+        }
+
+        let now = Utc::now();
+        state.posts.push(PostRecord {
+            id: post.id,
+            title: post.title.clone(),
+            content: post.content.clone(),
+            author_id: post.author_id,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            revisions: Vec::new(),
+        });
+        state.record_event(&DomainEvent::PostCreated {
+            post_id: post.id,
+            author_id: post.author_id,
+            title: post.title.clone(),
+        });
+
+        Ok(post.id)
+    }
+
+    async fn get_post_status(&self, post_id: &Uuid) -> anyhow::Result<Option<PostStatus>> {
+        Ok(self
+            .read()
+            .posts
+            .iter()
+            .find(|record| record.id == *post_id)
+            .map(|record| PostStatus {
+                id: record.id,
+                author_id: record.author_id,
+                deleted_at: record.deleted_at,
+            }))
+    }
+
+    async fn update_post(
+        &self,
+        post_id: &Uuid,
+        editor_id: &Uuid,
+        updates: &UpdatePost,
+    ) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        let editor_exists = state.user(editor_id).is_some();
+        let Some(record) = state
+            .posts
+            .iter_mut()
+            .find(|record| record.id == *post_id && record.deleted_at.is_none())
+        else {
+            return Ok(false);
+        };
+        if !editor_exists {
+            return Err(foreign_key_violation("post_revisions", "editor_id"));
+        }
+
+        let title = updates.title.as_ref().unwrap_or(&record.title);
+        let content = updates.content.as_ref().unwrap_or(&record.content);
+        if *title == record.title && *content == record.content {
+            return Ok(true);
+        }
+
+        // MAX(revision) + 1, as the SQL backends number them
+        let revision = record.revisions.last().map_or(0, |last| last.revision) + 1;
+        let now = Utc::now();
+        record.revisions.push(PostRevision {
+            post_id: record.id,
+            revision,
+            editor_id: *editor_id,
+            title: record.title.clone(),
+            content: record.content.clone(),
+            created_at: now,
+        });
+
+        if let Some(title) = &updates.title {
+            record.title = title.clone();
+        }
+        if let Some(content) = &updates.content {
+            record.content = content.clone();
+        }
+        record.updated_at = now;
+
+        Ok(true)
+    }
+
+    async fn get_post_revisions(&self, post_id: &Uuid) -> anyhow::Result<Vec<PostRevision>> {
+        Ok(self
+            .read()
+            .posts
+            .iter()
+            .find(|record| record.id == *post_id)
+            .map(|record| record.revisions.iter().rev().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn get_post_revision(
+        &self,
+        post_id: &Uuid,
+        revision: i32,
+    ) -> anyhow::Result<Option<PostRevision>> {
+        Ok(self
+            .read()
+            .posts
+            .iter()
+            .find(|record| record.id == *post_id)
+            .and_then(|record| {
+                record
+                    .revisions
+                    .iter()
+                    .find(|candidate| candidate.revision == revision)
+                    .cloned()
+            }))
+    }
+
+    async fn delete_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        match state
+            .posts
+            .iter_mut()
+            .find(|record| record.id == *post_id && record.deleted_at.is_none())
+        {
+            Some(record) => {
+                let now = Utc::now();
+                record.deleted_at = Some(now);
+                record.updated_at = now;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn restore_post(&self, post_id: &Uuid) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        match state
+            .posts
+            .iter_mut()
+            .find(|record| record.id == *post_id && record.deleted_at.is_some())
+        {
+            Some(record) => {
+                record.deleted_at = None;
+                record.updated_at = Utc::now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn purge_deleted_posts(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let mut state = self.write();
+
+        let purged: Vec<Uuid> = state
+            .posts
+            .iter()
+            .filter(|record| record.deleted_at.is_some_and(|deleted_at| deleted_at < cutoff))
+            .map(|record| record.id)
+            .collect();
+
+        state.posts.retain(|record| !purged.contains(&record.id));
+        state.likes.retain(|like| !purged.contains(&like.post_id));
+        state.delete_comments(|comment| purged.contains(&comment.post_id));
+
+        Ok(purged.len() as u64)
+    }
+
+    async fn like_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        if !state.posts.iter().any(|record| record.id == *post_id) {
+            return Err(foreign_key_violation("post_likes", "post_id"));
+        }
+        if state.user(user_id).is_none() {
+            return Err(foreign_key_violation("post_likes", "user_id"));
+        }
+        if state
+            .likes
+            .iter()
+            .any(|like| like.post_id == *post_id && like.user_id == *user_id)
+        {
+            return Ok(false);
+        }
+
+        state.likes.push(LikeRecord {
+            post_id: *post_id,
+            user_id: *user_id,
+            created_at: Utc::now(),
+        });
+
+        Ok(true)
+    }
+
+    async fn unlike_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        let before = state.likes.len();
+        state
+            .likes
+            .retain(|like| !(like.post_id == *post_id && like.user_id == *user_id));
+
+        Ok(state.likes.len() < before)
+    }
+
+    async fn get_post_likers(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<PostLiker>> {
+        let state = self.read();
+
+        let post_likes = state.likes.iter().filter(|like| like.post_id == *post_id);
+        let likers = keyset(post_likes, page, |like| (like.created_at, like.user_id))
+            .into_iter()
+            .filter_map(|like| {
+                Some(PostLiker {
+                    user: state.user(&like.user_id)?.clone().into(),
+                    liked_at: like.created_at,
+                })
+            })
+            .collect();
+
+        Ok(CursorPage::from_rows(likers, page, |liker| {
+            (liker.liked_at, liker.user.id)
+        }))
+    }
+
+    // Counters are computed on read here, so there is nothing to repair
+    async fn recount_post_counters(&self) -> anyhow::Result<u64> {
+        Ok(0)
+    }
+}
+
+#[async_trait]
+impl CommentRepository for InMemoryRepository {
+    async fn get_comments_page(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Comment>> {
+        let state = self.read();
+
+        let post_comments = state
+            .comments
+            .iter()
+            .filter(|record| record.post_id == *post_id);
+        let comments = keyset(post_comments, page, |record| (record.created_at, record.id))
+            .into_iter()
+            .filter_map(|record| state.comment(record))
+            .collect();
+
+        Ok(CursorPage::from_rows(comments, page, |comment: &Comment| {
+            (comment.created_at, comment.id)
+        }))
+    }
+
+    async fn get_comment_tree(
+        &self,
+        post_id: &Uuid,
+        request: &CommentTreeRequest,
+    ) -> anyhow::Result<CommentTree> {
+        let mut rows = Vec::new();
+        if request.max_depth >= 1 {
+            self.read()
+                .comment_tree_rows(post_id, request, request.parent_id, 1, &mut rows);
+        }
+
+        Ok(CommentTree::from_rows(rows, request))
+    }
+
+    async fn get_comment(&self, comment_id: &Uuid) -> anyhow::Result<Option<Comment>> {
+        let state = self.read();
+
+        Ok(state
+            .comments
+            .iter()
+            .find(|record| record.id == *comment_id)
+            .and_then(|record| state.comment(record)))
+    }
+
+    async fn create_comment(&self, comment: &CreateComment) -> anyhow::Result<Comment> {
+        let mut state = self.write();
+
+        if state.comments.iter().any(|record| record.id == comment.id) {
+            return Err(primary_key_violation("post_comments"));
+        }
+        if !state.posts.iter().any(|record| record.id == comment.post_id) {
+            return Err(foreign_key_violation("post_comments", "post_id"));
+        }
+        if state.user(&comment.author_id).is_none() {
+            return Err(foreign_key_violation("post_comments", "author_id"));
+        }
+        if let Some(parent_id) = comment.parent_id {
+            if !state.comments.iter().any(|record| record.id == parent_id) {
+                return Err(foreign_key_violation("post_comments", "parent_id"));
+            }
+        }
+
+        let now = Utc::now();
+        let record = CommentRecord {
+            id: comment.id,
+            post_id: comment.post_id,
+            parent_id: comment.parent_id,
+            author_id: comment.author_id,
+            content: comment.content.clone(),
+            created_at: now,
+            updated_at: now,
+        };
+        let created = state.comment(&record).ok_or(sqlx::Error::RowNotFound)?;
+        state.comments.push(record);
+
+        Ok(created)
+    }
+
+    async fn update_comment(&self, comment_id: &Uuid, content: &str) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        match state.comments.iter_mut().find(|record| record.id == *comment_id) {
+            Some(record) => {
+                record.content = content.to_string();
+                record.updated_at = Utc::now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_comment(&self, comment_id: &Uuid) -> anyhow::Result<bool> {
+        Ok(self.write().delete_comments(|record| record.id == *comment_id))
+    }
+}
+
+#[async_trait]
+impl FollowRepository for InMemoryRepository {
+    async fn add_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        if state.user(user_id).is_none() {
+            return Err(foreign_key_violation("follows", "user_id"));
+        }
+        if state.user(target_id).is_none() {
+            return Err(foreign_key_violation("follows", "target_id"));
+        }
+        if user_id == target_id {
+            return Err(ConstraintViolation {
+                kind: ConstraintKind::Check,
+                table: Some("follows".to_string()),
+                constraint: Some("follows_check".to_string()),
+                field: None,
+            }
+            .into());
+        }
+
+        if kind == FollowKind::Block {
+            state.follows.retain(|record| {
+                let edge = &record.edge;
+                !(edge.kind == FollowKind::Follow
+                    && ((edge.user_id == *user_id && edge.target_id == *target_id)
+                        || (edge.user_id == *target_id && edge.target_id == *user_id)))
+            });
+        }
+        if state.has_follow(user_id, target_id, kind) {
+            return Ok(false);
+        }
+
+        state.follows.push(FollowRecord {
+            edge: FollowEdge {
+                user_id: *user_id,
+                target_id: *target_id,
+                kind,
+            },
+            created_at: Utc::now(),
+        });
+
+        Ok(true)
+    }
+
+    async fn remove_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        let before = state.follows.len();
+        state.follows.retain(|record| {
+            let edge = &record.edge;
+            !(edge.user_id == *user_id && edge.target_id == *target_id && edge.kind == kind)
+        });
+
+        Ok(state.follows.len() < before)
+    }
+
+    async fn get_follow_edges(
+        &self,
+        user_id: &Uuid,
+        other_id: &Uuid,
+    ) -> anyhow::Result<Vec<FollowEdge>> {
+        Ok(self
+            .read()
+            .follows
+            .iter()
+            .map(|record| &record.edge)
+            .filter(|edge| {
+                (edge.user_id == *user_id && edge.target_id == *other_id)
+                    || (edge.user_id == *other_id && edge.target_id == *user_id)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn get_followers_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        Ok(self.read().follow_users(user_id, page, true))
+    }
+
+    async fn get_following_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>> {
+        Ok(self.read().follow_users(user_id, page, false))
+    }
+
+    async fn get_feed_subscribers(&self, author_id: &Uuid) -> anyhow::Result<Vec<Uuid>> {
+        let state = self.read();
+
+        Ok(state
+            .follows
+            .iter()
+            .map(|record| &record.edge)
+            .filter(|edge| edge.target_id == *author_id && edge.kind == FollowKind::Follow)
+            .filter(|edge| !state.has_follow(&edge.user_id, author_id, FollowKind::Mute))
+            .map(|edge| edge.user_id)
+            .collect())
+    }
+
+    async fn get_home_feed_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Post>> {
+        let state = self.read();
+
+        let feed_posts = state
+            .posts
+            .iter()
+            .filter(|record| state.in_home_feed(user_id, record));
+        let posts = keyset(feed_posts, page, |record| (record.created_at, record.id))
+            .into_iter()
+            .filter_map(|record| state.post(record, Some(user_id)))
+            .collect();
+
+        Ok(CursorPage::from_rows(posts, page, |post| (post.created_at, post.id)))
+    }
+
+    async fn get_home_feed_entries(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+    ) -> anyhow::Result<Vec<FeedEntry>> {
+        let state = self.read();
+        let (limit, _) = window(limit, 0);
+
+        Ok(state
+            .posts
+            .iter()
+            .rev()
+            .filter(|record| state.in_home_feed(user_id, record))
+            .take(limit)
+            .map(|record| FeedEntry {
+                post_id: record.id,
+                created_at: record.created_at,
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ChatRepository for InMemoryRepository {
+    async fn create_chat(&self, chat: &CreateChat) -> anyhow::Result<Chat> {
+        let mut state = self.write();
+
+        if state.chat_exists(&chat.id) {
+            return Err(primary_key_violation("chats"));
+        }
+        if state.user(&chat.created_by).is_none() {
+            return Err(foreign_key_violation("chats", "created_by"));
+        }
+
+        let mut participant_ids = vec![chat.created_by];
+        for user_id in &chat.participant_ids {
+            if state.user(user_id).is_none() {
+                return Err(foreign_key_violation("chat_participants", "user_id"));
+            }
+            if !participant_ids.contains(user_id) {
+                participant_ids.push(*user_id);
+            }
+        }
+
+        let now = Utc::now();
+        let created = Chat {
+            id: chat.id,
+            name: chat.name.clone(),
+            chat_type: chat.chat_type.clone(),
+            created_at: now,
+            updated_at: now,
+        };
+        state.chats.push(ChatRecord {
+            chat: created.clone(),
+            created_by: chat.created_by,
+        });
+        state
+            .participants
+            .extend(participant_ids.into_iter().map(|user_id| ParticipantRecord {
+                chat_id: chat.id,
+                user_id,
+                left_at: None,
+            }));
+
+        Ok(created)
+    }
+
+    async fn get_chat(&self, chat_id: &Uuid) -> anyhow::Result<Option<Chat>> {
+        Ok(self
+            .read()
+            .chats
+            .iter()
+            .find(|record| record.chat.id == *chat_id)
+            .map(|record| record.chat.clone()))
+    }
+
+    async fn get_user_chats(&self, user_id: &Uuid) -> anyhow::Result<Vec<Chat>> {
+        let state = self.read();
+
+        let mut chats: Vec<Chat> = state
+            .chats
+            .iter()
+            .filter(|record| {
+                state.participants.iter().any(|participant| {
+                    participant.chat_id == record.chat.id
+                        && participant.user_id == *user_id
+                        && participant.left_at.is_none()
+                })
+            })
+            .map(|record| record.chat.clone())
+            .collect();
+        chats.sort_by_key(|chat| Reverse(chat.updated_at));
+
+        Ok(chats)
+    }
+
+    async fn is_chat_participant(&self, chat_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool> {
+        Ok(self.read().participants.iter().any(|participant| {
+            participant.chat_id == *chat_id
+                && participant.user_id == *user_id
+                && participant.left_at.is_none()
+        }))
+    }
+
+    async fn create_message(&self, message: &CreateMessage) -> anyhow::Result<Message> {
+        let mut state = self.write();
+
+        if state.messages.iter().any(|record| record.id == message.id) {
+            return Err(primary_key_violation("messages"));
+        }
+        if !state.chat_exists(&message.chat_id) {
+            return Err(foreign_key_violation("messages", "chat_id"));
+        }
+        if state.user(&message.sender_id).is_none() {
+            return Err(foreign_key_violation("messages", "sender_id"));
+        }
+
+        let now = Utc::now();
+        let record = MessageRecord {
+            id: message.id,
+            chat_id: message.chat_id,
+            sender_id: message.sender_id,
+            content: message.content.clone(),
+            message_type: message.message_type.clone(),
+            metadata: message.metadata.clone(),
+            created_at: now,
+        };
+        let created = state.message(&record).ok_or(sqlx::Error::RowNotFound)?;
+        state.messages.push(record);
+
+        if let Some(chat) = state
+            .chats
+            .iter_mut()
+            .find(|record| record.chat.id == message.chat_id)
+        {
+            chat.chat.updated_at = now;
+        }
+        state.record_event(&DomainEvent::MessageSent {
+            message_id: message.id,
+            chat_id: message.chat_id,
+            sender_id: message.sender_id,
+            content: message.content.clone(),
+            created_at: now,
+        });
+
+        Ok(created)
+    }
+
+    async fn get_messages(
+        &self,
+        chat_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Message>> {
+        let state = self.read();
+        let (limit, offset) = window(limit, offset);
+
+        Ok(state
+            .messages
+            .iter()
+            .rev()
+            .filter(|record| record.chat_id == *chat_id)
+            .skip(offset)
+            .take(limit)
+            .filter_map(|record| state.message(record))
+            .collect())
+    }
+
+    async fn get_messages_page(
+        &self,
+        chat_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Message>> {
+        let state = self.read();
+
+        let chat_messages = state
+            .messages
+            .iter()
+            .filter(|record| record.chat_id == *chat_id);
+        let messages = keyset(chat_messages, page, |record| (record.created_at, record.id))
+            .into_iter()
+            .filter_map(|record| state.message(record))
+            .collect();
+
+        Ok(CursorPage::from_rows(messages, page, |message| {
+            (message.created_at, message.id)
+        }))
+    }
+}
+
+#[async_trait]
+impl NotificationRepository for InMemoryRepository {
+    async fn create_notification(
+        &self,
+        notification: &CreateNotification,
+    ) -> anyhow::Result<Notification> {
+        let mut state = self.write();
+
+        if state
+            .notifications
+            .iter()
+            .any(|existing| existing.id == notification.id)
+        {
+            return Err(primary_key_violation("notifications"));
+        }
+        if state.user(&notification.user_id).is_none() {
+            return Err(foreign_key_violation("notifications", "user_id"));
+        }
+
+        let created = Notification {
+            id: notification.id,
+            user_id: notification.user_id,
+            notification_type: notification.notification_type.clone(),
+            title: notification.title.clone(),
+            message: notification.message.clone(),
+            read: false,
+            metadata: notification.metadata.clone(),
+            created_at: Utc::now(),
+        };
+        state.notifications.push(created.clone());
+        state.record_event(&DomainEvent::NotificationCreated {
+            notification_id: created.id,
+            user_id: created.user_id,
+            notification_type: created.notification_type.clone(),
+            title: created.title.clone(),
+            message: created.message.clone(),
+            created_at: created.created_at,
+        });
+
+        Ok(created)
+    }
+
+    async fn get_notifications(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Notification>> {
+        let (limit, offset) = window(limit, offset);
+
+        Ok(self
+            .read()
+            .notifications
+            .iter()
+            .rev()
+            .filter(|notification| notification.user_id == *user_id)
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_notifications_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Notification>> {
+        let state = self.read();
+
+        let user_notifications = state
+            .notifications
+            .iter()
+            .filter(|notification| notification.user_id == *user_id);
+        let notifications = keyset(user_notifications, page, |notification| {
+            (notification.created_at, notification.id)
+        })
+        .into_iter()
+        .cloned()
+        .collect();
+
+        Ok(CursorPage::from_rows(notifications, page, |notification| {
+            (notification.created_at, notification.id)
+        }))
+    }
+
+    async fn mark_notification_read(
+        &self,
+        notification_id: &Uuid,
+        user_id: &Uuid,
+    ) -> anyhow::Result<bool> {
+        let mut state = self.write();
+
+        match state.notifications.iter_mut().find(|notification| {
+            notification.id == *notification_id && notification.user_id == *user_id
+        }) {
+            Some(notification) => {
+                notification.read = true;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchRepository for InMemoryRepository {
+    async fn search(
+        &self,
+        user_id: &Uuid,
+        request: &SearchRequest,
+    ) -> anyhow::Result<CursorPage<SearchHit>> {
+        let state = self.read();
+        let words: Vec<String> = request
+            .query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+
+        let mut hits = Vec::new();
+        for kind in &request.kinds {
+            let candidates: Vec<SearchHit> = match kind {
+                SearchKind::Post => state
+                    .posts
+                    .iter()
+                    .filter(|post| post.deleted_at.is_none())
+                    .filter_map(|post| {
+                        let text = format!("{} {}", post.title, post.content);
+                        Some(SearchHit {
+                            kind: *kind,
+                            id: post.id,
+                            title: Some(post.title.clone()),
+                            snippet: highlight(&post.content, &words),
+                            chat_id: None,
+                            rank: text_rank(&text, &words)?,
+                            created_at: post.created_at,
+                        })
+                    })
+                    .collect(),
+                SearchKind::User => state
+                    .users
+                    .iter()
+                    .filter_map(|user| {
+                        let text = [&user.username, &user.full_name, &user.bio]
+                            .into_iter()
+                            .flatten()
+                            .map(String::as_str)
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        Some(SearchHit {
+                            kind: *kind,
+                            id: user.id,
+                            title: user.full_name.clone().or_else(|| user.username.clone()),
+                            rank: text_rank(&text, &words)?,
+                            snippet: highlight(&text, &words),
+                            chat_id: None,
+                            created_at: user.created_at,
+                        })
+                    })
+                    .collect(),
+                SearchKind::Message => state
+                    .messages
+                    .iter()
+                    .filter(|message| {
+                        state.participants.iter().any(|p| {
+                            p.chat_id == message.chat_id
+                                && p.user_id == *user_id
+                                && p.left_at.is_none()
+                        })
+                    })
+                    .filter_map(|message| {
+                        Some(SearchHit {
+                            kind: *kind,
+                            id: message.id,
+                            title: state.user(&message.sender_id)?.username.clone(),
+                            snippet: highlight(&message.content, &words),
+                            chat_id: Some(message.chat_id),
+                            rank: text_rank(&message.content, &words)?,
+                            created_at: message.created_at,
+                        })
+                    })
+                    .collect(),
+            };
+
+            hits.extend(candidates.into_iter().filter(|hit| match request.cursor {
+                Some(cursor) => (hit.rank, hit.id) < (cursor.rank, cursor.id),
+                None => true,
+            }));
+        }
+
+        Ok(CursorPage::from_hits(hits, request))
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for InMemoryRepository {
+    async fn claim_outbox_events(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> anyhow::Result<Vec<OutboxRow>> {
+        let mut state = self.write();
+        let now = Utc::now();
+        let locked_until = now + chrono::Duration::from_std(lease)?;
+
+        Ok(state
+            .outbox
+            .iter_mut()
+            .filter(|record| {
+                record.published_at.is_none()
+                    && record.locked_until.is_none_or(|until| until < now)
+            })
+            .take(usize::try_from(limit).unwrap_or(0))
+            .map(|record| {
+                record.locked_until = Some(locked_until);
+                record.row.clone()
+            })
+            .collect())
+    }
+
+    async fn mark_outbox_published(&self, event_ids: &[i64]) -> anyhow::Result<()> {
+        let mut state = self.write();
+        let now = Utc::now();
+
+        for record in &mut state.outbox {
+            if event_ids.contains(&record.row.id) {
+                record.published_at = Some(now);
+                record.locked_until = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn purge_outbox(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64> {
+        let mut state = self.write();
+        let before = state.outbox.len();
+        state
+            .outbox
+            .retain(|record| record.published_at.is_none_or(|at| at >= cutoff));
+
+        Ok((before - state.outbox.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl AuditRepository for InMemoryRepository {
+    async fn append_audit_event(&self, event: &NewAuditEvent) -> anyhow::Result<AuditEvent> {
+        let mut state = self.write();
+
+        let previous = state
+            .audit_events
+            .last()
+            .map(|last| (last.seq, last.hash.clone()));
+        let event = event.link(previous);
+        state.audit_events.push(event.clone());
+
+        Ok(event)
+    }
+
+    async fn get_audit_events_page(
+        &self,
+        filter: &AuditFilter,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<AuditEvent>> {
+        let state = self.read();
+
+        let matching = state
+            .audit_events
+            .iter()
+            .filter(|event| filter.matches(event));
+        let events = keyset(matching, page, |event| (event.occurred_at, event.id))
+            .into_iter()
+            .cloned()
+            .collect();
+
+        Ok(CursorPage::from_rows(events, page, |event| (event.occurred_at, event.id)))
+    }
+
+    async fn get_audit_chain(&self, after_seq: i64, limit: i64) -> anyhow::Result<Vec<AuditEvent>> {
+        let state = self.read();
+        let (limit, _) = window(limit, 0);
+
+        Ok(state
+            .audit_events
+            .iter()
+            .filter(|event| event.seq > after_seq)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
+
+// A transaction snapshots the whole state and puts it back if the work fails.
+// Writes other tasks make meanwhile are undone with it, which tests that
+// don't run work concurrently never notice.
+#[async_trait]
+impl TransactionRepository for InMemoryRepository {
+    async fn transaction_with(
+        &self,
+        _isolation: Isolation,
+        work: UnitOfWork<'_>,
+    ) -> anyhow::Result<()> {
+        let snapshot = self.read().clone();
+
+        let result = work(Arc::new(self.clone())).await;
+        if result.is_err() {
+            *self.write() = snapshot;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use super::*;
+    use crate::{
+        audit::{AuditEntry, AuditLog},
+        feed,
+        models::{AuditAction, ChatType, Cursor},
+        testing::{self, create_post, create_user},
+    };
+
+    fn violation(error: anyhow::Error) -> ConstraintViolation {
+        error
+            .downcast::<ConstraintViolation>()
+            .expect("a constraint violation")
+    }
+
+    fn new_user(email: &str, username: Option<&str>) -> CreateUser {
+        CreateUser {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            username: username.map(str::to_string),
+            full_name: None,
+            avatar_url: None,
+            bio: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_email_is_a_unique_violation() {
+        let repository = InMemoryRepository::new();
+        create_user(&repository, "alice").await;
+
+        let error = repository
+            .create_user(&new_user("alice@example.com", Some("other")))
+            .await
+            .unwrap_err();
+
+        let violation = violation(error);
+        assert_eq!(violation.kind, ConstraintKind::Unique);
+        assert_eq!(violation.constraint.as_deref(), Some("users_email_key"));
+    }
+
+    #[tokio::test]
+    async fn duplicate_username_is_a_unique_violation() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+
+        let error = repository
+            .create_user(&new_user("other@example.com", Some("alice")))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            violation(error).constraint.as_deref(),
+            Some("users_username_key")
+        );
+
+        let rename = UpdateUser {
+            username: Some("alice".to_string()),
+            full_name: None,
+            avatar_url: None,
+            bio: None,
+        };
+        let error = repository.update_user(&bob.id, &rename).await.unwrap_err();
+        assert_eq!(violation(error).field.as_deref(), Some("username"));
+
+        // Keeping your own username, or having none, never collides
+        assert!(repository.update_user(&alice.id, &rename).await.is_ok());
+        repository
+            .create_user(&new_user("c@example.com", None))
+            .await
+            .unwrap();
+        repository
+            .create_user(&new_user("d@example.com", None))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn deleting_a_user_cascades() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+
+        let alice_post = create_post(&repository, &alice, "Alice's post").await;
+        let bob_post = create_post(&repository, &bob, "Bob's post").await;
+        let comment = CreateComment {
+            id: Uuid::new_v4(),
+            post_id: alice_post,
+            parent_id: None,
+            author_id: bob.id,
+            content: "Nice".to_string(),
+        };
+        repository.create_comment(&comment).await.unwrap();
+        repository.like_post(&bob_post, &alice.id).await.unwrap();
+
+        let chat = CreateChat {
+            id: Uuid::new_v4(),
+            name: None,
+            chat_type: ChatType::Direct,
+            created_by: alice.id,
+            participant_ids: vec![alice.id, bob.id],
+        };
+        repository.create_chat(&chat).await.unwrap();
+
+        assert!(repository.delete_user(&alice.id).await.unwrap());
+
+        assert!(repository
+            .get_user_by_id(&alice.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repository
+            .get_post_status(&alice_post)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repository.get_comment(&comment.id).await.unwrap().is_none());
+        assert!(repository.get_chat(&chat.id).await.unwrap().is_none());
+        assert!(repository.get_user_chats(&bob.id).await.unwrap().is_empty());
+
+        // Bob's own post stays, without Alice's like
+        let post = repository
+            .get_post_by_id(&bob_post, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(post.likes_count, 0);
+
+        assert!(!repository.delete_user(&alice.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn rows_need_their_foreign_keys() {
+        let repository = InMemoryRepository::new();
+        let ghost = new_user("ghost@example.com", None);
+        let post = CreatePost {
+            id: Uuid::new_v4(),
+            title: "Orphan".to_string(),
+            content: "No author".to_string(),
+            author_id: ghost.id,
+        };
+
+        let error = repository.create_post(&post).await.unwrap_err();
+        assert_eq!(violation(error).kind, ConstraintKind::ForeignKey);
+    }
+
+    #[tokio::test]
+    async fn handlers_run_against_the_repository() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/feed", feed::routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let post_id = create_post(&repository, &alice, "Hello").await;
+
+        let (status, _) = testing::send(&router, Method::GET, "/api/feed", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) =
+            testing::send(&router, Method::GET, "/api/feed", Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["data"][0]["id"], post_id.to_string());
+
+        // Tokens of deleted users stop working
+        repository.delete_user(&alice.id).await.unwrap();
+        let (status, _) =
+            testing::send(&router, Method::GET, "/api/feed", Some(&alice), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    fn ids(page: &CursorPage<Post>) -> Vec<Uuid> {
+        page.data.iter().map(|post| post.id).collect()
+    }
+
+    fn from(cursor: Option<&String>) -> PageRequest {
+        PageRequest {
+            cursor: Some(Cursor::decode(cursor.expect("a cursor")).expect("a valid cursor")),
+            limit: 3,
+        }
+    }
+
+    #[tokio::test]
+    async fn cursors_walk_pages_both_ways() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let mut post_ids = Vec::new();
+        for n in 0..7 {
+            post_ids.push(create_post(&repository, &alice, &format!("Post {}", n)).await);
+        }
+        post_ids.reverse();
+
+        let mut pages = vec![repository
+            .get_posts_page(&PageRequest::first(3), None)
+            .await
+            .unwrap()];
+        while pages.last().unwrap().next_cursor.is_some() {
+            let page = from(pages.last().unwrap().next_cursor.as_ref());
+            pages.push(repository.get_posts_page(&page, None).await.unwrap());
+        }
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].prev_cursor.is_none());
+        assert_eq!(pages.iter().flat_map(ids).collect::<Vec<_>>(), post_ids);
+
+        // Back from the last page through the same pages
+        let mut page = pages.pop().unwrap();
+        while let Some(expected) = pages.pop() {
+            page = repository
+                .get_posts_page(&from(page.prev_cursor.as_ref()), None)
+                .await
+                .unwrap();
+            assert_eq!(ids(&page), ids(&expected));
+            assert!(page.next_cursor.is_some());
+        }
+        assert!(page.prev_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn cursors_skip_rows_deleted_since() {
+        let repository = InMemoryRepository::new();
+        let alice = create_user(&repository, "alice").await;
+        let mut post_ids = Vec::new();
+        for n in 0..4 {
+            post_ids.push(create_post(&repository, &alice, &format!("Post {}", n)).await);
+        }
+        post_ids.reverse();
+
+        let first = repository
+            .get_posts_page(&PageRequest::first(2), None)
+            .await
+            .unwrap();
+        // The cursor's own row going away doesn't lose the rest
+        repository.delete_post(&post_ids[1]).await.unwrap();
+        let second = PageRequest {
+            limit: 2,
+            ..from(first.next_cursor.as_ref())
+        };
+        let second = repository.get_posts_page(&second, None).await.unwrap();
+
+        assert_eq!(ids(&second), &post_ids[2..]);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn transactions_commit_or_roll_back() {
+        testing::committed_work_is_kept(&InMemoryRepository::new()).await;
+        testing::failed_work_is_rolled_back(&InMemoryRepository::new()).await;
+        testing::nested_work_rolls_back_on_its_own(&InMemoryRepository::new()).await;
+    }
+
+    #[tokio::test]
+    async fn shared_suites() {
+        testing::posts_page_by_keyset(&InMemoryRepository::new()).await;
+        testing::comment_trees_nest_and_slice(&InMemoryRepository::new()).await;
+        testing::post_counters_follow_likes_and_comments(&InMemoryRepository::new()).await;
+        testing::audit_appends_extend_the_chain(&InMemoryRepository::new()).await;
+    }
+
+    #[tokio::test]
+    async fn edited_audit_entries_break_the_chain() {
+        let repository = InMemoryRepository::new();
+        let audit = AuditLog::new(Arc::new(repository.clone()));
+        for _ in 0..3 {
+            audit
+                .record(AuditEntry::new(AuditAction::CountersRepaired))
+                .await;
+        }
+
+        repository.write().audit_events[1].actor_id = Some(Uuid::new_v4());
+        let report = audit.verify().await.unwrap();
+        assert!(!report.valid);
+        assert_eq!(report.checked, 1);
+        assert_eq!(report.first_broken_seq, Some(2));
+
+        // Rehashing the edited entry moves the break to the next link
+        {
+            let mut state = repository.write();
+            let edited = &mut state.audit_events[1];
+            edited.hash = edited.compute_hash();
+        }
+        assert_eq!(audit.verify().await.unwrap().first_broken_seq, Some(3));
+    }
+}
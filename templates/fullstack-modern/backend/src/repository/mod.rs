@@ -0,0 +1,424 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use uuid::Uuid;
+
+use crate::{
+    database::Isolation,
+    models::{
+        AuditEvent, AuditFilter, Chat, Comment, CommentTree, CommentTreeRequest, CreateChat,
+        CreateComment, CreateMessage, CreateNotification, CreatePost, CreateUser, CursorPage,
+        FeedEntry, FollowEdge, FollowKind, FollowUser, Message, NewAuditEvent, Notification,
+        OutboxRow, PageRequest, Post, PostLiker, PostRevision, PostStatus, SearchHit,
+        SearchRequest, UpdatePost, UpdateUser, User,
+    },
+};
+
+pub mod cached;
+#[cfg(test)]
+pub mod memory;
+
+// Storage behind the API. `Database` implements these against Postgres and
+// `SqliteDatabase` (`sqlite` feature) against SQLite;
+// `memory::InMemoryRepository` (test builds only) mirrors their semantics
+// (unique keys, foreign keys, cascade deletes) so handlers can be exercised
+// without a database. `cached::CachedRepository` wraps any of them with a
+// Redis cache for user profiles and single posts.
+//
+// Constraint failures surface as `ConstraintViolation`s (directly, or inside
+// `sqlx::Error` for the SQL backends), which `AppError` maps to 409/422 either way.
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> anyhow::Result<Option<User>>;
+
+    async fn get_user_by_email(&self, email: &str) -> anyhow::Result<Option<User>>;
+
+    /// Fails if `id`, `email` or `username` is already taken.
+    async fn create_user(&self, user: &CreateUser) -> anyhow::Result<User>;
+
+    /// Only overwrites the fields that are `Some`.
+    async fn update_user(&self, user_id: &Uuid, updates: &UpdateUser) -> anyhow::Result<User>;
+
+    /// Deletes the user along with everything that references them. Returns
+    /// false if there was no such user.
+    async fn delete_user(&self, user_id: &Uuid) -> anyhow::Result<bool>;
+}
+
+// Post reads skip soft-deleted posts; only `get_post_status` sees them
+#[async_trait]
+pub trait PostRepository: Send + Sync {
+    /// Newest first; `user_id` fills in `is_liked` for that viewer.
+    async fn get_posts(
+        &self,
+        limit: i64,
+        offset: i64,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>>;
+
+    /// Keyset-paginated `get_posts`.
+    async fn get_posts_page(
+        &self,
+        page: &PageRequest,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<CursorPage<Post>>;
+
+    async fn get_post_by_id(
+        &self,
+        post_id: &Uuid,
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Option<Post>>;
+
+    /// The posts among `post_ids` that exist, in no particular order.
+    async fn get_posts_by_ids(
+        &self,
+        post_ids: &[Uuid],
+        user_id: Option<&Uuid>,
+    ) -> anyhow::Result<Vec<Post>>;
+
+    async fn create_post(&self, post: &CreatePost) -> anyhow::Result<Uuid>;
+
+    async fn get_post_status(&self, post_id: &Uuid) -> anyhow::Result<Option<PostStatus>>;
+
+    /// Only overwrites the fields that are `Some`, first recording the old
+    /// title and content as the next revision. An update that changes
+    /// nothing records no revision and leaves `updated_at` alone. Returns
+    /// false if the post doesn't exist or is deleted.
+    async fn update_post(
+        &self,
+        post_id: &Uuid,
+        editor_id: &Uuid,
+        updates: &UpdatePost,
+    ) -> anyhow::Result<bool>;
+
+    /// Newest first.
+    async fn get_post_revisions(&self, post_id: &Uuid) -> anyhow::Result<Vec<PostRevision>>;
+
+    async fn get_post_revision(
+        &self,
+        post_id: &Uuid,
+        revision: i32,
+    ) -> anyhow::Result<Option<PostRevision>>;
+
+    /// Soft delete. Returns false if the post doesn't exist or is already deleted.
+    async fn delete_post(&self, post_id: &Uuid) -> anyhow::Result<bool>;
+
+    /// Returns false if the post doesn't exist or isn't deleted.
+    async fn restore_post(&self, post_id: &Uuid) -> anyhow::Result<bool>;
+
+    /// Permanently removes posts soft-deleted before `cutoff`, along with
+    /// their likes, comments and revisions. Returns how many posts were removed.
+    async fn purge_deleted_posts(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64>;
+
+    /// Returns false if the user already liked the post.
+    async fn like_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool>;
+
+    /// Returns false if the user hadn't liked the post.
+    async fn unlike_post(&self, post_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool>;
+
+    /// Most recent like first, paginated on `(liked_at, user id)`.
+    async fn get_post_likers(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<PostLiker>>;
+
+    /// Recomputes every post's `likes_count` and `comments_count` from the
+    /// rows they count. Returns how many posts had drifted.
+    async fn recount_post_counters(&self) -> anyhow::Result<u64>;
+}
+
+#[async_trait]
+pub trait CommentRepository: Send + Sync {
+    /// All of a post's comments, replies included, newest first.
+    async fn get_comments_page(
+        &self,
+        post_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Comment>>;
+
+    /// The slice of `post_id`'s comment tree described by `request`.
+    async fn get_comment_tree(
+        &self,
+        post_id: &Uuid,
+        request: &CommentTreeRequest,
+    ) -> anyhow::Result<CommentTree>;
+
+    async fn get_comment(&self, comment_id: &Uuid) -> anyhow::Result<Option<Comment>>;
+
+    /// Fails if the post, author or parent doesn't exist.
+    async fn create_comment(&self, comment: &CreateComment) -> anyhow::Result<Comment>;
+
+    /// Returns false if there was no such comment.
+    async fn update_comment(&self, comment_id: &Uuid, content: &str) -> anyhow::Result<bool>;
+
+    /// Deletes the comment and all replies under it. Returns false if there
+    /// was no such comment.
+    async fn delete_comment(&self, comment_id: &Uuid) -> anyhow::Result<bool>;
+}
+
+#[async_trait]
+pub trait FollowRepository: Send + Sync {
+    /// Returns false if the edge already existed. Adding a block also
+    /// removes any follows between the two users, in both directions.
+    async fn add_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool>;
+
+    /// Returns false if there was no such edge.
+    async fn remove_follow(
+        &self,
+        user_id: &Uuid,
+        target_id: &Uuid,
+        kind: FollowKind,
+    ) -> anyhow::Result<bool>;
+
+    /// Every edge between the two users, in either direction.
+    async fn get_follow_edges(
+        &self,
+        user_id: &Uuid,
+        other_id: &Uuid,
+    ) -> anyhow::Result<Vec<FollowEdge>>;
+
+    /// Users following `user_id`, most recent follow first.
+    async fn get_followers_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>>;
+
+    /// Users `user_id` follows, most recent follow first.
+    async fn get_following_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<FollowUser>>;
+
+    /// Followers of `author_id` who haven't muted them, i.e. whose home
+    /// feeds show the author's posts (besides the author's own).
+    async fn get_feed_subscribers(&self, author_id: &Uuid) -> anyhow::Result<Vec<Uuid>>;
+
+    /// `user_id`'s own posts and those of users they follow and haven't
+    /// muted, newest first.
+    async fn get_home_feed_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Post>>;
+
+    /// The newest `limit` entries of `get_home_feed_page`, without the posts.
+    async fn get_home_feed_entries(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+    ) -> anyhow::Result<Vec<FeedEntry>>;
+}
+
+#[async_trait]
+pub trait ChatRepository: Send + Sync {
+    /// Creates the chat with its creator and `participant_ids` as members.
+    async fn create_chat(&self, chat: &CreateChat) -> anyhow::Result<Chat>;
+
+    async fn get_chat(&self, chat_id: &Uuid) -> anyhow::Result<Option<Chat>>;
+
+    /// Chats the user currently belongs to, most recently active first.
+    async fn get_user_chats(&self, user_id: &Uuid) -> anyhow::Result<Vec<Chat>>;
+
+    async fn is_chat_participant(&self, chat_id: &Uuid, user_id: &Uuid) -> anyhow::Result<bool>;
+
+    /// Stores the message and bumps the chat's `updated_at`.
+    async fn create_message(&self, message: &CreateMessage) -> anyhow::Result<Message>;
+
+    /// Newest first.
+    async fn get_messages(
+        &self,
+        chat_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Message>>;
+
+    /// Keyset-paginated `get_messages`.
+    async fn get_messages_page(
+        &self,
+        chat_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Message>>;
+}
+
+#[async_trait]
+pub trait NotificationRepository: Send + Sync {
+    async fn create_notification(
+        &self,
+        notification: &CreateNotification,
+    ) -> anyhow::Result<Notification>;
+
+    /// Newest first.
+    async fn get_notifications(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<Notification>>;
+
+    /// Keyset-paginated `get_notifications`.
+    async fn get_notifications_page(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<Notification>>;
+
+    /// Returns false if the notification doesn't exist or belongs to someone else.
+    async fn mark_notification_read(
+        &self,
+        notification_id: &Uuid,
+        user_id: &Uuid,
+    ) -> anyhow::Result<bool>;
+}
+
+#[async_trait]
+pub trait SearchRepository: Send + Sync {
+    /// Full-text matches for `request.query` among live posts, users and
+    /// messages in chats `user_id` belongs to, limited to `request.kinds`
+    /// and ordered by rank, best first.
+    async fn search(
+        &self,
+        user_id: &Uuid,
+        request: &SearchRequest,
+    ) -> anyhow::Result<CursorPage<SearchHit>>;
+}
+
+// `update_user`, `create_post`, `create_message` and `create_notification`
+// record a `DomainEvent` in the outbox as part of the same write; these hand
+// them to the relay.
+#[async_trait]
+pub trait OutboxRepository: Send + Sync {
+    /// Claims up to `limit` undelivered events, oldest first, for `lease`.
+    /// Events whose lease ran out without being marked published are handed
+    /// out again.
+    async fn claim_outbox_events(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> anyhow::Result<Vec<OutboxRow>>;
+
+    async fn mark_outbox_published(&self, event_ids: &[i64]) -> anyhow::Result<()>;
+
+    /// Deletes events published before `cutoff`. Returns how many.
+    async fn purge_outbox(&self, cutoff: DateTime<Utc>) -> anyhow::Result<u64>;
+}
+
+// The audit log is append-only: the SQL backends reject updates and deletes
+// of `audit_events` rows outright.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Links `event` after the newest entry and stores it. Appends are
+    /// serialized so the chain never forks.
+    async fn append_audit_event(&self, event: &NewAuditEvent) -> anyhow::Result<AuditEvent>;
+
+    /// Entries matching `filter`, newest first.
+    async fn get_audit_events_page(
+        &self,
+        filter: &AuditFilter,
+        page: &PageRequest,
+    ) -> anyhow::Result<CursorPage<AuditEvent>>;
+
+    /// Up to `limit` entries with `seq` above `after_seq`, in chain order.
+    async fn get_audit_chain(&self, after_seq: i64, limit: i64) -> anyhow::Result<Vec<AuditEvent>>;
+}
+
+/// Work for `TransactionRepository::transaction_with`, handed a repository
+/// whose every method runs in the transaction.
+pub type UnitOfWork<'a> =
+    Box<dyn Fn(Arc<dyn Repository>) -> BoxFuture<'a, anyhow::Result<()>> + Send + Sync + 'a>;
+
+#[async_trait]
+pub trait TransactionRepository: Send + Sync {
+    /// Runs `work` as one transaction, committed if it succeeds and rolled
+    /// back if it fails. Called from inside `work`, it runs under a savepoint
+    /// instead. `work` may be run again from the start when the database
+    /// aborts the transaction as a serialization failure or deadlock, so it
+    /// must not have side effects outside the repository, and the repository
+    /// it is handed must not outlive it.
+    async fn transaction_with(
+        &self,
+        isolation: Isolation,
+        work: UnitOfWork<'_>,
+    ) -> anyhow::Result<()>;
+}
+
+/// Everything handlers need from storage, as one object-safe trait.
+pub trait Repository:
+    UserRepository
+    + PostRepository
+    + CommentRepository
+    + FollowRepository
+    + ChatRepository
+    + NotificationRepository
+    + SearchRepository
+    + OutboxRepository
+    + AuditRepository
+    + TransactionRepository
+{
+}
+
+impl<T> Repository for T where
+    T: UserRepository
+        + PostRepository
+        + CommentRepository
+        + FollowRepository
+        + ChatRepository
+        + NotificationRepository
+        + SearchRepository
+        + OutboxRepository
+        + AuditRepository
+        + TransactionRepository
+{
+}
+
+impl dyn Repository + '_ {
+    /// `transaction_at` the default isolation level.
+    pub async fn transaction<'a, T, F>(&self, work: F) -> anyhow::Result<T>
+    where
+        T: Send + 'a,
+        F: Fn(Arc<dyn Repository>) -> BoxFuture<'a, anyhow::Result<T>> + Send + Sync + 'a,
+    {
+        self.transaction_at(Isolation::default(), work).await
+    }
+
+    /// `transaction_with`, returning what `work` produced.
+    pub async fn transaction_at<'a, T, F>(&self, isolation: Isolation, work: F) -> anyhow::Result<T>
+    where
+        T: Send + 'a,
+        F: Fn(Arc<dyn Repository>) -> BoxFuture<'a, anyhow::Result<T>> + Send + Sync + 'a,
+    {
+        // A retried attempt overwrites this, so it ends up with the result
+        // of the attempt that committed
+        let output = Mutex::new(None);
+        let (work, slot) = (&work, &output);
+        self.transaction_with(
+            isolation,
+            Box::new(move |repository| {
+                let run = work(repository);
+                Box::pin(async move {
+                    let value = run.await?;
+                    *slot.lock().unwrap_or_else(PoisonError::into_inner) = Some(value);
+                    Ok(())
+                })
+            }),
+        )
+        .await?;
+
+        output
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .ok_or_else(|| anyhow::anyhow!("Transaction finished without running its work"))
+    }
+}
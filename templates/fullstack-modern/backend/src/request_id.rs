@@ -0,0 +1,143 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Span;
+use uuid::Uuid;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Longest client-supplied id we are willing to echo back
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled on this task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Assigns every request an id (reusing a sane incoming `X-Request-Id`),
+/// exposes it to handlers and errors via `current()`, and echoes it back in
+/// the response headers. Must run outside `TraceLayer` so spans can record it.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN)
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let header_value = match HeaderValue::from_str(&request_id) {
+        Ok(value) => value,
+        Err(_) => return next.run(request).await,
+    };
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER.clone(), header_value.clone());
+
+    let mut response = REQUEST_ID.scope(request_id, next.run(request)).await;
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER.clone(), header_value);
+    response
+}
+
+/// Span for `TraceLayer` carrying the request id assigned above.
+pub fn make_span(request: &Request) -> Span {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = %request_id,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{self, Body},
+        middleware::from_fn,
+        routing::get,
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::error::AppError;
+
+    fn router() -> Router {
+        Router::new()
+            .route("/id", get(|| async { current().unwrap_or_default() }))
+            .route(
+                "/fail",
+                get(|| async { Err::<(), _>(AppError::internal("boom")) }),
+            )
+            .layer(from_fn(request_id))
+    }
+
+    async fn send(uri: &str, incoming: Option<&str>) -> (String, String) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(incoming) = incoming {
+            request = request.header(&REQUEST_ID_HEADER, incoming);
+        }
+        let response = router()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let header = response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (header, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn incoming_ids_are_kept() {
+        let (header, seen) = send("/id", Some("from-the-proxy")).await;
+
+        assert_eq!(header, "from-the-proxy");
+        assert_eq!(seen, "from-the-proxy");
+    }
+
+    #[tokio::test]
+    async fn missing_or_oversized_ids_are_replaced() {
+        let (header, seen) = send("/id", None).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+        assert_eq!(seen, header);
+
+        let oversized = "x".repeat(MAX_REQUEST_ID_LEN + 1);
+        let (header, _) = send("/id", Some(&oversized)).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+    }
+
+    #[tokio::test]
+    async fn error_bodies_carry_the_id() {
+        let (header, body) = send("/fail", Some("trace-me")).await;
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(header, "trace-me");
+        assert_eq!(body["request_id"], "trace-me");
+    }
+
+    #[test]
+    fn there_is_no_id_outside_a_request() {
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,24 @@
+use axum::{routing::MethodRouter, Router};
+
+use crate::services::Services;
+
+/// One endpoint as (method, path, handler). Route groups return these rather
+/// than a built `Router`, so the `routes` subcommand lists exactly what is
+/// mounted.
+pub type Route = (&'static str, &'static str, MethodRouter<Services>);
+
+/// Mounts `routes`; handlers for the same path with different methods are
+/// merged.
+pub fn router(routes: Vec<Route>) -> Router<Services> {
+    routes
+        .into_iter()
+        .fold(Router::new(), |router, (_, path, handler)| router.route(path, handler))
+}
+
+/// `path` as served once its group is nested under `prefix`.
+pub fn full_path(prefix: &str, path: &str) -> String {
+    match path {
+        "/" => prefix.to_string(),
+        path => format!("{}{}", prefix, path),
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    fs,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::config::{Config, ConfigError, ConfigIssue, ConfigProblem};
+
+// How often the config directory is checked for modified files
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Log filter when neither `log_filter` nor RUST_LOG is set
+const DEFAULT_LOG_FILTER: &str = "backend=debug,tower_http=debug";
+
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// The filter in effect without `log_filter`: RUST_LOG, or the built-in default.
+pub fn default_log_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_LOG_FILTER.into())
+}
+
+// Subset of `Config` that can change without restarting the server. Only
+// values read through `RuntimeSettings::current()` belong here; anything
+// captured when the server starts is listed in `restart_required_changes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadableSettings {
+    pub max_file_size: usize,
+    pub frontend_url: String,
+    pub rate_limit_per_second: u64,
+    pub rate_limit_burst: u32,
+    pub log_filter: Option<String>,
+    pub problem_details: bool,
+    pub problem_type_base: String,
+    pub default_locale: String,
+    pub post_restore_window_hours: u64,
+    pub post_retention_days: u64,
+}
+
+impl From<&Config> for ReloadableSettings {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_file_size: config.max_file_size,
+            frontend_url: config.frontend_url.clone(),
+            rate_limit_per_second: config.rate_limit_per_second,
+            rate_limit_burst: config.rate_limit_burst,
+            log_filter: config.log_filter.clone(),
+            problem_details: config.problem_details,
+            problem_type_base: config.problem_type_base.clone(),
+            default_locale: config.default_locale.clone(),
+            post_restore_window_hours: config.post_restore_window_hours,
+            post_retention_days: config.post_retention_days,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    pub applied: bool,
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Shared handle to the reloadable settings.
+///
+/// Readers call `current()` and get a consistent snapshot; a reload swaps
+/// the whole snapshot at once. Values that are only read at startup (such
+/// as `database_url`) are compared against the boot config and reported as
+/// requiring a restart.
+#[derive(Clone)]
+pub struct RuntimeSettings {
+    boot_config: Arc<Config>,
+    current: Arc<RwLock<Arc<ReloadableSettings>>>,
+    log_filter: Option<LogFilterHandle>,
+}
+
+impl RuntimeSettings {
+    pub fn new(config: &Config, log_filter: Option<LogFilterHandle>) -> Result<Self, ConfigError> {
+        let settings = Self {
+            boot_config: Arc::new(config.clone()),
+            current: Arc::new(RwLock::new(Arc::new(ReloadableSettings::from(config)))),
+            log_filter,
+        };
+
+        if let Some(filter) = &config.log_filter {
+            settings.apply_log_filter(filter)?;
+        }
+
+        Ok(settings)
+    }
+
+    pub fn current(&self) -> Arc<ReloadableSettings> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Re-reads every configuration layer and swaps in the reloadable subset.
+    /// Nothing changes if the new configuration fails to load or validate.
+    pub fn reload(&self) -> Result<ReloadReport, ConfigError> {
+        let config = Config::load()?;
+        config.validate()?;
+
+        let next = ReloadableSettings::from(&config);
+        let previous = self.current();
+        let restart_required = self.boot_config.restart_required_changes(&config);
+
+        if *previous == next {
+            return Ok(ReloadReport {
+                applied: false,
+                restart_required,
+            });
+        }
+
+        if next.log_filter != previous.log_filter {
+            match &next.log_filter {
+                Some(filter) => self.apply_log_filter(filter)?,
+                // Removing the key goes back to what the server started with
+                None => self.swap_log_filter(default_log_filter())?,
+            }
+        }
+
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(next);
+
+        Ok(ReloadReport {
+            applied: true,
+            restart_required,
+        })
+    }
+
+    /// Reloads on SIGHUP (Unix) and whenever a file in the config directory changes.
+    pub fn spawn_watchers(&self) {
+        let poller = self.clone();
+        tokio::spawn(async move { poller.poll_config_dir().await });
+
+        #[cfg(unix)]
+        {
+            let on_hangup = self.clone();
+            tokio::spawn(async move { on_hangup.reload_on_hangup().await });
+        }
+    }
+
+    async fn poll_config_dir(self) {
+        let mut last_modified = self.config_dir_modified();
+        let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let modified = self.config_dir_modified();
+            if modified != last_modified {
+                last_modified = modified;
+                self.reload_and_log("config file changed");
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    async fn reload_on_hangup(self) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                tracing::warn!("SIGHUP reloading disabled: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            self.reload_and_log("SIGHUP");
+        }
+    }
+
+    fn reload_and_log(&self, trigger: &str) {
+        match self.reload() {
+            Ok(report) => {
+                if report.applied {
+                    tracing::info!("Runtime settings reloaded ({})", trigger);
+                } else {
+                    tracing::debug!("No reloadable settings changed ({})", trigger);
+                }
+
+                if !report.restart_required.is_empty() {
+                    tracing::warn!(
+                        "Restart required to apply changes to: {}",
+                        report.restart_required.join(", ")
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Keeping previous runtime settings, reload failed: {}", e),
+        }
+    }
+
+    fn apply_log_filter(&self, filter: &str) -> Result<(), ConfigError> {
+        let env_filter = EnvFilter::try_new(filter).map_err(|e| log_filter_error(e.to_string()))?;
+        self.swap_log_filter(env_filter)
+    }
+
+    fn swap_log_filter(&self, env_filter: EnvFilter) -> Result<(), ConfigError> {
+        if let Some(handle) = &self.log_filter {
+            handle
+                .reload(env_filter)
+                .map_err(|e| log_filter_error(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    fn config_dir_modified(&self) -> Option<SystemTime> {
+        fs::read_dir(&self.boot_config.config_dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+            .max()
+    }
+}
+
+fn log_filter_error(reason: String) -> ConfigError {
+    ConfigError::Invalid(vec![ConfigIssue {
+        key: "log_filter".to_string(),
+        problem: ConfigProblem::Malformed(reason),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn snapshots_start_from_the_boot_config() {
+        let config = Config {
+            problem_details: true,
+            default_locale: "de".to_string(),
+            ..testing::config()
+        };
+
+        let settings = RuntimeSettings::new(&config, None).unwrap();
+        let current = settings.current();
+
+        assert!(current.problem_details);
+        assert_eq!(current.default_locale, "de");
+        assert_eq!(*current, ReloadableSettings::from(&config));
+    }
+
+    #[test]
+    fn bad_log_filters_are_rejected() {
+        let config = Config {
+            log_filter: Some("backend=loudest".to_string()),
+            ..testing::config()
+        };
+
+        match RuntimeSettings::new(&config, None) {
+            Err(ConfigError::Invalid(issues)) => assert_eq!(issues[0].key, "log_filter"),
+            _ => panic!("expected the log filter to be rejected"),
+        }
+    }
+
+    #[test]
+    fn only_startup_settings_need_a_restart() {
+        let boot = testing::config();
+        let edited = Config {
+            port: 9000,
+            problem_details: true,
+            post_retention_days: 60,
+            max_file_size: 2048,
+            frontend_url: "https://app.example".to_string(),
+            rate_limit_burst: 5,
+            ..testing::config()
+        };
+
+        assert_eq!(boot.restart_required_changes(&edited), ["port"]);
+        assert!(boot.restart_required_changes(&boot.clone()).is_empty());
+    }
+}
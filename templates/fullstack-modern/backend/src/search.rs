@@ -0,0 +1,200 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json,
+};
+
+use crate::{
+    auth::AuthUser,
+    error::AppResult,
+    models::{ApiResponse, CursorPage, SearchHit, SearchParams},
+    routing::Route,
+    services::Services,
+};
+
+// Full-text search over live posts, users and messages in the caller's chats
+pub fn routes() -> Vec<Route> {
+    vec![("GET", "/", get(search))]
+}
+
+/// `?q=` is free text (Postgres `websearch_to_tsquery` syntax: quoted
+/// phrases, `or`, `-word`); `?type=post,user,message` narrows the kinds.
+/// Hits come back best first; follow `next_cursor` for more.
+async fn search(
+    State(services): State<Services>,
+    user: AuthUser,
+    Query(params): Query<SearchParams>,
+) -> AppResult<Json<ApiResponse<CursorPage<SearchHit>>>> {
+    let request = params.search_request()?;
+    let hits = services.repository.search(&user.user_id, &request).await?;
+
+    Ok(Json(ApiResponse::success(hits)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{Method, StatusCode},
+        Router,
+    };
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        models::{ChatType, CreateChat, CreateMessage, MessageType, SearchCursor, User},
+        repository::{memory::InMemoryRepository, ChatRepository},
+        testing::{self, create_post, create_user},
+    };
+
+    async fn chat(repository: &InMemoryRepository, members: [&User; 2], content: &str) -> Uuid {
+        let chat = CreateChat {
+            id: Uuid::new_v4(),
+            name: None,
+            chat_type: ChatType::Direct,
+            created_by: members[0].id,
+            participant_ids: members.map(|member| member.id).to_vec(),
+        };
+        repository.create_chat(&chat).await.unwrap();
+
+        let message = CreateMessage {
+            id: Uuid::new_v4(),
+            chat_id: chat.id,
+            sender_id: members[0].id,
+            content: content.to_string(),
+            message_type: MessageType::Text,
+            metadata: None,
+        };
+        repository.create_message(&message).await.unwrap().id
+    }
+
+    async fn search_as(router: &Router, user: &User, query: &str) -> (StatusCode, Value) {
+        let uri = format!("/api/search?{}", query);
+        testing::send(router, Method::GET, &uri, Some(user), None).await
+    }
+
+    fn hit_ids(body: &Value) -> Vec<String> {
+        body["data"]["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hit| hit["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn messages_are_only_found_in_your_own_chats() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/search", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        let carol = create_user(&repository, "carol").await;
+        let post_id = create_post(&repository, &bob, "Rust release notes").await;
+        let shared = chat(&repository, [&bob, &alice], "Rust meetup tonight?").await;
+        chat(&repository, [&bob, &carol], "Rust gossip").await;
+
+        let (status, body) = search_as(&router, &alice, "q=rust").await;
+        assert_eq!(status, StatusCode::OK);
+        let mut ids = hit_ids(&body);
+        ids.sort();
+        let mut expected = vec![post_id.to_string(), shared.to_string()];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        let (_, body) = search_as(&router, &alice, "q=rust&type=message").await;
+        assert_eq!(hit_ids(&body), [shared.to_string()]);
+        assert_eq!(
+            body["data"]["data"][0]["snippet"],
+            "<mark>Rust</mark> meetup tonight?"
+        );
+
+        let (_, body) = search_as(&router, &alice, "q=carol&type=user").await;
+        assert_eq!(hit_ids(&body), [carol.id.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn snippets_escape_the_text_around_matches() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/search", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        let bob = create_user(&repository, "bob").await;
+        chat(
+            &repository,
+            [&bob, &alice],
+            "<script>alert('rust')</script>",
+        )
+        .await;
+
+        let (status, body) = search_as(&router, &alice, "q=alert&type=message").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["data"]["data"][0]["snippet"],
+            "&lt;script&gt;<mark>alert</mark>(&#39;rust&#39;)&lt;/script&gt;"
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_searches_are_rejected() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/search", routes());
+        let alice = create_user(&repository, "alice").await;
+
+        for query in [
+            "q=%20%20",
+            "q=rust&type=post,planet",
+            "q=rust&type=,",
+            "q=rust&cursor=zz",
+        ] {
+            let (status, _) = search_as(&router, &alice, query).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn hits_page_best_first() {
+        let repository = InMemoryRepository::new();
+        let services = testing::services(&repository).await;
+        let router = testing::router(services, "/api/search", routes());
+
+        let alice = create_user(&repository, "alice").await;
+        for title in [
+            "rust",
+            "rust rust",
+            "rust rust rust",
+            "rust again",
+            "more rust",
+        ] {
+            create_post(&repository, &alice, title).await;
+        }
+
+        let mut ranks = Vec::new();
+        let mut seen = Vec::new();
+        let mut query = "q=rust&type=post&limit=2".to_string();
+        loop {
+            let (status, body) = search_as(&router, &alice, &query).await;
+            assert_eq!(status, StatusCode::OK);
+            for hit in body["data"]["data"].as_array().unwrap() {
+                ranks.push(hit["rank"].as_f64().unwrap());
+            }
+            seen.extend(hit_ids(&body));
+
+            let Some(cursor) = body["data"]["next_cursor"].as_str() else {
+                break;
+            };
+            let decoded = SearchCursor::decode(cursor).unwrap();
+            assert_eq!(SearchCursor::decode(&decoded.encode()), Some(decoded));
+            query = format!("q=rust&type=post&limit=2&cursor={}", cursor);
+        }
+
+        assert_eq!(seen.len(), 5);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 5);
+        assert!(ranks.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+}
@@ -0,0 +1,309 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        ChatType, CreateChat, CreateComment, CreateMessage, CreatePost, CreateUser, MessageType,
+    },
+    repository::Repository,
+};
+
+// Demo and load-test data. Everything, ids included, is drawn from RNGs
+// seeded by the caller, so the same seed and size always produce the same
+// dataset. Each user, post and chat has a stream of its own, so a larger
+// `size` produces the same rows plus more. Rows that already exist are left
+// alone, which makes re-running a seed a no-op and growing `size` only add
+// the missing rows.
+
+const WORDS: &[&str] = &[
+    "rust", "async", "server", "deploy", "cache", "query", "schema", "index", "feed", "chat",
+    "weekend", "coffee", "release", "review", "bug", "feature", "design", "team", "launch",
+    "metrics", "latency", "build", "test", "docs", "idea", "project", "demo", "update",
+];
+
+// Chats get between two participants and this many
+const MAX_CHAT_PARTICIPANTS: usize = 5;
+
+/// How much data to generate.
+#[derive(Debug, Clone)]
+pub struct SeedSize {
+    pub users: usize,
+    pub posts_per_user: usize,
+    pub comments_per_post: usize,
+    pub chats: usize,
+    pub messages_per_chat: usize,
+}
+
+/// Rows created by a seed run; existing rows aren't counted.
+#[derive(Debug, Default)]
+pub struct SeedReport {
+    pub users: usize,
+    pub posts: usize,
+    pub comments: usize,
+    pub chats: usize,
+    pub messages: usize,
+}
+
+pub async fn seed(
+    repository: &dyn Repository,
+    size: &SeedSize,
+    seed: u64,
+) -> anyhow::Result<SeedReport> {
+    let mut report = SeedReport::default();
+
+    let mut user_ids = Vec::with_capacity(size.users);
+    for n in 1..=size.users {
+        let mut rng = stream(seed, &format!("user {}", n));
+        let new_user = CreateUser {
+            id: random_id(&mut rng),
+            email: format!("demo{}@example.com", n),
+            username: Some(format!("demo{}", n)),
+            full_name: Some(format!("Demo User {}", n)),
+            avatar_url: None,
+            bio: Some(sentence(&mut rng, 4, 10)),
+        };
+
+        let user_id = match repository.get_user_by_email(&new_user.email).await? {
+            Some(user) => user.id,
+            None => {
+                report.users += 1;
+                repository.create_user(&new_user).await?.id
+            }
+        };
+        user_ids.push(user_id);
+    }
+
+    for (n, author_id) in (1..).zip(&user_ids) {
+        for p in 1..=size.posts_per_user {
+            let mut rng = stream(seed, &format!("post {} {}", n, p));
+            let post = CreatePost {
+                id: random_id(&mut rng),
+                title: title(&mut rng),
+                content: paragraph(&mut rng),
+                author_id: *author_id,
+            };
+
+            if repository.get_post_status(&post.id).await?.is_some() {
+                continue;
+            }
+
+            let mut comments: Vec<CreateComment> = Vec::with_capacity(size.comments_per_post);
+            for _ in 0..size.comments_per_post {
+                let parent_id = match comments.choose(&mut rng) {
+                    Some(parent) if rng.gen_ratio(1, 3) => Some(parent.id),
+                    _ => None,
+                };
+                let comment = CreateComment {
+                    id: random_id(&mut rng),
+                    post_id: post.id,
+                    parent_id,
+                    author_id: *user_ids.choose(&mut rng).unwrap_or(author_id),
+                    content: sentence(&mut rng, 3, 16),
+                };
+                comments.push(comment);
+            }
+
+            // All or nothing, like chats: a later run skips the post once it exists
+            repository
+                .transaction(|tx| {
+                    let (post, comments) = (&post, &comments);
+                    Box::pin(async move {
+                        tx.create_post(post).await?;
+                        for comment in comments {
+                            tx.create_comment(comment).await?;
+                        }
+                        Ok(())
+                    })
+                })
+                .await?;
+            report.posts += 1;
+            report.comments += comments.len();
+        }
+    }
+
+    if user_ids.len() < 2 {
+        return Ok(report);
+    }
+
+    for n in 1..=size.chats {
+        let mut rng = stream(seed, &format!("chat {}", n));
+        let id = random_id(&mut rng);
+        if repository.get_chat(&id).await?.is_some() {
+            continue;
+        }
+
+        let count = rng.gen_range(2..=MAX_CHAT_PARTICIPANTS.min(user_ids.len()));
+        let participant_ids: Vec<Uuid> =
+            user_ids.choose_multiple(&mut rng, count).copied().collect();
+        let (chat_type, name) = if count > 2 {
+            (ChatType::Group, Some(title(&mut rng)))
+        } else {
+            (ChatType::Direct, None)
+        };
+        let chat = CreateChat {
+            id,
+            name,
+            chat_type,
+            created_by: participant_ids[0],
+            participant_ids: participant_ids.clone(),
+        };
+
+        let messages: Vec<CreateMessage> = (0..size.messages_per_chat)
+            .map(|_| CreateMessage {
+                id: random_id(&mut rng),
+                chat_id: chat.id,
+                sender_id: participant_ids[rng.gen_range(0..participant_ids.len())],
+                content: sentence(&mut rng, 1, 12),
+                message_type: MessageType::Text,
+                metadata: None,
+            })
+            .collect();
+
+        // All or nothing, since a later run skips the chat once it exists
+        repository
+            .transaction(|tx| {
+                let (chat, messages) = (&chat, &messages);
+                Box::pin(async move {
+                    tx.create_chat(chat).await?;
+                    for message in messages {
+                        tx.create_message(message).await?;
+                    }
+                    Ok(())
+                })
+            })
+            .await?;
+        report.chats += 1;
+        report.messages += messages.len();
+    }
+
+    Ok(report)
+}
+
+// The RNG for one part of the dataset, e.g. "post 3 1"
+fn stream(seed: u64, part: &str) -> StdRng {
+    let digest = Sha256::new()
+        .chain_update(seed.to_le_bytes())
+        .chain_update(part)
+        .finalize();
+    StdRng::from_seed(digest.into())
+}
+
+fn random_id(rng: &mut StdRng) -> Uuid {
+    uuid::Builder::from_random_bytes(rng.gen()).into_uuid()
+}
+
+fn words(rng: &mut StdRng, min: usize, max: usize) -> Vec<&'static str> {
+    let count = rng.gen_range(min..=max);
+    (0..count)
+        .map(|_| WORDS[rng.gen_range(0..WORDS.len())])
+        .collect()
+}
+
+fn sentence(rng: &mut StdRng, min: usize, max: usize) -> String {
+    let mut sentence = words(rng, min, max).join(" ");
+    if let Some(first) = sentence.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    sentence.push('.');
+    sentence
+}
+
+fn title(rng: &mut StdRng) -> String {
+    let mut title = sentence(rng, 2, 6);
+    title.pop();
+    title
+}
+
+fn paragraph(rng: &mut StdRng) -> String {
+    let count = rng.gen_range(2..=5);
+    (0..count)
+        .map(|_| sentence(rng, 5, 14))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::PageRequest,
+        repository::{memory::InMemoryRepository, UserRepository},
+    };
+
+    const SIZE: SeedSize = SeedSize {
+        users: 4,
+        posts_per_user: 2,
+        comments_per_post: 3,
+        chats: 2,
+        messages_per_chat: 3,
+    };
+
+    async fn posts(repository: &dyn Repository) -> Vec<(Uuid, Uuid)> {
+        let page = repository
+            .get_posts_page(&PageRequest::first(100), None)
+            .await
+            .unwrap();
+        page.data
+            .iter()
+            .map(|post| (post.id, post.author_id))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn the_same_seed_gives_the_same_data() {
+        let (first, second) = (InMemoryRepository::new(), InMemoryRepository::new());
+
+        let report = seed(&first, &SIZE, 42).await.unwrap();
+        seed(&second, &SIZE, 42).await.unwrap();
+
+        assert_eq!(report.users, 4);
+        assert_eq!(report.posts, 8);
+        assert_eq!(report.comments, 24);
+        assert_eq!(report.chats, 2);
+        assert_eq!(report.messages, 6);
+        let user = first.get_user_by_email("demo1@example.com").await.unwrap();
+        let same = second.get_user_by_email("demo1@example.com").await.unwrap();
+        assert_eq!(user.map(|user| user.id), same.map(|user| user.id));
+        assert_eq!(posts(&first).await, posts(&second).await);
+
+        let other = InMemoryRepository::new();
+        seed(&other, &SIZE, 7).await.unwrap();
+        assert_ne!(posts(&first).await, posts(&other).await);
+    }
+
+    #[tokio::test]
+    async fn reseeding_only_adds_what_is_missing() {
+        let repository = InMemoryRepository::new();
+        seed(&repository, &SIZE, 42).await.unwrap();
+        let before = posts(&repository).await;
+
+        let report = seed(&repository, &SIZE, 42).await.unwrap();
+        assert_eq!(
+            (
+                report.users,
+                report.posts,
+                report.comments,
+                report.chats,
+                report.messages
+            ),
+            (0, 0, 0, 0, 0)
+        );
+        assert_eq!(posts(&repository).await, before);
+
+        let larger = SeedSize {
+            posts_per_user: 3,
+            ..SIZE
+        };
+        let report = seed(&repository, &larger, 42).await.unwrap();
+        assert_eq!((report.users, report.posts, report.comments), (0, 4, 12));
+
+        // Growing into a size gives what seeding that size does from scratch
+        let fresh = InMemoryRepository::new();
+        seed(&fresh, &larger, 42).await.unwrap();
+        let (mut grown, mut fresh) = (posts(&repository).await, posts(&fresh).await);
+        grown.sort();
+        fresh.sort();
+        assert_eq!(grown, fresh);
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::{
+    audit::AuditLog,
+    config::Config,
+    feed::HomeFeed,
+    outbox::EventBus,
+    repository::{
+        cached::{CacheMetrics, CachedRepository},
+        Repository,
+    },
+    runtime::RuntimeSettings,
+    websocket::ConnectionManager,
+};
+
+// Shared application state handed to every handler
+#[derive(Clone)]
+pub struct Services {
+    pub config: Config,
+    // `Database` in production; tests can pass an `InMemoryRepository`
+    pub repository: Arc<dyn Repository>,
+    pub connection_manager: Arc<ConnectionManager>,
+    pub runtime: RuntimeSettings,
+    pub feed: HomeFeed,
+    // Set when users and posts are served through the Redis cache
+    pub cache: Option<Arc<CacheMetrics>>,
+    // Domain events relayed from the outbox
+    pub events: EventBus,
+    pub audit: AuditLog,
+}
+
+impl Services {
+    pub async fn new(
+        config: Config,
+        repository: Arc<dyn Repository>,
+        runtime: RuntimeSettings,
+    ) -> anyhow::Result<Self> {
+        let feed = HomeFeed::connect(&config).await?;
+        let (repository, cache) = CachedRepository::wrap(repository, &config).await;
+        let events = EventBus::connect(&config).await?;
+        let audit = AuditLog::new(repository.clone());
+
+        Ok(Self {
+            config,
+            repository,
+            connection_manager: Arc::new(ConnectionManager::new()),
+            runtime,
+            feed,
+            cache,
+            events,
+            audit,
+        })
+    }
+}
@@ -0,0 +1,374 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{self, Body},
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use chrono::{SubsecRound, Utc};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{
+    auth::{self, Claims},
+    config::{Config, EventSink, FeedStrategy, Profile, Secret},
+    i18n,
+    models::{
+        AuditAction, CommentSort, CommentTreeRequest, CreateComment, CreatePost, CreateUser,
+        Cursor, CursorPage, NewAuditEvent, PageRequest, Post, User, AUDIT_GENESIS_HASH,
+    },
+    repository::{memory::InMemoryRepository, Repository},
+    routing::{self, Route},
+    runtime::RuntimeSettings,
+    services::Services,
+};
+
+// Fixtures for unit tests: a configuration that needs neither Postgres nor
+// Redis, `Services` over an `InMemoryRepository`, and a way to drive route
+// groups through a real router.
+
+pub fn config() -> Config {
+    Config {
+        profile: Profile::Development,
+        config_dir: "config".to_string(),
+        database_url: Secret::new("postgresql://localhost/test"),
+        database_replica_urls: Vec::new(),
+        database_max_connections: 1,
+        auto_migrate: false,
+        read_your_writes_secs: 0,
+        redis_url: Secret::new("redis://localhost:6379"),
+        // Both zero, so no cache is put in front of the repository
+        cache_user_ttl_secs: 0,
+        cache_post_ttl_secs: 0,
+        jwt_secret: Secret::new("0123456789abcdef0123456789abcdef"),
+        supabase_url: "http://localhost".to_string(),
+        supabase_anon_key: "anon".to_string(),
+        supabase_service_role_key: Secret::new("service-role"),
+        port: 0,
+        sentry_dsn: None,
+        upload_dir: "./uploads".to_string(),
+        max_file_size: 1024,
+        frontend_url: "http://localhost:3000".to_string(),
+        cors_origins: Vec::new(),
+        rate_limit_per_second: 10,
+        rate_limit_burst: 50,
+        log_filter: None,
+        problem_details: false,
+        problem_type_base: "/errors".to_string(),
+        default_locale: i18n::FALLBACK_LOCALE.to_string(),
+        post_restore_window_hours: 72,
+        post_retention_days: 30,
+        feed_strategy: FeedStrategy::Read,
+        event_sink: EventSink::Memory,
+        admin_emails: vec!["admin@example.com".to_string()],
+        trusted_proxy_hops: 0,
+    }
+}
+
+pub async fn services(repository: &InMemoryRepository) -> Services {
+    let config = config();
+    auth::set_jwt_secret(config.jwt_secret.expose());
+
+    let runtime = RuntimeSettings::new(&config, None).expect("test config is valid");
+    Services::new(config, Arc::new(repository.clone()), runtime)
+        .await
+        .expect("test services need no external connections")
+}
+
+/// `routes` nested under `prefix`, as `main` mounts a route group.
+pub fn router(services: Services, prefix: &str, routes: Vec<Route>) -> Router {
+    Router::new()
+        .nest(prefix, routing::router(routes))
+        .with_state(services)
+}
+
+/// Sends one request, authenticated as `user` when given, and returns the
+/// status with the JSON body (`Null` when there is none).
+pub async fn send(
+    router: &Router,
+    method: Method,
+    uri: &str,
+    user: Option<&User>,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(user) = user {
+        let token = auth::create_token(&Claims::new(user.id, user.email.clone()))
+            .expect("JWT secret is set by `services`");
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let request = match body {
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .expect("request is well-formed");
+
+    let response = router.clone().oneshot(request).await.expect("infallible");
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body is readable");
+    let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+    (status, json)
+}
+
+pub fn new_user(name: &str) -> CreateUser {
+    CreateUser {
+        id: Uuid::new_v4(),
+        email: format!("{}@example.com", name),
+        username: Some(name.to_string()),
+        full_name: None,
+        avatar_url: None,
+        bio: None,
+    }
+}
+
+pub async fn create_user(repository: &dyn Repository, name: &str) -> User {
+    repository
+        .create_user(&new_user(name))
+        .await
+        .expect("user is new")
+}
+
+pub async fn create_post(repository: &dyn Repository, author: &User, title: &str) -> Uuid {
+    let post = CreatePost {
+        id: Uuid::new_v4(),
+        title: title.to_string(),
+        content: format!("{} content", title),
+        author_id: author.id,
+    };
+
+    repository.create_post(&post).await.expect("author exists")
+}
+
+// Transaction behaviour every repository has to share, run by each
+// repository's own tests. Users are named apart, so one database can run
+// them all.
+
+pub async fn committed_work_is_kept(repository: &dyn Repository) {
+    let user = new_user("committed");
+
+    let created = repository
+        .transaction(|tx| {
+            let user = &user;
+            Box::pin(async move { Ok(tx.create_user(user).await?.id) })
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(created, user.id);
+    assert!(repository.get_user_by_id(&user.id).await.unwrap().is_some());
+}
+
+pub async fn failed_work_is_rolled_back(repository: &dyn Repository) {
+    let user = new_user("rolled-back");
+
+    // The second insert fails on the primary key, taking the first with it
+    let result = repository
+        .transaction(|tx| {
+            let user = &user;
+            Box::pin(async move {
+                tx.create_user(user).await?;
+                tx.create_user(user).await?;
+                Ok(())
+            })
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert!(repository.get_user_by_id(&user.id).await.unwrap().is_none());
+}
+
+pub async fn nested_work_rolls_back_on_its_own(repository: &dyn Repository) {
+    let (outer, inner) = (new_user("outer"), new_user("inner"));
+
+    repository
+        .transaction(|tx| {
+            let (outer, inner) = (&outer, &inner);
+            Box::pin(async move {
+                tx.create_user(outer).await?;
+                let result: anyhow::Result<()> = tx
+                    .transaction(|tx| {
+                        Box::pin(async move {
+                            tx.create_user(inner).await?;
+                            anyhow::bail!("changed my mind")
+                        })
+                    })
+                    .await;
+                assert!(result.is_err());
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+    assert!(repository
+        .get_user_by_id(&outer.id)
+        .await
+        .unwrap()
+        .is_some());
+    assert!(repository
+        .get_user_by_id(&inner.id)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+// Repository behaviour the SQL backends implement in SQL (keyset queries,
+// the comment tree CTE, counter triggers, audit chain appends) and the
+// in-memory one in Rust, run against each of them the same way.
+
+async fn add_comment(
+    repository: &dyn Repository,
+    post_id: &Uuid,
+    author: &User,
+    parent_id: Option<Uuid>,
+) -> Uuid {
+    let comment = CreateComment {
+        id: Uuid::new_v4(),
+        post_id: *post_id,
+        parent_id,
+        author_id: author.id,
+        content: "Comment".to_string(),
+    };
+    repository.create_comment(&comment).await.unwrap().id
+}
+
+pub async fn posts_page_by_keyset(repository: &dyn Repository) {
+    let author = create_user(repository, "pager").await;
+    let mut ours = Vec::new();
+    for n in 0..5 {
+        ours.push(create_post(repository, &author, &format!("Page {}", n)).await);
+    }
+
+    // Forward through every page; other posts may be interleaved
+    let mut pages: Vec<CursorPage<Post>> = Vec::new();
+    let mut page = PageRequest::first(2);
+    loop {
+        let result = repository.get_posts_page(&page, None).await.unwrap();
+        assert!(result.data.len() <= 2);
+        let next = result.next_cursor.clone();
+        pages.push(result);
+
+        let Some(next) = next else {
+            break;
+        };
+        page.cursor = Cursor::decode(&next);
+    }
+    let seen: Vec<&Post> = pages.iter().flat_map(|page| &page.data).collect();
+
+    let keys: Vec<_> = seen.iter().map(|post| (post.created_at, post.id)).collect();
+    assert!(keys.windows(2).all(|pair| pair[0] > pair[1]), "newest first, no repeats");
+    for id in &ours {
+        assert_eq!(seen.iter().filter(|post| post.id == *id).count(), 1);
+    }
+
+    // Back from the second page lands on the first
+    let ids = |page: &CursorPage<Post>| page.data.iter().map(|post| post.id).collect::<Vec<_>>();
+    let previous = PageRequest {
+        cursor: Cursor::decode(pages[1].prev_cursor.as_ref().unwrap()),
+        limit: 2,
+    };
+    let first = repository.get_posts_page(&previous, None).await.unwrap();
+    assert_eq!(ids(&first), ids(&pages[0]));
+}
+
+pub async fn comment_trees_nest_and_slice(repository: &dyn Repository) {
+    let author = create_user(repository, "threader").await;
+    let post_id = create_post(repository, &author, "Thread").await;
+
+    // busy: reply (with a nested reply) and quiet_reply; quiet: nothing
+    let busy = add_comment(repository, &post_id, &author, None).await;
+    let quiet = add_comment(repository, &post_id, &author, None).await;
+    let reply = add_comment(repository, &post_id, &author, Some(busy)).await;
+    add_comment(repository, &post_id, &author, Some(reply)).await;
+    add_comment(repository, &post_id, &author, Some(busy)).await;
+
+    let request = CommentTreeRequest {
+        parent_id: None,
+        sort: CommentSort::Top,
+        max_depth: 2,
+        limit: 10,
+        offset: 0,
+        replies_limit: 1,
+    };
+    let tree = repository.get_comment_tree(&post_id, &request).await.unwrap();
+
+    let ids: Vec<Uuid> = tree.comments.iter().map(|node| node.comment.id).collect();
+    assert_eq!(ids, [busy, quiet]);
+    assert_eq!(tree.more_comments, 0);
+
+    let busy = &tree.comments[0];
+    assert_eq!((busy.depth, busy.reply_count, busy.more_replies), (1, 2, 1));
+    assert_eq!(busy.comment.author.username.as_deref(), Some("threader"));
+
+    // Cut off at the depth limit, the nested reply is only counted
+    let reply_node = &busy.replies[0];
+    assert_eq!(reply_node.comment.id, reply);
+    assert_eq!((reply_node.depth, reply_node.reply_count), (2, 1));
+    assert!(reply_node.replies.is_empty());
+    assert_eq!(reply_node.more_replies, 1);
+}
+
+pub async fn post_counters_follow_likes_and_comments(repository: &dyn Repository) {
+    let author = create_user(repository, "counted").await;
+    let fan = create_user(repository, "counting-fan").await;
+    let post_id = create_post(repository, &author, "Counted").await;
+
+    repository.like_post(&post_id, &author.id).await.unwrap();
+    repository.like_post(&post_id, &fan.id).await.unwrap();
+    let top = add_comment(repository, &post_id, &fan, None).await;
+    add_comment(repository, &post_id, &author, Some(top)).await;
+
+    let counts = |post: Post| (post.likes_count, post.comments_count);
+    let post = repository.get_post_by_id(&post_id, None).await.unwrap();
+    assert_eq!(counts(post.unwrap()), (2, 2));
+
+    // Deleting the top comment takes its reply with it
+    repository.unlike_post(&post_id, &fan.id).await.unwrap();
+    repository.delete_comment(&top).await.unwrap();
+    let post = repository.get_post_by_id(&post_id, None).await.unwrap();
+    assert_eq!(counts(post.unwrap()), (1, 0));
+}
+
+pub async fn audit_appends_extend_the_chain(repository: &dyn Repository) {
+    let actor = create_user(repository, "audited").await;
+
+    let actions = [AuditAction::Login, AuditAction::ProfileUpdated, AuditAction::PostDeleted];
+    for action in actions {
+        let event = NewAuditEvent {
+            occurred_at: Utc::now().trunc_subsecs(3),
+            action,
+            actor_id: Some(actor.id),
+            target_type: Some("user".to_string()),
+            target_id: Some(actor.id),
+            ip: Some("203.0.113.7".to_string()),
+            user_agent: None,
+            request_id: None,
+            before: None,
+            after: Some(serde_json::json!({ "full_name": "Audited" })),
+        };
+        let appended = repository.append_audit_event(&event).await.unwrap();
+        assert_eq!(appended.hash, appended.compute_hash());
+    }
+
+    let chain = repository.get_audit_chain(0, 100).await.unwrap();
+    let mut previous = (0, AUDIT_GENESIS_HASH.to_string());
+    for event in &chain {
+        assert_eq!(event.seq, previous.0 + 1);
+        assert_eq!(event.prev_hash, previous.1);
+        assert_eq!(event.hash, event.compute_hash());
+        previous = (event.seq, event.hash.clone());
+    }
+
+    let ours: Vec<&str> = chain
+        .iter()
+        .filter(|event| event.actor_id == Some(actor.id))
+        .map(|event| event.action.as_str())
+        .collect();
+    assert_eq!(ours, actions.map(|action| action.as_str()));
+}
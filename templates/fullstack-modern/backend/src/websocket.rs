@@ -13,7 +13,15 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
-use crate::{auth::Claims, services::Services};
+use crate::{
+    auth::Claims,
+    models::DomainEvent,
+    outbox::{Dedup, EventBus},
+    services::Services,
+};
+
+// Recent events the fan-out remembers to drop redeliveries
+const FAN_OUT_DEDUP_WINDOW: usize = 10_000;
 
 // WebSocket message types
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -132,7 +140,7 @@ impl ConnectionManager {
             let connections = self.connections.read().await;
             
             for user_id in participants {
-                if exclude_user.map_or(true, |excluded| excluded != *user_id) {
+                if exclude_user != Some(*user_id) {
                     if let Some(sender) = connections.get(user_id) {
                         let _ = sender.send(message.clone());
                     }
@@ -172,6 +180,69 @@ impl ConnectionManager {
     }
 }
 
+/// Pushes domain events to connected clients: new messages to the rest of the
+/// chat, notifications to their recipient.
+pub fn spawn_event_fan_out(events: &EventBus, connection_manager: Arc<ConnectionManager>) {
+    let mut receiver = events.subscribe();
+
+    tokio::spawn(async move {
+        let mut dedup = Dedup::new(FAN_OUT_DEDUP_WINDOW);
+
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("WebSocket fan-out fell behind and missed {} events", missed);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if !dedup.first_time(&event.dedup_key) {
+                continue;
+            }
+
+            match event.event {
+                DomainEvent::MessageSent {
+                    message_id,
+                    chat_id,
+                    sender_id,
+                    content,
+                    created_at,
+                } => {
+                    let message = WsMessage::ChatMessage {
+                        chat_id,
+                        message_id,
+                        content,
+                        sender_id,
+                        timestamp: created_at,
+                    };
+                    connection_manager
+                        .send_to_chat(&chat_id, message, Some(sender_id))
+                        .await;
+                }
+                DomainEvent::NotificationCreated {
+                    notification_id,
+                    user_id,
+                    notification_type,
+                    title,
+                    message,
+                    created_at,
+                } => {
+                    let notification = WsMessage::Notification {
+                        id: notification_id,
+                        title,
+                        message,
+                        notification_type,
+                        timestamp: created_at,
+                    };
+                    connection_manager.send_to_user(&user_id, notification).await;
+                }
+                DomainEvent::PostCreated { .. } | DomainEvent::UserUpdated { .. } => {}
+            }
+        }
+    });
+}
+
 // WebSocket handler
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,